@@ -4,6 +4,8 @@ SCRIPT_PATH=$(dirname "$0")
 SERVICE="acs.service"
 SERVICE_PATH=$SCRIPT_PATH/../$SERVICE
 TMP_SERVICE=`mktemp`
+SLEEP_HOOK_PATH=$SCRIPT_PATH/acs-sleep-hook
+SLEEP_HOOK_DIR=/usr/lib/systemd/system-sleep
 LONG_ARGS="help,acs-path:,user:"
 ACS_PATH=""
 SERVICE_ARGS="run --no-animation --quiet"
@@ -32,6 +34,14 @@ setup_service() {
   && systemctl daemon-reload
 }
 
+# Applies the daemon's settings again after a resume, with the same acs as the service
+setup_sleep_hook() {
+  mkdir -p $SLEEP_HOOK_DIR \
+  && sed -E "s@^( +)acs reapply@\1$ACS_PATH reapply@" $SLEEP_HOOK_PATH > $SLEEP_HOOK_DIR/acs \
+  && chmod 755 $SLEEP_HOOK_DIR/acs \
+  && chown root:root $SLEEP_HOOK_DIR/acs
+}
+
 start_service() {
   systemctl restart acs
   systemctl enable acs
@@ -82,6 +92,6 @@ else
   exit 1
 fi
 
-setup_service && start_service
+setup_service && setup_sleep_hook && start_service
 clean_up
 
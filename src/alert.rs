@@ -0,0 +1,194 @@
+#![forbid(unsafe_code)]
+//! Threshold checks for monitoring tools, used by `acs check`
+//!
+//! The exit code follows the Nagios plugin convention, 0 when every metric is fine, 1 when a
+//! warning threshold is crossed, 2 when a critical one is, and 3 when a metric can not be read.
+//! Checking several metrics at once gives the worst of them, a critical one wins over one that
+//! can not be read.
+
+use std::cmp::Ordering;
+
+/// How bad a reading is, ordered from best to worst, the values are the exit codes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    Ok = 0,
+    Warning = 1,
+    Unknown = 3,
+    Critical = 2,
+}
+
+impl Level {
+    pub fn exit_code(self) -> i32 {
+        self as i32
+    }
+
+    /// How bad it is, unknown goes under critical even though its exit code is higher
+    fn rank(self) -> u8 {
+        match self {
+            Level::Ok => 0,
+            Level::Warning => 1,
+            Level::Unknown => 2,
+            Level::Critical => 3,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Ok => "OK",
+            Level::Warning => "WARNING",
+            Level::Unknown => "UNKNOWN",
+            Level::Critical => "CRITICAL",
+        }
+    }
+}
+
+impl Ord for Level {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+impl PartialOrd for Level {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A limit on a metric, either a highest or a lowest allowed value
+#[derive(Clone, Debug, PartialEq)]
+pub struct Threshold {
+    /// The metric, e.g. temp
+    pub name: &'static str,
+    pub unit: &'static str,
+    /// If the value has to stay under the limits instead of over them
+    pub max: bool,
+    pub warning: Option<f32>,
+    pub critical: Option<f32>,
+}
+
+impl Threshold {
+    /// Past the limit means over it for a max and under it for a min
+    fn crossed(&self, value: f32, limit: Option<f32>) -> bool {
+        match limit {
+            Some(limit) if self.max => value > limit,
+            Some(limit) => value < limit,
+            None => false,
+        }
+    }
+
+    pub fn evaluate(&self, value: f32) -> Level {
+        if self.crossed(value, self.critical) {
+            Level::Critical
+        } else if self.crossed(value, self.warning) {
+            Level::Warning
+        } else {
+            Level::Ok
+        }
+    }
+}
+
+/// Evaluate every threshold against its reading, None for a reading that could not be taken
+///
+/// Returns the worst level and a one line summary, e.g.
+/// "WARNING - temp 87.0C over 85C, freq 2400MHz"
+pub fn evaluate_all(readings: &[(Threshold, Option<f32>)]) -> (Level, String) {
+    let mut worst = Level::Ok;
+    let mut parts = Vec::new();
+
+    for (threshold, value) in readings {
+        let value = match value {
+            Some(value) => value,
+            None => {
+                worst = worst.max(Level::Unknown);
+                parts.push(format!("{} could not be read", threshold.name));
+                continue;
+            }
+        };
+        let level = threshold.evaluate(*value);
+        worst = worst.max(level);
+
+        let limit = match level {
+            Level::Critical => threshold.critical,
+            Level::Warning => threshold.warning,
+            Level::Ok | Level::Unknown => None,
+        };
+        parts.push(match limit {
+            Some(limit) => format!(
+                "{} {:.1}{} {} {}{}",
+                threshold.name,
+                value,
+                threshold.unit,
+                if threshold.max { "over" } else { "under" },
+                limit,
+                threshold.unit
+            ),
+            None => format!("{} {:.1}{}", threshold.name, value, threshold.unit),
+        });
+    }
+
+    (worst, format!("{} - {}", worst.name(), parts.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp() -> Threshold {
+        Threshold {
+            name: "temp",
+            unit: "C",
+            max: true,
+            warning: Some(85.0),
+            critical: Some(95.0),
+        }
+    }
+
+    fn freq() -> Threshold {
+        Threshold {
+            name: "freq",
+            unit: "MHz",
+            max: false,
+            warning: Some(1200.0),
+            critical: None,
+        }
+    }
+
+    #[test]
+    fn threshold_evaluate_unit_test() {
+        assert_eq!(temp().evaluate(60.0), Level::Ok);
+        assert_eq!(temp().evaluate(85.0), Level::Ok);
+        assert_eq!(temp().evaluate(87.0), Level::Warning);
+        assert_eq!(temp().evaluate(96.0), Level::Critical);
+        assert_eq!(freq().evaluate(2400.0), Level::Ok);
+        assert_eq!(freq().evaluate(800.0), Level::Warning);
+    }
+
+    #[test]
+    fn evaluate_all_unit_test() {
+        assert_eq!(
+            evaluate_all(&[(temp(), Some(60.0)), (freq(), Some(2400.0))]),
+            (Level::Ok, "OK - temp 60.0C, freq 2400.0MHz".to_string())
+        );
+
+        // The worst level wins
+        let (level, summary) = evaluate_all(&[(temp(), Some(96.0)), (freq(), Some(800.0))]);
+        assert_eq!(level, Level::Critical);
+        assert_eq!(level.exit_code(), 2);
+        assert_eq!(
+            summary,
+            "CRITICAL - temp 96.0C over 95C, freq 800.0MHz under 1200MHz"
+        );
+
+        // A reading that can not be taken is unknown, unless another one is critical
+        let (level, summary) = evaluate_all(&[(temp(), None), (freq(), Some(800.0))]);
+        assert_eq!(level.exit_code(), 3);
+        assert_eq!(
+            summary,
+            "UNKNOWN - temp could not be read, freq 800.0MHz under 1200MHz"
+        );
+        let (level, _) = evaluate_all(&[(temp(), None), (freq(), Some(2400.0))]);
+        assert_eq!(level, Level::Unknown);
+        let (level, _) = evaluate_all(&[(temp(), Some(96.0)), (freq(), None)]);
+        assert_eq!(level, Level::Critical);
+    }
+}
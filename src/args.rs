@@ -1,18 +1,37 @@
 #![forbid(unsafe_code)]
+use std::io::Write;
+use std::path::Path;
+use std::process::exit;
 use std::{thread, time};
+use structopt::clap::Shell;
 use structopt::StructOpt;
 
+use super::alert::{evaluate_all, Level, Threshold};
+use super::bench::{run_bench, samples_to_csv};
 use super::config;
+use super::config::check::{check_config, Hardware};
 use super::config::init_config;
 use super::daemon;
 use super::daemon::daemon_init;
-use super::display::show_config;
+use super::display::{
+    print_bench_summary, print_differences, print_info, print_status, set_freq_unit, show_config,
+    Format, FreqUnit,
+};
+use super::gov::Tunable;
+use super::graph::svg::{graph_to_file, parse_metrics, render_svg, Sample};
 use super::graph::{get_graph_type, GraphType};
+use super::history::History;
 use super::interactive::interactive;
 use super::interface::{DaemonControl, DaemonController, Get, Getter, Interface, Set, Setter};
+use super::msr::VoltagePlane;
+use super::network::remote::fetch_status;
 use super::settings::Settings;
 use super::setup::check_config_dir_exists;
-use super::warn_user;
+use super::snapshot::{diff, SystemState};
+use super::sysfs;
+use super::system::{capabilities, check_cpu_freq, get_highest_temp, list_cpus};
+use super::theme::{self, Theme};
+use super::{print_done, print_error, warn_user};
 
 #[derive(StructOpt)]
 enum DaemonControlType {
@@ -57,6 +76,10 @@ enum GetType {
     Freq {
         #[structopt(short, long)]
         raw: bool,
+
+        /// Also show the average over this many readings, 0.1s apart, instead of avg_window
+        #[structopt(short, long)]
+        avg: Option<usize>,
     },
 
     /// Get whether turbo is enabled or not
@@ -92,6 +115,10 @@ enum GetType {
     Temp {
         #[structopt(short, long)]
         raw: bool,
+
+        /// Get the temperature of a thermal zone by type instead, e.g. x86_pkg_temp
+        #[structopt(short, long)]
+        zone: Option<String>,
     },
 
     /// The governors of the individual cores
@@ -101,12 +128,103 @@ enum GetType {
         raw: bool,
     },
 
+    /// The energy performance preference of the individual cores
+    #[structopt(name = "epp")]
+    Epp {
+        #[structopt(short, long)]
+        raw: bool,
+    },
+
+    /// Time spent in each idle state (C-state) of the individual cores
+    #[structopt(name = "cstates")]
+    CStates {
+        #[structopt(short, long)]
+        raw: bool,
+    },
+
     /// The battery condition in percentage
     #[structopt(name = "bat-cond")]
     BatCond {
         #[structopt(short, long)]
         raw: bool,
     },
+
+    /// The battery percentage charging stops at
+    #[structopt(name = "charge-limit")]
+    ChargeLimit {
+        #[structopt(short, long)]
+        raw: bool,
+    },
+
+    /// The clock of each GPU, for Intel and AMD GPUs
+    #[structopt(name = "gpu")]
+    Gpu {
+        #[structopt(short, long)]
+        raw: bool,
+    },
+
+    /// The speed of each fan and the fan controls
+    #[structopt(name = "fans")]
+    Fans {
+        #[structopt(short, long)]
+        raw: bool,
+    },
+
+    /// The tunables of the governors in use, e.g. the schedutil rate limit
+    #[structopt(name = "tunables")]
+    Tunables {
+        #[structopt(short, long)]
+        raw: bool,
+    },
+
+    /// The voltage offset programmed for each plane, Intel only
+    #[structopt(name = "voltage-offset")]
+    VoltageOffset {
+        #[structopt(short, long)]
+        raw: bool,
+    },
+
+    /// Package energy used since the running daemon started
+    #[structopt(name = "energy")]
+    Energy {
+        #[structopt(short, long)]
+        raw: bool,
+    },
+
+    /// The frequency, temperature, and governor of every core
+    #[structopt(name = "status")]
+    Status {
+        #[structopt(short, long)]
+        raw: bool,
+    },
+}
+
+#[derive(StructOpt)]
+enum ConfigType {
+    /// Check the config for problems, exits with 1 if there are any
+    #[structopt(name = "check")]
+    Check {
+        /// The config file to check instead of /etc/acs/acs.toml
+        #[structopt(short, long)]
+        file: Option<String>,
+    },
+}
+
+#[derive(StructOpt)]
+enum ListType {
+    /// The governors each core offers, grouped when cores offer the same ones
+    #[structopt(name = "governors")]
+    Governors {
+        #[structopt(short, long)]
+        raw: bool,
+    },
+
+    /// The thermal zones with their type names and temperatures
+    #[structopt(name = "thermal-zones")]
+    ThermalZones {
+        #[structopt(short, long)]
+        raw: bool,
+    },
 }
 
 #[derive(StructOpt)]
@@ -115,7 +233,152 @@ enum SetType {
     Gov {
         #[structopt()]
         value: String,
+
+        /// Energy performance preference to set along with the governor
+        #[structopt(long)]
+        epp: Option<String>,
     },
+
+    /// Set the energy performance preference, e.g. balance_power
+    #[structopt(name = "epp")]
+    Epp {
+        #[structopt()]
+        value: String,
+    },
+
+    /// Turn turbo "on" or "off"
+    #[structopt(name = "turbo")]
+    Turbo {
+        #[structopt(parse(try_from_str = parse_on_off))]
+        value: bool,
+    },
+
+    /// Set the highest frequency in MHz the cores can run at
+    #[structopt(name = "max-freq")]
+    MaxFreq {
+        #[structopt()]
+        value: i32,
+
+        /// Only set it for this core instead of every core
+        #[structopt(short, long)]
+        core: Option<usize>,
+    },
+
+    /// Set the lowest frequency in MHz the cores can run at
+    #[structopt(name = "min-freq")]
+    MinFreq {
+        #[structopt()]
+        value: i32,
+
+        /// Only set it for this core instead of every core
+        #[structopt(short, long)]
+        core: Option<usize>,
+    },
+
+    /// Take a core "offline" or bring it back "online", cpu0 always stays online
+    #[structopt(name = "core")]
+    Core {
+        #[structopt()]
+        core: usize,
+
+        #[structopt(parse(try_from_str = parse_online))]
+        online: bool,
+    },
+
+    /// Stop charging the battery at a percentage from 1 to 100
+    #[structopt(name = "charge-limit")]
+    ChargeLimit {
+        #[structopt()]
+        value: u8,
+    },
+
+    /// Set the shortest time in µs between frequency changes, needs schedutil as the governor
+    #[structopt(name = "schedutil-rate")]
+    SchedutilRate {
+        #[structopt()]
+        value: u64,
+    },
+
+    /// Set the load in percent from 1 to 100 over which ondemand goes to the highest frequency
+    #[structopt(name = "ondemand-threshold")]
+    OndemandThreshold {
+        #[structopt()]
+        value: u64,
+    },
+
+    /// Set how often in µs ondemand checks the load, needs ondemand as the governor
+    #[structopt(name = "ondemand-rate")]
+    OndemandRate {
+        #[structopt()]
+        value: u64,
+    },
+
+    /// Set a fan control from 'acs get fans' to "auto" or a duty cycle from 0 to 255
+    ///
+    /// Experimental, needs experimental_fan_control = true in the config
+    #[structopt(name = "fan")]
+    Fan {
+        /// The control, e.g. hwmon3/pwm1
+        #[structopt()]
+        pwm: String,
+
+        #[structopt(parse(try_from_str = parse_fan_value))]
+        value: FanValue,
+    },
+
+    /// Undervolt a voltage plane (core, gpu, cache, uncore, or analogio) by an offset in mV
+    ///
+    /// Experimental and Intel only, too large an offset freezes the machine. Needs
+    /// experimental_undervolt = true in the config and --i-understand-the-risks
+    #[structopt(
+        name = "voltage-offset",
+        setting = structopt::clap::AppSettings::AllowNegativeNumbers
+    )]
+    VoltageOffset {
+        #[structopt()]
+        plane: VoltagePlane,
+
+        /// The offset in mV, e.g. -50, 0 removes the undervolt
+        #[structopt()]
+        millivolts: i32,
+
+        #[structopt(long = "i-understand-the-risks")]
+        i_understand_the_risks: bool,
+    },
+}
+
+/// A duty cycle, or automatic control when None
+struct FanValue(Option<u8>);
+
+/// Parse "auto" or a duty cycle from 0 to 255
+fn parse_fan_value(value: &str) -> Result<FanValue, String> {
+    match value {
+        "auto" => Ok(FanValue(None)),
+        _ => value
+            .parse::<u8>()
+            .map(|v| FanValue(Some(v)))
+            .map_err(|_| format!("Expected \"auto\" or 0 to 255, got \"{}\"", value)),
+    }
+}
+
+/// Parse "on" or "off" as a bool
+fn parse_on_off(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "on" | "true" | "1" => Ok(true),
+        "off" | "false" | "0" => Ok(false),
+        _ => Err(format!("Expected \"on\" or \"off\", got \"{}\"", value)),
+    }
+}
+
+fn parse_online(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "online" | "on" | "1" => Ok(true),
+        "offline" | "off" | "0" => Ok(false),
+        _ => Err(format!(
+            "Expected \"online\" or \"offline\", got \"{}\"",
+            value
+        )),
+    }
 }
 
 #[derive(StructOpt)]
@@ -135,7 +398,28 @@ _
     \
     Automatic CPU frequency scaler and power saver"
 )]
+struct ACSArgs {
+    /// Output format of the get commands, "human" or "json"
+    #[structopt(long, global = true, default_value = "human")]
+    format: Format,
+
+    /// Print without colors, also the case when NO_COLOR is set or stdout is not a terminal
+    #[structopt(long, global = true)]
+    no_color: bool,
+
+    /// Unit frequencies are shown in, "mhz" or "ghz", --raw prints the kHz from sysfs instead
+    #[structopt(long, global = true, default_value = "mhz")]
+    freq_unit: FreqUnit,
+
+    /// Print what would be written to sysfs instead of writing it, the daemon logs it
+    #[structopt(long, global = true)]
+    dry_run: bool,
+
+    #[structopt(subcommand)]
+    command: ACSCommand,
+}
 
+#[derive(StructOpt)]
 enum ACSCommand {
     /// Controls interaction with a running daemon
     #[structopt(name = "daemon", alias = "d")]
@@ -145,6 +429,10 @@ enum ACSCommand {
         control: DaemonControlType,
     },
 
+    /// Ask the running daemon to apply its settings again, e.g. from a resume script
+    #[structopt(name = "reapply")]
+    Reapply,
+
     /// Get a specific value or status
     #[structopt(name = "get", alias = "g")]
     Get {
@@ -153,6 +441,13 @@ enum ACSCommand {
         get: GetType,
     },
 
+    /// List what is available on this system
+    #[structopt(name = "list", alias = "l")]
+    List {
+        #[structopt(subcommand)]
+        list: ListType,
+    },
+
     /// Set a specific value
     #[structopt(name = "set", alias = "s")]
     Set {
@@ -160,6 +455,95 @@ enum ACSCommand {
         set: SetType,
     },
 
+    /// Apply a profile from the config, or list the profiles if no name is given
+    #[structopt(name = "profile", alias = "p")]
+    Profile {
+        /// The name of the profile to apply
+        #[structopt()]
+        name: Option<String>,
+
+        #[structopt(short, long)]
+        raw: bool,
+    },
+
+    /// Sample values for a duration and write a graph of them to an SVG file
+    #[structopt(name = "graph")]
+    Graph {
+        /// The SVG file to write the graph to
+        #[structopt(short, long)]
+        output: String,
+
+        /// Seconds to sample for
+        #[structopt(short, long, default_value = "60")]
+        duration: u64,
+
+        /// Milliseconds between samples
+        #[structopt(short, long, default_value = "1000")]
+        interval: u64,
+
+        /// Comma separated metrics to graph, can be "freq", "usage", or "temp"
+        #[structopt(short, long, default_value = "freq,temp")]
+        metrics: String,
+
+        /// Graph the readings the daemon saved to history_file instead of sampling
+        #[structopt(long)]
+        history: bool,
+    },
+
+    /// Load every core and record frequency, temperature, and power to see how cooling holds up
+    #[structopt(name = "bench")]
+    Bench {
+        /// Seconds to run the load for
+        #[structopt(short, long, default_value = "60")]
+        duration: u64,
+
+        /// Threads to run busy loops on, defaults to one per core
+        #[structopt(short, long)]
+        threads: Option<usize>,
+
+        /// Milliseconds between samples
+        #[structopt(short, long, default_value = "1000")]
+        interval: u64,
+
+        /// Write the samples to a csv file
+        #[structopt(long = "csv")]
+        csv_file: Option<String>,
+    },
+
+    /// Show the cpu model, cpufreq driver, core count, and which features the hardware has
+    #[structopt(name = "info")]
+    Info {
+        #[structopt(short, long)]
+        raw: bool,
+    },
+
+    /// Save the governor, frequency limits, EPP, turbo, and charge limit to compare later
+    #[structopt(name = "snapshot")]
+    Snapshot {
+        /// The file to write the snapshot to, printed when left out
+        #[structopt(short, long)]
+        output: Option<String>,
+    },
+
+    /// Show what changed since a snapshot taken with 'acs snapshot'
+    #[structopt(name = "diff")]
+    Diff {
+        /// The snapshot file to compare to
+        #[structopt()]
+        snapshot: String,
+
+        #[structopt(short, long)]
+        raw: bool,
+    },
+
+    /// Print the completion script of a shell, e.g. 'acs completions bash > /etc/bash_completion.d/acs'
+    #[structopt(name = "completions")]
+    Completions {
+        /// The shell to complete in
+        #[structopt(possible_values = &Shell::variants(), case_insensitive = true)]
+        shell: Shell,
+    },
+
     /// Interactive mode for auto clock speed commands
     #[structopt(name = "interactive", alias = "i")]
     Interactive {},
@@ -172,6 +556,47 @@ enum ACSCommand {
     #[structopt(name = "initconfig")]
     InitConfig {},
 
+    /// Show the status of a daemon on another machine that serves it with remote_status_port
+    #[structopt(name = "remote-status")]
+    RemoteStatus {
+        /// Where the daemon serves its status, e.g. nas.local:7171
+        #[structopt()]
+        address: String,
+
+        #[structopt(short, long)]
+        raw: bool,
+    },
+
+    /// Take one reading and compare it to thresholds, for monitoring tools like Nagios
+    ///
+    /// Prints a one line summary and exits with 0 when every metric is fine, 1 when a warning
+    /// threshold is crossed, 2 when a critical one is, and 3 when a metric can not be read
+    #[structopt(name = "check")]
+    Check {
+        /// Warn when the temperature in C of the hottest core is over this
+        #[structopt(long)]
+        max_temp: Option<f32>,
+
+        /// Critical when the temperature in C of the hottest core is over this
+        #[structopt(long)]
+        max_temp_crit: Option<f32>,
+
+        /// Warn when the average frequency in MHz is under this
+        #[structopt(long)]
+        min_freq: Option<f32>,
+
+        /// Critical when the average frequency in MHz is under this
+        #[structopt(long)]
+        min_freq_crit: Option<f32>,
+    },
+
+    /// Work with the config file
+    #[structopt(name = "config")]
+    Config {
+        #[structopt(subcommand)]
+        config: ConfigType,
+    },
+
     /// Run the daemon, this checks and edit your cpu's speed
     #[structopt(name = "run")]
     Run {
@@ -253,16 +678,37 @@ enum ACSCommand {
     },
 }
 
+/// The completion script of every command and flag for a shell
+pub fn completions(shell: Shell) -> Vec<u8> {
+    let mut script = Vec::new();
+    ACSArgs::clap().gen_completions_to("acs", shell, &mut script);
+    script
+}
+
 pub fn parse_args(config: config::Config) {
     let set_settings = Settings::default();
 
+    let args = ACSArgs::from_args();
+
+    if args.no_color {
+        theme::set_color(false);
+    }
+    set_freq_unit(args.freq_unit);
+    sysfs::set_dry_run(args.dry_run);
+    match Theme::from_config(config.theme.as_deref()) {
+        Ok(t) => theme::set_theme(t),
+        Err(e) => warn_user!(format!("Invalid theme in config: {:?}", e).trim_end()),
+    }
+
     let int = Interface {
         set: Set {},
-        get: Get {},
+        get: Get {
+            format: args.format,
+        },
         dec: DaemonControl {},
     };
 
-    match ACSCommand::from_args() {
+    match args.command {
         ACSCommand::Daemon { control } => match control {
             DaemonControlType::Disable => int.dec.disable(),
             DaemonControlType::Enable => int.dec.enable(),
@@ -270,8 +716,10 @@ pub fn parse_args(config: config::Config) {
             DaemonControlType::Toggle => int.dec.toggle(),
         },
 
+        ACSCommand::Reapply => int.dec.reapply(),
+
         ACSCommand::Get { get } => match get {
-            GetType::Freq { raw } => int.get.freq(raw),
+            GetType::Freq { raw, avg } => int.get.freq(raw, avg.unwrap_or(config.avg_window)),
             GetType::Power { raw } => int.get.power(raw),
             GetType::Usage { raw, delay } => int.get.usage(raw, delay),
             GetType::Thermal { raw } => int.get.thermal(raw),
@@ -279,17 +727,268 @@ pub fn parse_args(config: config::Config) {
             GetType::AvailableGovs { raw } => int.get.available_govs(raw),
             GetType::CPUs { raw } => int.get.cpus(raw),
             GetType::Speeds { raw } => int.get.speeds(raw),
-            GetType::Temp { raw } => int.get.temp(raw),
+            GetType::Temp { raw, zone } => int.get.temp(raw, zone),
             GetType::Govs { raw } => int.get.govs(raw),
+            GetType::Epp { raw } => int.get.epp(raw),
+            GetType::CStates { raw } => int.get.cstates(raw),
             GetType::BatCond { raw } => int.get.bat_cond(raw),
+            GetType::ChargeLimit { raw } => int.get.charge_limit(raw),
+            GetType::Gpu { raw } => int.get.gpu(raw),
+            GetType::Fans { raw } => int.get.fans(raw),
+            GetType::Tunables { raw } => int.get.tunables(raw),
+            GetType::VoltageOffset { raw } => int.get.voltage_offset(raw),
+            GetType::Energy { raw } => int.get.energy(raw),
+            GetType::Status { raw } => int.get.status(raw),
+        },
+
+        ACSCommand::List { list } => match list {
+            ListType::Governors { raw } => int.get.governors_by_core(raw),
+            ListType::ThermalZones { raw } => int.get.thermal(raw),
         },
 
         ACSCommand::Set { set } => match set {
-            SetType::Gov { value } => int.set.gov(value, config, set_settings),
+            SetType::Gov { value, epp } => {
+                int.set.gov(value, config, set_settings);
+                if let Some(epp) = epp {
+                    int.set.epp(epp);
+                }
+            }
+            SetType::Epp { value } => int.set.epp(value),
+            SetType::Turbo { value } => int.set.turbo(value),
+            SetType::MaxFreq { value, core } => int.set.max_freq(value, core),
+            SetType::MinFreq { value, core } => int.set.min_freq(value, core),
+            SetType::Core { core, online } => int.set.core_online(core, online),
+            SetType::ChargeLimit { value } => int.set.charge_limit(value),
+            SetType::SchedutilRate { value } => int.set.tunable(Tunable::SchedutilRateLimit, value),
+            SetType::OndemandThreshold { value } => {
+                int.set.tunable(Tunable::OndemandUpThreshold, value)
+            }
+            SetType::OndemandRate { value } => {
+                int.set.tunable(Tunable::OndemandSamplingRate, value)
+            }
+            SetType::Fan { pwm, value } => int.set.fan(pwm, value.0, &config),
+            SetType::VoltageOffset {
+                plane,
+                millivolts,
+                i_understand_the_risks,
+            } => int
+                .set
+                .voltage_offset(plane, millivolts, i_understand_the_risks, &config),
+        },
+
+        ACSCommand::Profile { name, raw } => match name {
+            Some(name) => int.set.profile(name, &config),
+            None => int.get.profiles(&config, raw),
+        },
+
+        ACSCommand::Bench {
+            duration,
+            threads,
+            interval,
+            csv_file,
+        } => {
+            let threads = threads.unwrap_or_else(|| {
+                thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(1)
+            });
+
+            // On stderr so the json summary is all that is on stdout
+            eprintln!("Running {} threads for {} seconds...", threads, duration);
+            let (samples, summary) = match run_bench(
+                time::Duration::from_secs(duration),
+                threads,
+                time::Duration::from_millis(interval),
+            ) {
+                Ok(result) => result,
+                Err(e) => {
+                    print_error!(format!("Could not run the benchmark, {:?}", e));
+                    return;
+                }
+            };
+
+            print_bench_summary(&summary, args.format);
+
+            if let Some(csv_file) = csv_file {
+                match samples_to_csv(&samples, Path::new(&csv_file)) {
+                    Ok(_) => print_done!(format!("Wrote samples to {}", csv_file)),
+                    Err(e) => print_error!(format!("Could not write samples, {:?}", e)),
+                }
+            }
+        }
+
+        ACSCommand::Graph {
+            output,
+            duration,
+            interval,
+            metrics,
+            history,
+        } => {
+            let metrics = match parse_metrics(&metrics) {
+                Ok(metrics) => metrics,
+                Err(e) => {
+                    print_error!(format!("{:?}", e));
+                    return;
+                }
+            };
+
+            if history {
+                let path = match &config.history_file {
+                    Some(path) => path.clone(),
+                    None => {
+                        print_error!(
+                            "Set history_file in the config for the daemon to keep a history"
+                        );
+                        return;
+                    }
+                };
+                let max_age = time::Duration::from_secs(config.history_max_age_hours * 3600);
+                let result = History::load(Path::new(&path), max_age).and_then(|history| {
+                    let samples: Vec<Sample> = history.samples().iter().cloned().collect();
+                    std::fs::write(&output, render_svg(&metrics, &samples))?;
+                    Ok(samples.len())
+                });
+                match result {
+                    Ok(count) => {
+                        print_done!(format!(
+                            "Wrote {} readings from {} to {}",
+                            count, path, output
+                        ))
+                    }
+                    Err(e) => print_error!(format!("Could not graph the history, {:?}", e)),
+                }
+                return;
+            }
+
+            println!("Sampling for {} seconds...", duration);
+            match graph_to_file(
+                Path::new(&output),
+                &metrics,
+                time::Duration::from_secs(duration),
+                time::Duration::from_millis(interval),
+            ) {
+                Ok(_) => print_done!(format!("Wrote graph to {}", output)),
+                Err(e) => print_error!(format!("Could not write graph, {:?}", e)),
+            }
+        }
+
+        ACSCommand::Completions { shell } => {
+            if let Err(e) = std::io::stdout().write_all(&completions(shell)) {
+                print_error!(format!("Could not print the completions, {:?}", e));
+                exit(1);
+            }
+        }
+
+        ACSCommand::Info { raw } => print_info(&capabilities(), raw, args.format),
+
+        ACSCommand::Snapshot { output } => {
+            let state = SystemState::capture();
+            match output {
+                Some(output) => match state.save(Path::new(&output)) {
+                    Ok(_) => print_done!(format!("Wrote snapshot to {}", output)),
+                    Err(e) => print_error!(format!("Could not write snapshot, {:?}", e)),
+                },
+                None => println!("{}", state.to_json()),
+            }
+        }
+
+        ACSCommand::Diff { snapshot, raw } => match SystemState::load(Path::new(&snapshot)) {
+            Ok(before) => {
+                print_differences(&diff(&before, &SystemState::capture()), raw, args.format)
+            }
+            Err(e) => {
+                print_error!(format!("Could not read snapshot {}, {:?}", snapshot, e));
+                exit(1);
+            }
         },
 
         ACSCommand::ShowConfig {} => show_config(&config),
         ACSCommand::InitConfig {} => init_config(),
+
+        ACSCommand::Config {
+            config: ConfigType::Check { file },
+        } => {
+            let path = file.unwrap_or_else(config::config_path);
+            let text = match config::read_config_text(Path::new(&path)) {
+                Ok(text) => text,
+                Err(e) => {
+                    print_error!(format!(
+                        "Could not read {}, {}",
+                        path,
+                        format!("{:?}", e).trim_end()
+                    ));
+                    exit(1);
+                }
+            };
+
+            match check_config(&text, &Hardware::detect()) {
+                Ok(problems) if problems.is_empty() => print_done!(format!("{} is valid", path)),
+                Ok(problems) => {
+                    for problem in &problems {
+                        println!("{}", problem);
+                    }
+                    print_error!(format!("Found {} problems in {}", problems.len(), path));
+                    exit(1);
+                }
+                Err(e) => {
+                    print_error!(format!("{:?}", e).trim_end());
+                    exit(1);
+                }
+            }
+        }
+        ACSCommand::Check {
+            max_temp,
+            max_temp_crit,
+            min_freq,
+            min_freq_crit,
+        } => {
+            let cpus = list_cpus();
+            let mut readings = Vec::new();
+            if max_temp.is_some() || max_temp_crit.is_some() {
+                let threshold = Threshold {
+                    name: "temp",
+                    unit: "C",
+                    max: true,
+                    warning: max_temp,
+                    critical: max_temp_crit,
+                };
+                // The hottest core, a core without a sensor reads 0 or under
+                let temp = get_highest_temp(&cpus);
+                readings.push((threshold, (temp > 0).then_some(temp as f32 / 1000.0)));
+            }
+            if min_freq.is_some() || min_freq_crit.is_some() {
+                let threshold = Threshold {
+                    name: "freq",
+                    unit: "MHz",
+                    max: false,
+                    warning: min_freq,
+                    critical: min_freq_crit,
+                };
+                let freq = check_cpu_freq(&cpus) / 1000.0;
+                readings.push((threshold, (freq > 0.0).then_some(freq)));
+            }
+
+            if readings.is_empty() {
+                println!("UNKNOWN - give at least one threshold, see 'acs check --help'");
+                exit(Level::Unknown.exit_code());
+            }
+
+            let (level, summary) = evaluate_all(&readings);
+            println!("{}", summary);
+            exit(level.exit_code());
+        }
+
+        ACSCommand::RemoteStatus { address, raw } => match fetch_status(&address) {
+            Ok(status) => print_status(status, raw, args.format),
+            Err(e) => {
+                print_error!(format!(
+                    "Could not get the status from {}, {:?}",
+                    address, e
+                ));
+                exit(1);
+            }
+        },
+
         ACSCommand::Interactive {} => interactive(),
 
         // Run command
@@ -392,3 +1091,20 @@ pub fn parse_args(config: config::Config) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completions_unit_test() {
+        let subcommands = ["get", "set", "run", "monit", "snapshot", "completions"];
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script = String::from_utf8(completions(shell)).unwrap();
+            for command in subcommands {
+                assert!(script.contains(command), "{} in {}", command, shell);
+            }
+            assert!(script.contains("dry-run"));
+        }
+    }
+}
@@ -0,0 +1,270 @@
+#![forbid(unsafe_code)]
+//! Load every core with busy loops and record how the frequency and temperature hold up
+//!
+//! `acs bench` is meant for checking cooling, a frequency that drops while the load stays the
+//! same, or a thermal throttle counter that goes up, means the cpu could not keep its clocks.
+use crate::cpu::{Speed, CPU};
+use crate::csv::Writable;
+use crate::error::Error;
+use crate::power::rapl::{to_watts, Rapl};
+use crate::system::{check_cpu_freq, get_highest_temp, list_cpus, read_core_id, read_package_id};
+use crate::thermal::{read_throttle_counter, THROTTLE_COUNTERS};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::hint::black_box;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::{thread, time};
+
+pub const BENCH_CSV_HEADER: &str = "seconds,freq_mhz,temp_c,power_w";
+
+/// How far under its highest point the frequency has to drop to count as throttling, 0.1 is 10%
+const THROTTLE_DROP: f64 = 0.1;
+
+/// The readings taken at one point of the run
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BenchSample {
+    /// Seconds since the load started
+    pub seconds: f64,
+    /// Average frequency of every core
+    pub freq_mhz: f64,
+    /// Highest core temperature
+    pub temp_c: f64,
+    /// Package power, when there is an energy counter
+    pub power_w: Option<f64>,
+}
+
+impl Writable for BenchSample {
+    fn to_csv(&self) -> String {
+        format!(
+            "{:.2},{:.0},{:.1},{}\n",
+            self.seconds,
+            self.freq_mhz,
+            self.temp_c,
+            self.power_w
+                .map(|p| format!("{:.2}", p))
+                .unwrap_or_default()
+        )
+    }
+}
+
+/// The stats printed at the end of a run
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BenchSummary {
+    pub threads: usize,
+    pub samples: usize,
+    pub min_freq_mhz: f64,
+    pub max_freq_mhz: f64,
+    pub avg_freq_mhz: f64,
+    pub peak_temp_c: f64,
+    pub avg_power_w: Option<f64>,
+    /// How much the thermal throttle counters went up, when the cpu has them
+    pub throttle_events: Option<u64>,
+    pub throttled: bool,
+}
+
+/// Work out the stats of a run
+///
+/// With no throttle counters, a frequency that drops well under the highest it reached counts as
+/// throttling since the load does not change during the run
+pub fn summarize(
+    samples: &[BenchSample],
+    threads: usize,
+    throttle_events: Option<u64>,
+) -> BenchSummary {
+    let freqs: Vec<f64> = samples.iter().map(|s| s.freq_mhz).collect();
+    let min_freq = freqs.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max_freq = freqs.iter().cloned().fold(0.0, f64::max);
+    let avg_freq = if freqs.is_empty() {
+        0.0
+    } else {
+        freqs.iter().sum::<f64>() / freqs.len() as f64
+    };
+
+    let powers: Vec<f64> = samples.iter().filter_map(|s| s.power_w).collect();
+    let avg_power = if powers.is_empty() {
+        None
+    } else {
+        Some(powers.iter().sum::<f64>() / powers.len() as f64)
+    };
+
+    let throttled = match throttle_events {
+        Some(events) => events > 0,
+        None => !samples.is_empty() && min_freq < max_freq * (1.0 - THROTTLE_DROP),
+    };
+
+    BenchSummary {
+        threads,
+        samples: samples.len(),
+        min_freq_mhz: if min_freq.is_finite() { min_freq } else { 0.0 },
+        max_freq_mhz: max_freq,
+        avg_freq_mhz: avg_freq,
+        peak_temp_c: samples.iter().map(|s| s.temp_c).fold(0.0, f64::max),
+        avg_power_w: avg_power,
+        throttle_events,
+        throttled,
+    }
+}
+
+/// Total of the thermal throttle counters, only some cpus, e.g. Intel, have them
+///
+/// Every thread shows the core counter of its core and the package counter of its package, so
+/// each counter is only added once
+pub fn read_throttle_count() -> Option<u64> {
+    let mut total: Option<u64> = None;
+    let mut counted = HashSet::new();
+    for cpu in list_cpus() {
+        let number = cpu.number as usize;
+        let package = read_package_id(number).ok();
+        let core = read_core_id(number).unwrap_or(number as u32);
+        let owners = [(package, Some(core)), (package, None)];
+        for (counter, owner) in THROTTLE_COUNTERS.into_iter().zip(owners) {
+            if !counted.insert((counter, owner)) {
+                continue;
+            }
+            if let Some(count) = read_throttle_counter(&cpu.name, counter) {
+                total = Some(total.unwrap_or(0) + count);
+            }
+        }
+    }
+    total
+}
+
+/// Spin a busy loop on each thread until stop gets set
+fn start_load(threads: usize, stop: &Arc<AtomicBool>) -> Vec<thread::JoinHandle<()>> {
+    (0..threads)
+        .map(|_| {
+            let stop = Arc::clone(stop);
+            thread::spawn(move || {
+                let mut x: u64 = 1;
+                while !stop.load(Ordering::Relaxed) {
+                    x = black_box(x.wrapping_mul(6364136223846793005).wrapping_add(1));
+                }
+            })
+        })
+        .collect()
+}
+
+/// Read the frequency and temperature of every core, a temperature that can not be read is 0
+fn read_cpus(cpus: &mut [CPU]) {
+    for cpu in cpus.iter_mut() {
+        cpu.get_cur();
+        cpu.get_temp().ok();
+    }
+}
+
+/// Load the cpu with busy loops for the duration, reading a sample every interval
+pub fn run_bench(
+    duration: time::Duration,
+    threads: usize,
+    interval: time::Duration,
+) -> Result<(Vec<BenchSample>, BenchSummary), Error> {
+    let mut cpus = list_cpus();
+    if cpus.is_empty() {
+        return Err(Error::HdwNotFound);
+    }
+
+    let rapl = Rapl::new().ok();
+    let mut last_energy = rapl
+        .as_ref()
+        .and_then(|r| r.read_energy().ok())
+        .map(|e| (e, time::Instant::now()));
+    let throttle_start = read_throttle_count();
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let workers = start_load(threads, &stop);
+
+    let start = time::Instant::now();
+    let mut samples = Vec::new();
+    while start.elapsed() < duration {
+        thread::sleep(interval);
+        read_cpus(&mut cpus);
+
+        let power_w = match (&rapl, last_energy) {
+            (Some(rapl), Some((last, at))) => rapl.read_energy().ok().map(|energy| {
+                let watts = to_watts(rapl.energy_delta(last, energy), at.elapsed());
+                last_energy = Some((energy, time::Instant::now()));
+                watts as f64
+            }),
+            _ => None,
+        };
+
+        samples.push(BenchSample {
+            seconds: start.elapsed().as_secs_f64(),
+            freq_mhz: check_cpu_freq(&cpus) as f64 / 1000.0,
+            temp_c: get_highest_temp(&cpus) as f64 / 1000.0,
+            power_w,
+        });
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    for worker in workers {
+        worker.join().ok();
+    }
+
+    let throttle_events = match (throttle_start, read_throttle_count()) {
+        (Some(before), Some(after)) => Some(after.saturating_sub(before)),
+        _ => None,
+    };
+
+    let summary = summarize(&samples, threads, throttle_events);
+    Ok((samples, summary))
+}
+
+/// Write the samples to a csv file with a header
+pub fn samples_to_csv(samples: &[BenchSample], path: &Path) -> Result<(), Error> {
+    let rows: String = samples.iter().map(|s| s.to_csv()).collect();
+    fs::write(path, format!("{}\n{}", BENCH_CSV_HEADER, rows))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(freq_mhz: f64, temp_c: f64, power_w: Option<f64>) -> BenchSample {
+        BenchSample {
+            seconds: 0.0,
+            freq_mhz,
+            temp_c,
+            power_w,
+        }
+    }
+
+    #[test]
+    fn summarize_unit_test() {
+        let samples = vec![
+            sample(4000.0, 60.0, Some(30.0)),
+            sample(3900.0, 80.0, Some(20.0)),
+            sample(3200.0, 95.0, None),
+        ];
+
+        let summary = summarize(&samples, 8, None);
+        assert_eq!(summary.samples, 3);
+        assert_eq!(summary.min_freq_mhz, 3200.0);
+        assert_eq!(summary.max_freq_mhz, 4000.0);
+        assert_eq!(summary.avg_freq_mhz, 3700.0);
+        assert_eq!(summary.peak_temp_c, 95.0);
+        assert_eq!(summary.avg_power_w, Some(25.0));
+        assert!(summary.throttled);
+
+        // The counters are trusted over the frequency when the cpu has them
+        assert!(!summarize(&samples, 8, Some(0)).throttled);
+        assert!(!summarize(&samples[..2], 8, None).throttled);
+
+        let empty = summarize(&[], 1, None);
+        assert_eq!(empty.min_freq_mhz, 0.0);
+        assert!(!empty.throttled);
+    }
+
+    #[test]
+    fn bench_sample_to_csv_unit_test() {
+        assert_eq!(
+            sample(3200.4, 71.3, Some(15.5)).to_csv(),
+            "0.00,3200,71.3,15.50\n"
+        );
+        assert_eq!(sample(800.0, 40.0, None).to_csv(), "0.00,800,40.0,\n");
+    }
+}
@@ -31,6 +31,8 @@ use autoclockspeed::config::get_config;
 fn main() {
     env_logger::init();
 
+    autoclockspeed::theme::detect();
+
     autoclockspeed::setup::setup();
 
     let config: autoclockspeed::config::Config = get_config();
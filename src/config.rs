@@ -1,13 +1,17 @@
 #![forbid(unsafe_code)]
 use super::daemon::State;
 use super::{print_done, warn_user};
+use crate::error::Error;
 use crate::print_error;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::default::Default;
 use std::fmt;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{ErrorKind, Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+pub mod check;
 
 /// Return the local config path
 pub fn config_path() -> String {
@@ -32,13 +36,54 @@ impl Default for Config {
             powersave_under: 20,
             overheat_threshold: 80,
             high_cpu_threshold: 50,
+            low_cpu_threshold: 50,
             high_cpu_time_needed: 15,
+            min_governor_hold_secs: 0,
+            adaptive_polling: false,
+            min_poll_ms: 250,
+            max_poll_ms: 5000,
             active_rules: vec![
                 State::BatteryLow,
                 State::LidClosed,
                 State::Charging,
                 State::CpuUsageHigh,
             ],
+            performance_epp: None,
+            powersave_epp: None,
+            charge_threshold: None,
+            metrics_port: None,
+            remote_status_port: None,
+            remote_status_bind: "127.0.0.1".to_string(),
+            disable_turbo_on_battery: false,
+            battery_max_freq: None,
+            restore_on_exit: true,
+            notify_temp: None,
+            csv_max_size_mb: None,
+            csv_max_files: 5,
+            avg_window: 0,
+            log_backend: None,
+            theme: None,
+            sysfs_debounce_ms: 0,
+            watchdog_secs: 120,
+            watchdog_exit: false,
+            history_file: None,
+            history_max_age_hours: 24,
+            history_flush_secs: 60,
+            experimental_fan_control: false,
+            experimental_undervolt: false,
+            thermal_zones: Vec::new(),
+            active_throttle: false,
+            throttle_temp: 90,
+            throttle_recover_temp: 80,
+            throttle_step_mhz: 200,
+            schedule: BTreeMap::new(),
+            core_governors: BTreeMap::new(),
+            app_governors: BTreeMap::new(),
+            hooks: BTreeMap::new(),
+            influx: None,
+            battery: FreqLimits::default(),
+            ac: FreqLimits::default(),
+            profiles: HashMap::new(),
         }
     }
 }
@@ -111,18 +156,214 @@ pub struct Config {
     pub powersave_under: i8,
     /// ACS in edit mode will activate powersave if the temperature reaches this value
     pub overheat_threshold: i8,
+    /// ACS in edit mode will activate performance if the cpu usage stays above this value
     pub high_cpu_threshold: i8,
+    /// Once the cpu usage was high, it has to drop under this value to count as low again
+    pub low_cpu_threshold: i8,
     pub high_cpu_time_needed: u64,
+    /// The minimum amount of seconds a governor is kept before the daemon switches it again
+    pub min_governor_hold_secs: u64,
+    /// Poll slower while readings are stable and faster when they change, instead of using the
+    /// delay settings
+    pub adaptive_polling: bool,
+    /// The shortest time between polls in milliseconds when adaptive_polling is on
+    pub min_poll_ms: u64,
+    /// The longest time between polls in milliseconds when adaptive_polling is on
+    pub max_poll_ms: u64,
     pub active_rules: Vec<State>,
+    /// Energy performance preference set along with the performance governor
+    pub performance_epp: Option<String>,
+    /// Energy performance preference set along with the powersave governor
+    pub powersave_epp: Option<String>,
+    /// Stop charging the battery at this percentage, the daemon keeps it applied
+    pub charge_threshold: Option<u8>,
+    /// Port the daemon serves Prometheus metrics on, needs the metrics feature
+    pub metrics_port: Option<u16>,
+    /// Port the daemon serves its status on for 'acs remote-status', needs the remote feature
+    pub remote_status_port: Option<u16>,
+    /// Address the status is served on, only this machine can connect unless it is changed
+    pub remote_status_bind: String,
+    /// Turn turbo off while on battery and back to how it was once charging
+    pub disable_turbo_on_battery: bool,
+    /// Highest frequency in MHz the cores can run at while on battery, lifted once charging
+    ///
+    /// The same as `max_freq` in the battery table, which is used when both are set
+    pub battery_max_freq: Option<i32>,
+    /// Put the governors and turbo back to how they were before the daemon started when it stops
+    pub restore_on_exit: bool,
+    /// Send a desktop notification when the temperature goes over this and when it cools back
+    /// down, needs the notify feature
+    pub notify_temp: Option<i8>,
+    /// Size in MB at which the csv log is rotated instead of logging being stopped
+    pub csv_max_size_mb: Option<u64>,
+    /// How many rotated csv logs are kept next to the active one
+    pub csv_max_files: u32,
+    /// How many readings the average frequency is shown over next to the current one, 0 hides it
+    pub avg_window: usize,
+    /// "stdout" or "journald", journald is picked on its own when running under systemd
+    pub log_backend: Option<String>,
+    /// Colors of the terminal output, "dark", "light", or "mono"
+    pub theme: Option<String>,
+    /// Milliseconds a value written to a sysfs file is trusted to still be there, so writing it
+    /// again is skipped without reading the file, 0 reads the file every time
+    pub sysfs_debounce_ms: u64,
+    /// Seconds the daemon loop can go without finishing an iteration before it counts as stuck,
+    /// 0 turns the check off
+    pub watchdog_secs: u64,
+    /// Exit with 1 when the daemon loop is stuck so a service manager restarts acs
+    pub watchdog_exit: bool,
+    /// File the daemon keeps its readings in across restarts, off when left out
+    pub history_file: Option<String>,
+    /// Hours of readings kept in the history
+    pub history_max_age_hours: u64,
+    /// Seconds between writes of the history file
+    pub history_flush_secs: u64,
+    /// Allow `acs set fan`, experimental since a fan left spinning too slow can overheat the
+    /// hardware
+    pub experimental_fan_control: bool,
+    /// Allow `acs set voltage-offset`, too large an undervolt freezes the machine
+    pub experimental_undervolt: bool,
+    /// Thermal zone types whose highest temperature is used for overheating, "all" uses every zone
+    /// Leaving it empty uses the temperature of the cores
+    pub thermal_zones: Vec<String>,
+    /// Step the max frequency down while too hot instead of only reporting it
+    pub active_throttle: bool,
+    /// With active_throttle, the max frequency goes down a step each poll while over this
+    pub throttle_temp: i8,
+    /// The max frequency goes back up a step each poll once the temperature is under this
+    pub throttle_recover_temp: i8,
+    /// How far in MHz the max frequency moves each step
+    pub throttle_step_mhz: i32,
+    /// Profiles applied during time windows, e.g. "22:00-07:00" = "powersave"
+    /// The profile is applied when a window starts, so a profile set by hand lasts until the next one
+    pub schedule: BTreeMap<String, String>,
+    /// Governors pinned to ranges of cores, e.g. "0-3" = "performance"
+    /// Cores not listed follow the governor chosen by the daemon
+    pub core_governors: BTreeMap<String, String>,
+    /// Governors used while a process is running, e.g. "cargo" = "performance"
+    /// A rule matches the process name or part of its command line, the most performant one wins
+    pub app_governors: BTreeMap<String, String>,
+    /// Scripts the daemon runs on an event, e.g. on_battery = "/usr/local/bin/on-battery.sh"
+    pub hooks: BTreeMap<String, String>,
+    /// Where the daemon pushes its readings in InfluxDB line protocol, off when left out
+    pub influx: Option<InfluxConfig>,
+    /// Frequency limits applied while on battery, lifted once plugged in
+    pub battery: FreqLimits,
+    /// Frequency limits applied while plugged in, lifted once on battery
+    pub ac: FreqLimits,
+    /// Named sets of settings that can be applied with 'acs profile <name>'
+    pub profiles: HashMap<String, Profile>,
+}
+
+impl Config {
+    /// The frequency limits for a power source
+    pub fn freq_limits(&self, charging: bool) -> FreqLimits {
+        if charging {
+            self.ac.clone()
+        } else {
+            FreqLimits {
+                max_freq: self.battery.max_freq.or(self.battery_max_freq),
+                ..self.battery.clone()
+            }
+        }
+    }
+}
+
+/// The lowest and highest frequency in MHz for the cores on one power source, a limit that is
+/// left out is not changed
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FreqLimits {
+    pub min_freq: Option<i32>,
+    pub max_freq: Option<i32>,
+}
+
+impl FreqLimits {
+    pub fn is_empty(&self) -> bool {
+        self.min_freq.is_none() && self.max_freq.is_none()
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// An InfluxDB (or Telegraf) endpoint to push readings to
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct InfluxConfig {
+    /// Base address of the server, e.g. "http://localhost:8086"
+    pub url: String,
+    /// Database on InfluxDB 1, or bucket on InfluxDB 2 when org is set
+    pub bucket: String,
+    /// Organization on InfluxDB 2
+    pub org: Option<String>,
+    /// API token on InfluxDB 2
+    pub token: Option<String>,
+    /// Seconds between pushes, the readings in between are sent together
+    #[serde(default = "default_influx_interval")]
+    pub interval_secs: u64,
+}
+
+fn default_influx_interval() -> u64 {
+    10
+}
+
+/// A named bundle of cpu settings, any setting left out is not changed when applied
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Profile {
+    pub governor: Option<String>,
+    /// Minimum frequency in MHz
+    pub min_freq: Option<i32>,
+    /// Maximum frequency in MHz
+    pub max_freq: Option<i32>,
+    pub turbo: Option<bool>,
+    /// Energy performance preference
+    pub epp: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct SafeConfig {
     pub powersave_under: Option<i8>,
     pub overheat_threshold: Option<i8>,
     pub high_cpu_threshold: Option<i8>,
+    pub low_cpu_threshold: Option<i8>,
     pub high_cpu_time_needed: Option<u64>,
+    pub min_governor_hold_secs: Option<u64>,
+    pub adaptive_polling: Option<bool>,
+    pub min_poll_ms: Option<u64>,
+    pub max_poll_ms: Option<u64>,
     pub active_rules: Option<Vec<String>>,
+    pub performance_epp: Option<String>,
+    pub powersave_epp: Option<String>,
+    pub charge_threshold: Option<u8>,
+    pub metrics_port: Option<u16>,
+    pub remote_status_port: Option<u16>,
+    pub remote_status_bind: Option<String>,
+    pub disable_turbo_on_battery: Option<bool>,
+    pub battery_max_freq: Option<i32>,
+    pub restore_on_exit: Option<bool>,
+    pub notify_temp: Option<i8>,
+    pub csv_max_size_mb: Option<u64>,
+    pub csv_max_files: Option<u32>,
+    pub avg_window: Option<usize>,
+    pub log_backend: Option<String>,
+    pub theme: Option<String>,
+    pub sysfs_debounce_ms: Option<u64>,
+    pub watchdog_secs: Option<u64>,
+    pub watchdog_exit: Option<bool>,
+    pub history_file: Option<String>,
+    pub history_max_age_hours: Option<u64>,
+    pub history_flush_secs: Option<u64>,
+    pub experimental_fan_control: Option<bool>,
+    pub experimental_undervolt: Option<bool>,
+    pub thermal_zones: Option<Vec<String>>,
+    pub active_throttle: Option<bool>,
+    pub throttle_temp: Option<i8>,
+    pub throttle_recover_temp: Option<i8>,
+    pub throttle_step_mhz: Option<i32>,
+    pub schedule: Option<BTreeMap<String, String>>,
+    pub core_governors: Option<BTreeMap<String, String>>,
+    pub app_governors: Option<BTreeMap<String, String>>,
+    pub hooks: Option<BTreeMap<String, String>>,
+    pub influx: Option<InfluxConfig>,
+    pub battery: Option<FreqLimits>,
+    pub ac: Option<FreqLimits>,
+    pub profiles: Option<HashMap<String, Profile>>,
 }
 
 trait SafeFillConfig {
@@ -158,10 +399,30 @@ impl SafeFillConfig for SafeConfig {
             base.high_cpu_threshold = hc;
         }
 
+        if let Some(lc) = self.low_cpu_threshold {
+            base.low_cpu_threshold = lc;
+        }
+
         if let Some(ht) = self.high_cpu_time_needed {
             base.high_cpu_time_needed = ht;
         }
 
+        if let Some(mh) = self.min_governor_hold_secs {
+            base.min_governor_hold_secs = mh;
+        }
+
+        if let Some(ap) = self.adaptive_polling {
+            base.adaptive_polling = ap;
+        }
+
+        if let Some(mp) = self.min_poll_ms {
+            base.min_poll_ms = mp;
+        }
+
+        if let Some(mp) = self.max_poll_ms {
+            base.max_poll_ms = mp;
+        }
+
         if let Some(ars) = &self.active_rules {
             base.active_rules.clear();
             for rule in ars {
@@ -175,6 +436,144 @@ impl SafeFillConfig for SafeConfig {
             }
         }
 
+        if self.performance_epp.is_some() {
+            base.performance_epp = self.performance_epp.clone();
+        }
+
+        if self.powersave_epp.is_some() {
+            base.powersave_epp = self.powersave_epp.clone();
+        }
+
+        if self.charge_threshold.is_some() {
+            base.charge_threshold = self.charge_threshold;
+        }
+
+        if self.metrics_port.is_some() {
+            base.metrics_port = self.metrics_port;
+        }
+        if self.remote_status_port.is_some() {
+            base.remote_status_port = self.remote_status_port;
+        }
+        if let Some(bind) = &self.remote_status_bind {
+            base.remote_status_bind = bind.clone();
+        }
+
+        if let Some(dt) = self.disable_turbo_on_battery {
+            base.disable_turbo_on_battery = dt;
+        }
+
+        if self.battery_max_freq.is_some() {
+            base.battery_max_freq = self.battery_max_freq;
+        }
+
+        if let Some(re) = self.restore_on_exit {
+            base.restore_on_exit = re;
+        }
+
+        if self.notify_temp.is_some() {
+            base.notify_temp = self.notify_temp;
+        }
+
+        if self.csv_max_size_mb.is_some() {
+            base.csv_max_size_mb = self.csv_max_size_mb;
+        }
+
+        if let Some(cf) = self.csv_max_files {
+            base.csv_max_files = cf;
+        }
+
+        if let Some(aw) = self.avg_window {
+            base.avg_window = aw;
+        }
+
+        if self.log_backend.is_some() {
+            base.log_backend = self.log_backend.clone();
+        }
+
+        if self.theme.is_some() {
+            base.theme = self.theme.clone();
+        }
+
+        if let Some(sd) = self.sysfs_debounce_ms {
+            base.sysfs_debounce_ms = sd;
+        }
+
+        if let Some(ws) = self.watchdog_secs {
+            base.watchdog_secs = ws;
+        }
+
+        if let Some(we) = self.watchdog_exit {
+            base.watchdog_exit = we;
+        }
+        if self.history_file.is_some() {
+            base.history_file = self.history_file.clone();
+        }
+        if let Some(hours) = self.history_max_age_hours {
+            base.history_max_age_hours = hours;
+        }
+        if let Some(secs) = self.history_flush_secs {
+            base.history_flush_secs = secs;
+        }
+
+        if let Some(fc) = self.experimental_fan_control {
+            base.experimental_fan_control = fc;
+        }
+
+        if let Some(eu) = self.experimental_undervolt {
+            base.experimental_undervolt = eu;
+        }
+
+        if let Some(tz) = &self.thermal_zones {
+            base.thermal_zones = tz.clone();
+        }
+
+        if let Some(at) = self.active_throttle {
+            base.active_throttle = at;
+        }
+
+        if let Some(tt) = self.throttle_temp {
+            base.throttle_temp = tt;
+        }
+
+        if let Some(rt) = self.throttle_recover_temp {
+            base.throttle_recover_temp = rt;
+        }
+
+        if let Some(step) = self.throttle_step_mhz {
+            base.throttle_step_mhz = step;
+        }
+
+        if let Some(sc) = &self.schedule {
+            base.schedule = sc.clone();
+        }
+
+        if let Some(cg) = &self.core_governors {
+            base.core_governors = cg.clone();
+        }
+
+        if let Some(ag) = &self.app_governors {
+            base.app_governors = ag.clone();
+        }
+        if let Some(hooks) = &self.hooks {
+            base.hooks = hooks.clone();
+        }
+
+        if self.influx.is_some() {
+            base.influx = self.influx.clone();
+        }
+
+        if let Some(battery) = &self.battery {
+            base.battery = battery.clone();
+        }
+
+        if let Some(ac) = &self.ac {
+            base.ac = ac.clone();
+        }
+
+        if let Some(pr) = &self.profiles {
+            base.profiles = pr.clone();
+        }
+
         base
     }
 }
@@ -185,6 +584,120 @@ impl fmt::Display for Config {
     }
 }
 
+/// Parse a range of cores from the config such as "0-3" or a single core such as "5"
+pub fn parse_core_range(range: &str) -> Result<(usize, usize), Error> {
+    let (first, last) = match range.split_once('-') {
+        Some((first, last)) => (
+            first.trim().parse::<usize>()?,
+            last.trim().parse::<usize>()?,
+        ),
+        None => {
+            let core = range.trim().parse::<usize>()?;
+            (core, core)
+        }
+    };
+
+    if first > last {
+        return Err(Error::Parse);
+    }
+
+    Ok((first, last))
+}
+
+/// Parse a time of day such as "07:30" into the minute of the day
+pub fn parse_time(time: &str) -> Result<u16, Error> {
+    let (hour, minute) = time.trim().split_once(':').ok_or(Error::Parse)?;
+    let hour = hour.parse::<u16>()?;
+    let minute = minute.parse::<u16>()?;
+
+    if hour > 23 || minute > 59 {
+        return Err(Error::Parse);
+    }
+
+    Ok(hour * 60 + minute)
+}
+
+/// Parse a time window from the config such as "22:00-07:00" into its start and end minute
+///
+/// The end is not part of the window, and a window that ends before it starts wraps past midnight
+pub fn parse_time_window(window: &str) -> Result<(u16, u16), Error> {
+    let (start, end) = window.split_once('-').ok_or(Error::Parse)?;
+    Ok((parse_time(start)?, parse_time(end)?))
+}
+
+/// Merge `over` into `base`, tables are merged key by key and any other value in `over` replaces
+/// the one in `base`
+pub fn merge_toml(base: &mut toml::Value, over: toml::Value) {
+    match (base, over) {
+        (toml::Value::Table(base), toml::Value::Table(over)) => {
+            for (key, value) in over {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_toml(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, over) => *base = over,
+    }
+}
+
+/// Read a config and merge in the files from its include list, `stack` holds the files that are
+/// being read so a file that ends up including itself is caught
+fn read_with_includes(path: &Path, stack: &mut Vec<PathBuf>) -> Result<toml::Value, Error> {
+    let canonical = path.canonicalize()?;
+    if stack.contains(&canonical) {
+        return Err(Error::InvalidConfig(format!(
+            "{} ends up including itself",
+            path.display()
+        )));
+    }
+
+    let text = fs::read_to_string(path)?;
+    let mut value: toml::Value = toml::from_str(&text)
+        .map_err(|e| Error::InvalidConfig(format!("{}: {}", path.display(), e)))?;
+
+    let includes = match value.as_table_mut().and_then(|t| t.remove("include")) {
+        Some(toml::Value::Array(includes)) => includes,
+        Some(_) => {
+            return Err(Error::InvalidConfig(format!(
+                "include in {} has to be a list of paths",
+                path.display()
+            )))
+        }
+        None => return Ok(value),
+    };
+
+    stack.push(canonical);
+    let dir = path.parent().unwrap_or_else(|| Path::new("/"));
+    let mut merged = toml::Value::Table(toml::map::Map::new());
+    for include in includes {
+        let include = include.as_str().ok_or_else(|| {
+            Error::InvalidConfig(format!(
+                "include in {} has to be a list of paths",
+                path.display()
+            ))
+        })?;
+        // An absolute path replaces the directory
+        merge_toml(&mut merged, read_with_includes(&dir.join(include), stack)?);
+    }
+    stack.pop();
+
+    merge_toml(&mut merged, value);
+    Ok(merged)
+}
+
+/// Read a config file with the configs it includes merged in
+///
+/// `include = ["base.toml"]` merges the files in the order they are listed, later files win over
+/// earlier ones and the including file wins over all of them. Relative paths are from the
+/// directory of the including file.
+pub fn read_config_text(path: &Path) -> Result<String, Error> {
+    let value = read_with_includes(path, &mut Vec::new())?;
+    toml::to_string(&value).map_err(|e| Error::InvalidConfig(e.to_string()))
+}
+
 fn read_as_string(config_file: &mut File) -> String {
     // Read it to new string
     let mut config: String = String::new();
@@ -202,17 +715,83 @@ fn parse_as_toml(config: String) -> Config {
             powersave_under: None,
             overheat_threshold: None,
             high_cpu_threshold: None,
+            low_cpu_threshold: None,
             high_cpu_time_needed: None,
+            min_governor_hold_secs: None,
+            adaptive_polling: None,
+            min_poll_ms: None,
+            max_poll_ms: None,
             active_rules: None,
+            performance_epp: None,
+            powersave_epp: None,
+            charge_threshold: None,
+            metrics_port: None,
+            remote_status_port: None,
+            remote_status_bind: None,
+            disable_turbo_on_battery: None,
+            battery_max_freq: None,
+            restore_on_exit: None,
+            notify_temp: None,
+            csv_max_size_mb: None,
+            csv_max_files: None,
+            avg_window: None,
+            log_backend: None,
+            theme: None,
+            sysfs_debounce_ms: None,
+            watchdog_secs: None,
+            watchdog_exit: None,
+            history_file: None,
+            history_max_age_hours: None,
+            history_flush_secs: None,
+            experimental_fan_control: None,
+            experimental_undervolt: None,
+            thermal_zones: None,
+            active_throttle: None,
+            throttle_temp: None,
+            throttle_recover_temp: None,
+            throttle_step_mhz: None,
+            schedule: None,
+            core_governors: None,
+            app_governors: None,
+            hooks: None,
+            influx: None,
+            battery: None,
+            ac: None,
+            profiles: None,
         });
 
     safe_config.safe_fill_config()
 }
 
+/// Parse a config, failing instead of falling back to the defaults when it is not valid
+pub fn parse_config(config: &str) -> Result<Config, Error> {
+    let mut safe_config: SafeConfig =
+        toml::from_str(config).map_err(|e| Error::InvalidConfig(e.to_string()))?;
+    Ok(safe_config.safe_fill_config())
+}
+
+/// Read and parse the config file, failing if either does not work
+pub fn try_open_config() -> Result<Config, Error> {
+    let config = read_config_text(Path::new(&config_path()))?;
+    parse_config(&config)
+}
+
 pub fn open_config() -> Result<Config, std::io::Error> {
     let conf_path = config_path();
-    let mut config_file: File = File::open(conf_path)?;
-    let config_string = read_as_string(&mut config_file);
+    let mut config_file: File = File::open(&conf_path)?;
+
+    // The file is only read on its own when its includes can not be merged in
+    let config_string = match read_config_text(Path::new(&conf_path)) {
+        Ok(merged) => merged,
+        Err(e) => {
+            warn_user!(format!(
+                "Could not merge the included configs, {}",
+                format!("{:?}", e).trim_end()
+            ));
+            read_as_string(&mut config_file)
+        }
+    };
+
     let config_toml = parse_as_toml(config_string);
 
     Ok(config_toml)
@@ -233,6 +812,67 @@ pub fn get_config() -> Config {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn merge_toml_unit_test() {
+        let mut base: toml::Value = toml::from_str(
+            r#"
+            powersave_under = 20
+            active_rules = ["battery_percent_rule", "cpu_usage_rule"]
+            [profiles.quiet]
+            governor = "powersave"
+            max_freq = 2000
+            "#,
+        )
+        .unwrap();
+        let over: toml::Value = toml::from_str(
+            r#"
+            active_rules = ["lid_open_rule"]
+            [profiles.quiet]
+            max_freq = 1600
+            "#,
+        )
+        .unwrap();
+        merge_toml(&mut base, over);
+
+        assert_eq!(base["powersave_under"].as_integer(), Some(20));
+        // Lists are replaced, not appended to
+        assert_eq!(base["active_rules"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            base["profiles"]["quiet"]["governor"].as_str(),
+            Some("powersave")
+        );
+        assert_eq!(
+            base["profiles"]["quiet"]["max_freq"].as_integer(),
+            Some(1600)
+        );
+    }
+
+    #[test]
+    fn read_config_text_unit_test() -> Result<(), Error> {
+        let dir = TestDir::new("include");
+        fs::create_dir_all(dir.join("shared"))?;
+        fs::write(
+            dir.join("shared/base.toml"),
+            "powersave_under = 30\noverheat_threshold = 70\n",
+        )?;
+        fs::write(
+            dir.join("acs.toml"),
+            "include = [\"shared/base.toml\"]\npowersave_under = 15\n",
+        )?;
+        fs::write(dir.join("loop.toml"), "include = [\"loop2.toml\"]\n")?;
+        fs::write(dir.join("loop2.toml"), "include = [\"loop.toml\"]\n")?;
+
+        let merged = read_config_text(&dir.join("acs.toml")).and_then(|t| parse_config(&t));
+        let looped = read_config_text(&dir.join("loop.toml"));
+
+        let config = merged?;
+        assert_eq!(config.powersave_under, 15);
+        assert_eq!(config.overheat_threshold, 70);
+        assert!(matches!(looped, Err(Error::InvalidConfig(_))));
+        Ok(())
+    }
 
     #[test]
     fn default_config_unit_test() {
@@ -240,6 +880,88 @@ mod tests {
         assert!(config.powersave_under > 0 && config.powersave_under < 100);
     }
 
+    #[test]
+    fn default_config_to_toml_unit_test() {
+        let serialized = toml::to_string(&Config::default()).expect("Could not serialize config");
+        assert!(serialized.contains("powersave_under = 20\n"));
+    }
+
+    #[test]
+    fn parse_profiles_unit_test() {
+        let config = parse_as_toml(
+            "
+[profiles.balanced]
+governor = \"powersave\"
+max_freq = 3200
+turbo = true
+"
+            .to_string(),
+        );
+        let balanced = &config.profiles["balanced"];
+        assert_eq!(balanced.governor.as_deref(), Some("powersave"));
+        assert_eq!(balanced.max_freq, Some(3200));
+        assert_eq!(balanced.turbo, Some(true));
+        assert!(balanced.epp.is_none());
+    }
+
+    #[test]
+    fn parse_config_unit_test() {
+        let config = parse_config("powersave_under = 30\n").expect("Config should parse");
+        assert_eq!(config.powersave_under, 30);
+        assert_eq!(config.overheat_threshold, 80);
+
+        assert!(matches!(
+            parse_config("powersave_under = \"thirty\"\n"),
+            Err(Error::InvalidConfig(_))
+        ));
+        assert!(parse_config("powersave_under = ").is_err());
+    }
+
+    #[test]
+    fn freq_limits_unit_test() {
+        let config = parse_config(
+            "battery_max_freq = 2000\n[battery]\nmin_freq = 800\n[ac]\nmin_freq = 1600\n",
+        )
+        .expect("Config should parse");
+        assert_eq!(
+            config.freq_limits(false),
+            FreqLimits {
+                min_freq: Some(800),
+                max_freq: Some(2000),
+            }
+        );
+        assert_eq!(config.freq_limits(true).min_freq, Some(1600));
+        assert_eq!(config.freq_limits(true).max_freq, None);
+
+        // The battery table wins over battery_max_freq
+        let config = parse_config("battery_max_freq = 2000\n[battery]\nmax_freq = 2400\n")
+            .expect("Config should parse");
+        assert_eq!(config.freq_limits(false).max_freq, Some(2400));
+        assert!(Config::default().freq_limits(true).is_empty());
+    }
+
+    #[test]
+    fn parse_core_range_unit_test() {
+        assert!(matches!(parse_core_range("0-3"), Ok((0, 3))));
+        assert!(matches!(parse_core_range("5"), Ok((5, 5))));
+        assert!(matches!(parse_core_range(" 4 - 15 "), Ok((4, 15))));
+        assert!(parse_core_range("3-1").is_err());
+        assert!(parse_core_range("a-b").is_err());
+    }
+
+    #[test]
+    fn parse_time_window_unit_test() {
+        assert!(matches!(parse_time_window("07:00-22:00"), Ok((420, 1320))));
+        assert!(matches!(
+            parse_time_window("22:00 - 07:30"),
+            Ok((1320, 450))
+        ));
+        assert!(parse_time_window("24:00-07:00").is_err());
+        assert!(parse_time_window("22:00-07:60").is_err());
+        assert!(parse_time_window("22-07").is_err());
+        assert!(parse_time_window("22:00").is_err());
+    }
+
     #[test]
     fn read_as_string_unit_test() -> Result<(), std::io::Error> {
         let conf_file = "acs.toml";
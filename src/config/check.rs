@@ -0,0 +1,485 @@
+#![forbid(unsafe_code)]
+//! Validation of a config that parsed, used by `acs config check`
+//!
+//! Parsing only makes sure the values have the right types, everything that depends on the
+//! values themselves or on this system, like governor names, is checked here. Each problem is
+//! reported with the key it was found under so it can be fixed without guessing.
+use super::{parse_config, parse_core_range, parse_time_window, Config, SafeConfig};
+use crate::cpu::{available_epps, hardware_freq_limits};
+use crate::error::Error;
+use crate::hooks::{validate_hook, EVENTS};
+use crate::logger::Backend;
+use crate::network::influx::Endpoint;
+use crate::power::battery::validate_charge_threshold;
+use crate::profile::in_time_window;
+use crate::system::check_available_governors;
+use crate::theme::Theme;
+use crate::thermal::read_thermal_zones;
+use std::fmt;
+
+/// The rule names accepted in active_rules
+pub const RULES: [&str; 4] = [
+    "battery_percent_rule",
+    "lid_open_rule",
+    "ac_charging_rule",
+    "cpu_usage_rule",
+];
+
+/// A problem with one key of the config
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConfigProblem {
+    pub key: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+/// What this system supports, an empty list or None means it could not be read and is not checked
+#[derive(Debug, Default)]
+pub struct Hardware {
+    pub governors: Vec<String>,
+    pub epps: Vec<String>,
+    /// Lowest and highest frequency in MHz
+    pub freq_range_mhz: Option<(i32, i32)>,
+    pub thermal_zones: Vec<String>,
+}
+
+impl Hardware {
+    /// Read what the first core and the thermal zones of this system support
+    pub fn detect() -> Hardware {
+        Hardware {
+            governors: check_available_governors().unwrap_or_default(),
+            epps: available_epps(0).unwrap_or_default(),
+            freq_range_mhz: hardware_freq_limits(0)
+                .ok()
+                .map(|(min, max)| (min / 1000, max / 1000)),
+            thermal_zones: read_thermal_zones()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|z| z.name)
+                .collect(),
+        }
+    }
+}
+
+struct Problems(Vec<ConfigProblem>);
+
+impl Problems {
+    fn add(&mut self, key: &str, message: String) {
+        self.0.push(ConfigProblem {
+            key: key.to_string(),
+            message,
+        });
+    }
+
+    fn governor(&mut self, key: &str, gov: &str, hardware: &Hardware) {
+        if !hardware.governors.is_empty() && !hardware.governors.iter().any(|g| g == gov) {
+            self.add(
+                key,
+                format!(
+                    "\"{}\" is not an available governor, expected one of: {}",
+                    gov,
+                    hardware.governors.join(", ")
+                ),
+            );
+        }
+    }
+
+    fn epp(&mut self, key: &str, epp: &str, hardware: &Hardware) {
+        if !hardware.epps.is_empty() && !hardware.epps.iter().any(|e| e == epp) {
+            self.add(
+                key,
+                format!(
+                    "\"{}\" is not an available epp, expected one of: {}",
+                    epp,
+                    hardware.epps.join(", ")
+                ),
+            );
+        }
+    }
+
+    fn freq(&mut self, key: &str, mhz: i32, hardware: &Hardware) {
+        let (min, max) = hardware.freq_range_mhz.unwrap_or((1, i32::MAX));
+        if mhz < min || mhz > max {
+            self.add(
+                key,
+                format!(
+                    "{} MHz is outside of what the cpu supports, {} to {}",
+                    mhz, min, max
+                ),
+            );
+        }
+    }
+
+    fn percent(&mut self, key: &str, value: i8) {
+        if !(0..=100).contains(&value) {
+            self.add(key, format!("{} is not a percentage from 0 to 100", value));
+        }
+    }
+}
+
+/// Check the keys and rule names that get lost once the config is parsed
+fn check_raw(raw: &toml::Value, problems: &mut Problems) {
+    let known = serde_json::to_value(SafeConfig::default()).unwrap_or_default();
+
+    if let Some(table) = raw.as_table() {
+        for key in table.keys() {
+            if known.get(key).is_none() {
+                problems.add(key, "is not a config key".to_string());
+            }
+        }
+    }
+
+    if let Some(rules) = raw.get("active_rules").and_then(|r| r.as_array()) {
+        for rule in rules.iter().filter_map(|r| r.as_str()) {
+            if !RULES.contains(&rule) {
+                problems.add(
+                    "active_rules",
+                    format!(
+                        "\"{}\" is not a rule, expected one of: {}",
+                        rule,
+                        RULES.join(", ")
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Check if two time windows share a minute of the day
+fn windows_overlap(a: (u16, u16), b: (u16, u16)) -> bool {
+    (0..24 * 60).any(|minute| in_time_window(a, minute) && in_time_window(b, minute))
+}
+
+/// Check the values of a parsed config
+pub fn validate_config(config: &Config, hardware: &Hardware) -> Vec<ConfigProblem> {
+    let mut problems = Problems(Vec::new());
+
+    problems.percent("powersave_under", config.powersave_under);
+    problems.percent("high_cpu_threshold", config.high_cpu_threshold);
+    problems.percent("low_cpu_threshold", config.low_cpu_threshold);
+    if config.low_cpu_threshold > config.high_cpu_threshold {
+        problems.add(
+            "low_cpu_threshold",
+            "has to be at or under high_cpu_threshold".to_string(),
+        );
+    }
+
+    if config.min_poll_ms == 0 {
+        problems.add("min_poll_ms", "has to be over 0".to_string());
+    }
+    if config.min_poll_ms > config.max_poll_ms {
+        problems.add(
+            "max_poll_ms",
+            "has to be at or over min_poll_ms".to_string(),
+        );
+    }
+
+    if let Some(epp) = &config.performance_epp {
+        problems.epp("performance_epp", epp, hardware);
+    }
+    if let Some(epp) = &config.powersave_epp {
+        problems.epp("powersave_epp", epp, hardware);
+    }
+
+    if let Some(threshold) = config.charge_threshold {
+        if let Err(e) = validate_charge_threshold(threshold) {
+            problems.add(
+                "charge_threshold",
+                format!("{:?}", e).trim_end().to_string(),
+            );
+        }
+    }
+
+    if let Some(mhz) = config.battery_max_freq {
+        problems.freq("battery_max_freq", mhz, hardware);
+    }
+    for (source, limits) in [("battery", &config.battery), ("ac", &config.ac)] {
+        if let Some(mhz) = limits.min_freq {
+            problems.freq(&format!("{}.min_freq", source), mhz, hardware);
+        }
+        if let Some(mhz) = limits.max_freq {
+            problems.freq(&format!("{}.max_freq", source), mhz, hardware);
+        }
+        if let (Some(min), Some(max)) = (limits.min_freq, limits.max_freq) {
+            if min > max {
+                problems.add(
+                    &format!("{}.min_freq", source),
+                    format!("{} MHz is over max_freq, {} MHz", min, max),
+                );
+            }
+        }
+    }
+
+    if config.throttle_recover_temp >= config.throttle_temp {
+        problems.add(
+            "throttle_recover_temp",
+            "has to be under throttle_temp".to_string(),
+        );
+    }
+    if config.throttle_step_mhz <= 0 {
+        problems.add("throttle_step_mhz", "has to be over 0".to_string());
+    }
+
+    if config.history_max_age_hours == 0 {
+        problems.add("history_max_age_hours", "has to be over 0".to_string());
+    }
+    if config.history_flush_secs == 0 {
+        problems.add("history_flush_secs", "has to be over 0".to_string());
+    }
+
+    if config.csv_max_size_mb == Some(0) {
+        problems.add("csv_max_size_mb", "has to be over 0".to_string());
+    }
+
+    if let Some(backend) = &config.log_backend {
+        if Backend::from_config(Some(backend)).is_err() {
+            problems.add(
+                "log_backend",
+                format!(
+                    "\"{}\" is not a backend, expected stdout or journald",
+                    backend
+                ),
+            );
+        }
+    }
+
+    if let Some(theme) = &config.theme {
+        if Theme::from_config(Some(theme)).is_err() {
+            problems.add(
+                "theme",
+                format!(
+                    "\"{}\" is not a theme, expected dark, light, or mono",
+                    theme
+                ),
+            );
+        }
+    }
+
+    if let Some(influx) = &config.influx {
+        if Endpoint::from_config(influx).is_err() {
+            problems.add(
+                "influx.url",
+                format!("\"{}\" is not an address like http://host:8086", influx.url),
+            );
+        }
+        if influx.interval_secs == 0 {
+            problems.add("influx.interval_secs", "has to be over 0".to_string());
+        }
+    }
+
+    if !hardware.thermal_zones.is_empty() {
+        for zone in &config.thermal_zones {
+            if zone != "all" && !hardware.thermal_zones.contains(zone) {
+                problems.add(
+                    "thermal_zones",
+                    format!("\"{}\" is not a thermal zone on this system", zone),
+                );
+            }
+        }
+    }
+
+    let mut windows: Vec<(&str, (u16, u16))> = Vec::new();
+    for (window, profile) in &config.schedule {
+        let key = format!("schedule.\"{}\"", window);
+        match parse_time_window(window) {
+            Ok(minutes) => {
+                // The schedule is checked in order, so the earlier window wins the overlap
+                if let Some((earlier, _)) = windows
+                    .iter()
+                    .find(|(_, other)| windows_overlap(*other, minutes))
+                {
+                    problems.add(
+                        &key,
+                        format!("overlaps \"{}\", which is used during the overlap", earlier),
+                    );
+                }
+                windows.push((window, minutes));
+            }
+            Err(_) => problems.add(&key, "is not a time window like 22:00-07:00".to_string()),
+        }
+        if !config.profiles.contains_key(profile) {
+            problems.add(&key, format!("profile \"{}\" is not defined", profile));
+        }
+    }
+
+    for (range, gov) in &config.core_governors {
+        let key = format!("core_governors.\"{}\"", range);
+        if parse_core_range(range).is_err() {
+            problems.add(&key, "is not a range of cores like 0-3".to_string());
+        }
+        problems.governor(&key, gov, hardware);
+    }
+
+    for (app, gov) in &config.app_governors {
+        problems.governor(&format!("app_governors.\"{}\"", app), gov, hardware);
+    }
+
+    for (event, path) in &config.hooks {
+        let key = format!("hooks.{}", event);
+        if !EVENTS.contains(&event.as_str()) {
+            problems.add(
+                &key,
+                format!("is not an event, expected one of: {}", EVENTS.join(", ")),
+            );
+        } else if let Err(message) = validate_hook(path) {
+            problems.add(&key, message);
+        }
+    }
+
+    let mut profiles: Vec<_> = config.profiles.iter().collect();
+    profiles.sort_by(|a, b| a.0.cmp(b.0));
+    for (name, profile) in profiles {
+        if let Some(gov) = &profile.governor {
+            problems.governor(&format!("profiles.{}.governor", name), gov, hardware);
+        }
+        if let Some(epp) = &profile.epp {
+            problems.epp(&format!("profiles.{}.epp", name), epp, hardware);
+        }
+        if let Some(min) = profile.min_freq {
+            problems.freq(&format!("profiles.{}.min_freq", name), min, hardware);
+        }
+        if let Some(max) = profile.max_freq {
+            problems.freq(&format!("profiles.{}.max_freq", name), max, hardware);
+        }
+        if let (Some(min), Some(max)) = (profile.min_freq, profile.max_freq) {
+            if min > max {
+                problems.add(
+                    &format!("profiles.{}.min_freq", name),
+                    "has to be at or under max_freq".to_string(),
+                );
+            }
+        }
+    }
+
+    problems.0
+}
+
+/// Parse and validate the text of a config file
+///
+/// A config that does not parse is an error, anything wrong with a config that does is a problem
+pub fn check_config(text: &str, hardware: &Hardware) -> Result<Vec<ConfigProblem>, Error> {
+    let raw: toml::Value = toml::from_str(text).map_err(|e| Error::InvalidConfig(e.to_string()))?;
+    let config = parse_config(text)?;
+
+    let mut problems = Problems(Vec::new());
+    check_raw(&raw, &mut problems);
+    problems.0.extend(validate_config(&config, hardware));
+    Ok(problems.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hardware() -> Hardware {
+        Hardware {
+            governors: vec!["performance".to_string(), "powersave".to_string()],
+            epps: vec!["performance".to_string(), "power".to_string()],
+            freq_range_mhz: Some((800, 4000)),
+            thermal_zones: vec!["x86_pkg_temp".to_string()],
+        }
+    }
+
+    fn keys(text: &str) -> Vec<String> {
+        check_config(text, &hardware())
+            .unwrap()
+            .into_iter()
+            .map(|p| p.key)
+            .collect()
+    }
+
+    #[test]
+    fn check_config_valid_unit_test() {
+        let text = r#"
+            powersave_under = 20
+            active_rules = ["battery_percent_rule", "cpu_usage_rule"]
+            thermal_zones = ["x86_pkg_temp"]
+
+            [schedule]
+            "22:00-07:00" = "quiet"
+            "07:00-22:00" = "quiet"
+
+            [core_governors]
+            "0-3" = "performance"
+
+            [profiles.quiet]
+            governor = "powersave"
+            max_freq = 2000
+        "#;
+        assert_eq!(keys(text), Vec::<String>::new());
+    }
+
+    #[test]
+    fn check_config_problems_unit_test() {
+        let text = r#"
+            overheat_treshold = 80
+            active_rules = ["battery_rule"]
+            low_cpu_threshold = 60
+            high_cpu_threshold = 40
+            battery_max_freq = 6000
+            throttle_temp = 75
+            throttle_recover_temp = 75
+
+            [schedule]
+            "22-07" = "missing"
+            "06:00-08:00" = "broken"
+            "07:00-09:00" = "broken"
+
+            [core_governors]
+            "3-1" = "ondemand"
+
+            [app_governors]
+            cargo = "turbo"
+
+            [hooks]
+            on_battery = "on-battery.sh"
+            on_suspend = "/bin/true"
+
+            [influx]
+            url = "influx:8086"
+            bucket = "acs"
+
+            [battery]
+            min_freq = 2400
+            max_freq = 1600
+
+            [profiles.broken]
+            min_freq = 3000
+            max_freq = 2000
+        "#;
+        assert_eq!(
+            keys(text),
+            vec![
+                "overheat_treshold",
+                "active_rules",
+                "low_cpu_threshold",
+                "battery_max_freq",
+                "battery.min_freq",
+                "throttle_recover_temp",
+                "influx.url",
+                "schedule.\"07:00-09:00\"",
+                "schedule.\"22-07\"",
+                "schedule.\"22-07\"",
+                "core_governors.\"3-1\"",
+                "core_governors.\"3-1\"",
+                "app_governors.\"cargo\"",
+                "hooks.on_battery",
+                "hooks.on_suspend",
+                "profiles.broken.min_freq",
+            ]
+        );
+    }
+
+    #[test]
+    fn check_config_parse_error_unit_test() {
+        assert!(matches!(
+            check_config("powersave_under = \"a lot\"", &hardware()),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+}
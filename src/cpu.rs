@@ -1,16 +1,21 @@
 #![forbid(unsafe_code)]
-use efcl::{bold, color, Color};
+use crate::theme::{bold, paint};
+use efcl::Color;
+use log::debug;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 use std::fmt;
-use std::fs::{self, File};
-use std::io::Write;
+use std::fs;
 use std::path::Path;
 
 use crate::proc::ProcStat;
 
+use crate::config::parse_core_range;
+use crate::display::format_freq;
 use crate::error::Error;
 use crate::gov::Gov;
-use crate::system::{calculate_cpu_percent, read_int, read_str};
+use crate::sysfs;
+use crate::system::{calculate_cpu_percent, interpret_turbo, read_int, read_str};
 
 /// Any trait relating to a CPU Core
 pub trait Speed {
@@ -31,7 +36,7 @@ pub trait Speed {
 }
 
 /// Data relating to the CPU
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CPU {
     pub name: String,
     pub number: i8,
@@ -102,10 +107,7 @@ impl Speed for CPU {
         }
 
         let path: String = format!("/sys/devices/system/cpu/{}/{}", self.name, sub_path);
-        let mut buffer = File::create(path)?;
-        buffer.write_all(to_write.as_bytes())?;
-
-        Ok(())
+        sysfs::write(Path::new(&path), &to_write)
     }
 
     /// Pull and update some the attributes of the cpu
@@ -192,6 +194,7 @@ impl Speed for CPU {
 
     /// Set the governor
     fn set_gov(&mut self, gov: Gov) -> Result<(), Error> {
+        validate_governor(self.number as usize, &gov.to_string())?;
         self.gov = format!("{gov}");
         self.write_value(WritableValue::Gov)?;
         Ok(())
@@ -233,7 +236,7 @@ impl fmt::Display for CPU {
             Color::GREEN
         };
 
-        let temp: String = color!(temp_color, format!("{}C", reduced_cpu_cur_temp).as_str());
+        let temp: String = paint(temp_color, format!("{}C", reduced_cpu_cur_temp).as_str());
 
         let scaled_cpus_cur_usage = self.cur_usage * 100.0;
 
@@ -248,21 +251,18 @@ impl fmt::Display for CPU {
             Color::LIGHTGRAY
         };
 
-        let usage: String = color!(
+        let usage: String = paint(
             usage_color,
-            format!("{:.2}%", scaled_cpus_cur_usage).as_str()
+            format!("{:.2}%", scaled_cpus_cur_usage).as_str(),
         );
 
         writeln!(
             f,
-            "{}:\t{}MHz\t{}MHz\t{}\t{}\t{}\t{}",
-            bold!(&self.name),
-            self.max_freq / 1000,
-            self.min_freq / 1000,
-            color!(
-                Color::GREEN,
-                format!("{}MHz", self.cur_freq / 1000).as_str()
-            ),
+            "{}:\t{}\t{}\t{}\t{}\t{}\t{}",
+            bold(&self.name),
+            format_freq(self.max_freq as f64),
+            format_freq(self.min_freq as f64),
+            paint(Color::GREEN, &format_freq(self.cur_freq as f64)),
             temp,
             usage,
             self.gov,
@@ -270,10 +270,336 @@ impl fmt::Display for CPU {
     }
 }
 
+/// Where the kernel lists the cores
+const CPU_DIR: &str = "/sys/devices/system/cpu";
+
+/// Check if a core is online
+///
+/// cpu0 usually has no `online` file because it cannot be taken offline, so a core that exists
+/// without one is considered online
+pub fn is_core_online(core_id: usize) -> bool {
+    is_core_online_in(Path::new(CPU_DIR), core_id)
+}
+
+pub fn is_core_online_in(dir: &Path, core_id: usize) -> bool {
+    let core_path = dir.join(format!("cpu{}", core_id));
+    if !core_path.exists() {
+        return false;
+    }
+
+    match fs::read_to_string(core_path.join("online")) {
+        Ok(online) => online.trim() == "1",
+        Err(_) => true,
+    }
+}
+
+/// Take a core offline or bring it back online
+///
+/// The kernel does not let cpu0 go offline, neither can cores without an `online` file
+pub fn set_core_online(core_id: usize, online: bool) -> Result<(), Error> {
+    set_core_online_in(Path::new(CPU_DIR), core_id, online)
+}
+
+pub fn set_core_online_in(dir: &Path, core_id: usize, online: bool) -> Result<(), Error> {
+    let core_path = dir.join(format!("cpu{}", core_id));
+    if !core_path.exists() {
+        return Err(Error::HdwNotFound);
+    }
+
+    let online_path = core_path.join("online");
+    if core_id == 0 || !online_path.exists() {
+        return Err(Error::CoreNotHotpluggable(core_id));
+    }
+    sysfs::write(&online_path, if online { "1" } else { "0" })
+}
+
+/// Parse a list of cores like the kernel writes them, e.g. "0-3,6,8-9"
+pub fn parse_core_list(list: &str) -> Result<Vec<usize>, Error> {
+    let mut cores = Vec::new();
+    for range in list.trim().split(',').filter(|r| !r.is_empty()) {
+        let (first, last) = parse_core_range(range)?;
+        cores.extend(first..=last);
+    }
+    Ok(cores)
+}
+
+/// The cores that are online right now
+pub fn online_cores() -> Result<Vec<usize>, Error> {
+    parse_core_list(&fs::read_to_string(format!("{}/online", CPU_DIR))?)
+}
+
+/// Turbo switch of the intel_pstate driver, writing 1 disables turbo
+const INTEL_NO_TURBO_PATH: &str = "/sys/devices/system/cpu/intel_pstate/no_turbo";
+
+/// Turbo switch of generic cpufreq drivers such as acpi-cpufreq on AMD, writing 1 enables turbo
+const CPUFREQ_BOOST_PATH: &str = "/sys/devices/system/cpu/cpufreq/boost";
+
+/// Path to a file in the cpufreq directory of a core
+fn cpufreq_path(core_id: usize, file: &str) -> String {
+    format!("/sys/devices/system/cpu/cpu{}/cpufreq/{}", core_id, file)
+}
+
+/// The lowest and highest frequency in kHz the hardware of a core supports
+pub fn hardware_freq_limits(core_id: usize) -> Result<(i32, i32), Error> {
+    Ok((
+        read_int(&cpufreq_path(core_id, "cpuinfo_min_freq"))?,
+        read_int(&cpufreq_path(core_id, "cpuinfo_max_freq"))?,
+    ))
+}
+
+/// The lowest and highest frequency in kHz a core is allowed to run at right now
+pub fn scaling_freq_limits(core_id: usize) -> Result<(i32, i32), Error> {
+    Ok((
+        read_int(&cpufreq_path(core_id, "scaling_min_freq"))?,
+        read_int(&cpufreq_path(core_id, "scaling_max_freq"))?,
+    ))
+}
+
+/// Check that a frequency is within a range, both in kHz
+pub fn validate_freq(freq: i32, min: i32, max: i32) -> Result<(), Error> {
+    if freq < min || freq > max {
+        return Err(Error::OutOfRange(freq as i64, min as i64, max as i64));
+    }
+    Ok(())
+}
+
+/// Set the highest frequency in kHz a core is allowed to run at
+///
+/// It has to be within the limits of the hardware and not under the current minimum
+pub fn set_max_freq(core_id: usize, freq: i32) -> Result<(), Error> {
+    if !is_core_online(core_id) {
+        return Err(Error::CoreOffline(core_id));
+    }
+
+    let (hw_min, hw_max) = hardware_freq_limits(core_id)?;
+    let cur_min = read_int(&cpufreq_path(core_id, "scaling_min_freq")).unwrap_or(hw_min);
+    validate_freq(freq, cur_min.max(hw_min), hw_max)?;
+
+    sysfs::write(
+        Path::new(&cpufreq_path(core_id, "scaling_max_freq")),
+        &freq.to_string(),
+    )
+}
+
+/// Set the lowest frequency in kHz a core is allowed to run at
+///
+/// It has to be within the limits of the hardware and not over the current maximum
+pub fn set_min_freq(core_id: usize, freq: i32) -> Result<(), Error> {
+    if !is_core_online(core_id) {
+        return Err(Error::CoreOffline(core_id));
+    }
+
+    let (hw_min, hw_max) = hardware_freq_limits(core_id)?;
+    let cur_max = read_int(&cpufreq_path(core_id, "scaling_max_freq")).unwrap_or(hw_max);
+    validate_freq(freq, hw_min, cur_max.min(hw_max))?;
+
+    sysfs::write(
+        Path::new(&cpufreq_path(core_id, "scaling_min_freq")),
+        &freq.to_string(),
+    )
+}
+
+/// Set the governor of a single core
+pub fn set_governor_for_core(core_id: usize, gov: &str) -> Result<(), Error> {
+    if !is_core_online(core_id) {
+        return Err(Error::CoreOffline(core_id));
+    }
+    validate_governor(core_id, gov)?;
+
+    sysfs::write(Path::new(&cpufreq_path(core_id, "scaling_governor")), gov)
+}
+
+/// Get the governors the cpufreq driver of a core offers
+///
+/// They depend on the driver, intel_pstate only has performance and powersave
+pub fn available_governors(core: usize) -> Result<Vec<String>, Error> {
+    let govs = read_str(&cpufreq_path(core, "scaling_available_governors"))?;
+    Ok(govs.split_whitespace().map(|x| x.to_owned()).collect())
+}
+
+/// Check that a core offers a governor, the error lists the ones it does
+///
+/// Cores whose list can not be read are let through so the write shows the real problem
+pub fn validate_governor(core: usize, gov: &str) -> Result<(), Error> {
+    match available_governors(core) {
+        Ok(available) if !available.iter().any(|a| a == gov) => {
+            Err(Error::InvalidValue(gov.to_string(), available))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Group cores by the governors they offer, a single group means every core offers the same
+pub fn group_by_governors(cores: Vec<(usize, Vec<String>)>) -> Vec<(Vec<String>, Vec<usize>)> {
+    let mut groups: Vec<(Vec<String>, Vec<usize>)> = Vec::new();
+    for (core, govs) in cores {
+        match groups.iter_mut().find(|(g, _)| *g == govs) {
+            Some((_, group)) => group.push(core),
+            None => groups.push((govs, vec![core])),
+        }
+    }
+    groups
+}
+
+/// The governors offered by each online core
+pub fn list_available_governors(cpus: &[CPU]) -> Vec<(usize, Vec<String>)> {
+    cpus.iter()
+        .filter_map(|cpu| {
+            let core = cpu.number as usize;
+            Some((core, available_governors(core).ok()?))
+        })
+        .collect()
+}
+
+/// Get the energy performance preferences that a core accepts
+pub fn available_epps(core: usize) -> Result<Vec<String>, Error> {
+    let prefs = read_str(&cpufreq_path(
+        core,
+        "energy_performance_available_preferences",
+    ))?;
+    Ok(prefs.split_whitespace().map(|x| x.to_owned()).collect())
+}
+
+/// Get the energy performance preference (EPP) of a core
+///
+/// Only exposed by drivers like amd_pstate_epp and intel_pstate
+pub fn get_epp(core: usize) -> Result<String, Error> {
+    let path = cpufreq_path(core, "energy_performance_preference");
+    if !Path::new(&path).exists() {
+        return Err(Error::HdwNotFound);
+    }
+    read_str(&path)
+}
+
+/// Set the energy performance preference (EPP) of a core
+///
+/// Older kernels do not have the EPP file, in which case nothing is written
+pub fn set_epp(core: usize, pref: &str) -> Result<(), Error> {
+    if !is_core_online(core) {
+        return Err(Error::CoreOffline(core));
+    }
+
+    let path = cpufreq_path(core, "energy_performance_preference");
+    if !Path::new(&path).exists() {
+        debug!("no EPP file for cpu{}, skipping", core);
+        return Ok(());
+    }
+
+    if let Ok(available) = available_epps(core) {
+        if !available.iter().any(|a| a == pref) {
+            return Err(Error::InvalidValue(pref.to_string(), available));
+        }
+    }
+
+    sysfs::write(Path::new(&path), pref)
+}
+
+/// The sysfs file that turns turbo on and off
+#[derive(Debug, PartialEq, Eq)]
+pub enum TurboInterface {
+    /// intel_pstate/no_turbo, the value is inverted
+    IntelPstate,
+    /// cpufreq/boost
+    CpufreqBoost,
+}
+
+impl TurboInterface {
+    /// Find which turbo file this system has, intel_pstate is checked first
+    pub fn detect() -> Result<TurboInterface, Error> {
+        if Path::new(INTEL_NO_TURBO_PATH).exists() {
+            Ok(TurboInterface::IntelPstate)
+        } else if Path::new(CPUFREQ_BOOST_PATH).exists() {
+            Ok(TurboInterface::CpufreqBoost)
+        } else {
+            Err(Error::Unsupported("Turbo".to_string()))
+        }
+    }
+
+    fn path(&self) -> &'static Path {
+        match self {
+            TurboInterface::IntelPstate => Path::new(INTEL_NO_TURBO_PATH),
+            TurboInterface::CpufreqBoost => Path::new(CPUFREQ_BOOST_PATH),
+        }
+    }
+
+    /// The value to write to turn turbo on or off
+    fn value(&self, enabled: bool) -> &'static str {
+        match (self, enabled) {
+            (TurboInterface::IntelPstate, true) | (TurboInterface::CpufreqBoost, false) => "0",
+            (TurboInterface::IntelPstate, false) | (TurboInterface::CpufreqBoost, true) => "1",
+        }
+    }
+
+    /// Turn the contents of the file into if turbo is enabled
+    fn interpret(&self, mut contents: String) -> Result<bool, Error> {
+        match self {
+            TurboInterface::IntelPstate => interpret_turbo(&mut contents),
+            TurboInterface::CpufreqBoost => Ok(contents.trim().parse::<u8>()? == 1),
+        }
+    }
+}
+
+/// Check if turbo is enabled for the whole cpu
+pub fn get_turbo() -> Result<bool, Error> {
+    let interface = TurboInterface::detect()?;
+    let contents = fs::read_to_string(interface.path())?;
+    interface.interpret(contents)
+}
+
+/// Enable or disable turbo for the whole cpu
+pub fn set_turbo(enabled: bool) -> Result<(), Error> {
+    let interface = TurboInterface::detect()?;
+    sysfs::write(interface.path(), interface.value(enabled))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::csv::Writable;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn turbo_interface_unit_test() -> Result<(), Error> {
+        // intel_pstate is inverted
+        assert_eq!(TurboInterface::IntelPstate.value(true), "0");
+        assert_eq!(TurboInterface::IntelPstate.value(false), "1");
+        assert!(TurboInterface::IntelPstate.interpret("0\n".to_string())?);
+
+        assert_eq!(TurboInterface::CpufreqBoost.value(true), "1");
+        assert_eq!(TurboInterface::CpufreqBoost.value(false), "0");
+        assert!(TurboInterface::CpufreqBoost.interpret("1\n".to_string())?);
+        assert!(!TurboInterface::CpufreqBoost.interpret("0\n".to_string())?);
+        Ok(())
+    }
+
+    #[test]
+    fn validate_freq_unit_test() {
+        assert!(validate_freq(3_200_000, 800_000, 4_000_000).is_ok());
+        assert!(validate_freq(800_000, 800_000, 4_000_000).is_ok());
+        assert!(matches!(
+            validate_freq(5_000_000, 800_000, 4_000_000),
+            Err(Error::OutOfRange(5_000_000, 800_000, 4_000_000))
+        ));
+        assert!(validate_freq(400_000, 800_000, 4_000_000).is_err());
+    }
+
+    #[test]
+    fn group_by_governors_unit_test() {
+        let pstate = vec!["performance".to_string(), "powersave".to_string()];
+        let acpi = vec!["ondemand".to_string(), "performance".to_string()];
+        assert_eq!(
+            group_by_governors(vec![
+                (0, pstate.clone()),
+                (1, acpi.clone()),
+                (2, pstate.clone())
+            ]),
+            vec![(pstate.clone(), vec![0, 2]), (acpi, vec![1])]
+        );
+        assert_eq!(
+            group_by_governors(vec![(0, pstate.clone()), (1, pstate.clone())]),
+            vec![(pstate, vec![0, 1])]
+        );
+    }
 
     #[test]
     fn cpu_random_unit_test() {
@@ -308,4 +634,37 @@ mod tests {
         let out = cpu.to_csv();
         assert!(out.contains(",,0,0,0,0,5,0,\n"));
     }
+
+    #[test]
+    fn core_online_unit_test() -> Result<(), Error> {
+        let dir = TestDir::new("online");
+        fs::create_dir_all(dir.join("cpu0"))?;
+        fs::create_dir_all(dir.join("cpu4"))?;
+        fs::write(dir.join("cpu4/online"), "1\n")?;
+
+        let cpu0 = set_core_online_in(&dir, 0, false);
+        let missing = set_core_online_in(&dir, 9, false);
+        set_core_online_in(&dir, 4, false)?;
+        let offline = is_core_online_in(&dir, 4);
+        set_core_online_in(&dir, 4, true)?;
+        let online = is_core_online_in(&dir, 4);
+        let cpu0_online = is_core_online_in(&dir, 0);
+
+        assert!(matches!(cpu0, Err(Error::CoreNotHotpluggable(0))));
+        assert!(matches!(missing, Err(Error::HdwNotFound)));
+        assert!(!offline);
+        assert!(online);
+        // cpu0 has no online file and is always online
+        assert!(cpu0_online);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_core_list_unit_test() -> Result<(), Error> {
+        assert_eq!(parse_core_list("0-3,6,8-9\n")?, vec![0, 1, 2, 3, 6, 8, 9]);
+        assert_eq!(parse_core_list("0\n")?, vec![0]);
+        assert_eq!(parse_core_list("\n")?, Vec::<usize>::new());
+        assert!(parse_core_list("0-a").is_err());
+        Ok(())
+    }
 }
@@ -10,8 +10,12 @@
 //!
 //! csv.rs logs system data the the state of each cpu to a csv file, not to be confused with
 //! logger.rs which write operation logs to the screen during usage.
+//!
+//! When `csv_max_size_mb` is set in the config, a file that would grow past it gets rotated
+//! instead of logging being stopped. `acs.csv` is moved to `acs.1.csv`, `acs.1.csv` to
+//! `acs.2.csv` and so on, keeping `csv_max_files` of the old files.
 
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Write;
 use std::path::Path;
 use std::{
@@ -19,6 +23,7 @@ use std::{
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
+use crate::config::Config;
 use crate::settings::Settings;
 use crate::{
     cpu::CPU,
@@ -30,6 +35,10 @@ pub const CSV_HEADER: &str = "epoch,name,number,max_freq,min_freq,cur_freq,cur_t
 pub struct CSVWriter {
     /// The max amount of file space the csv logger will take up in MB
     log_size_cutoff: i32,
+    /// Size in MB at which the file gets rotated, used instead of log_size_cutoff when set
+    max_size_mb: Option<u64>,
+    /// How many rotated files are kept
+    max_files: u32,
     /// The path to be logged to
     path: String,
     /// If the cpu writer is on
@@ -37,14 +46,67 @@ pub struct CSVWriter {
 }
 
 /// Generates a new instance of CSVWriten based on a settings object.
-pub fn gen_writer(settings: &Settings) -> CSVWriter {
+pub fn gen_writer(settings: &Settings, config: &Config) -> CSVWriter {
     CSVWriter {
         log_size_cutoff: settings.log_size_cutoff,
+        max_size_mb: config.csv_max_size_mb,
+        max_files: config.csv_max_files,
         path: settings.csv_file.to_string(),
         enabled: settings.log_csv,
     }
 }
 
+/// The path of the nth rotated file, e.g. `acs.1.csv` for `acs.csv`
+pub fn rotated_path(path: &str, n: u32) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+
+    let name = match path.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, n, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, n),
+    };
+
+    path.with_file_name(name).to_string_lossy().to_string()
+}
+
+/// Shift the rotated files up by one, dropping the oldest, and move the active file to the first
+///
+/// With max_files at 0 the active file is removed without keeping it
+pub fn rotate(path: &str, max_files: u32) -> std::io::Result<()> {
+    if max_files == 0 {
+        return fs::remove_file(path);
+    }
+
+    let oldest = rotated_path(path, max_files);
+    if Path::new(&oldest).exists() {
+        fs::remove_file(&oldest)?;
+    }
+
+    for n in (1..max_files).rev() {
+        let from = rotated_path(path, n);
+        if Path::new(&from).exists() {
+            fs::rename(&from, rotated_path(path, n + 1))?;
+        }
+    }
+
+    fs::rename(path, rotated_path(path, 1))
+}
+
+/// Check if the file has to rotate before adding the lines
+///
+/// A file with no rows after the header is never rotated, so rows bigger than the max still get
+/// written instead of rotating on every write
+fn needs_rotation(size: u64, lines: u64, max_bytes: u64) -> bool {
+    let header = CSV_HEADER.len() as u64 + 1;
+    size > header && size + lines > max_bytes
+}
+
+/// Create a new csv file with only the header in it
+fn create_with_header(path: &str) -> std::io::Result<()> {
+    let mut file = File::create(path)?;
+    writeln!(file, "{}", CSV_HEADER)
+}
+
 pub trait Writer {
     fn write<'a>(&mut self, writables: impl Iterator<Item = &'a dyn Writable>, logger: &mut Logger);
     fn init(&mut self, logger: &mut Logger);
@@ -83,7 +145,7 @@ impl Writer for CSVWriter {
     /// This method gets called every `daemon.settings.delay` millis or every `daemon.settings.delay_battery` millis when on battery
     ///
     /// Each time this method gets called it creates a new row in the csv file. If the csv file
-    /// gets larger than `self.log_size_cutoff` MB it will cease logging.
+    /// gets larger than `self.log_size_cutoff` MB it will cease logging, unless rotation is on.
     ///
     /// If an error occurs it will log the error to the daemon logger.
     fn write<'a>(
@@ -97,6 +159,24 @@ impl Writer for CSVWriter {
 
         let lines = writables.map(|c| c.to_csv()).collect::<String>();
 
+        if let Some(max_size_mb) = self.max_size_mb {
+            let size = fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+
+            // Rotate before the file would go over the max, so every file parses on its own
+            if needs_rotation(size, lines.len() as u64, max_size_mb * 1_000_000) {
+                if let Err(e) =
+                    rotate(&self.path, self.max_files).and_then(|_| create_with_header(&self.path))
+                {
+                    logger.log(
+                        &format!("Could not rotate the CSV file: {}", e),
+                        logger::Severity::Error,
+                    );
+                    self.enabled = false;
+                    return;
+                }
+            }
+        }
+
         // Open file in append mode
         let mut file = match OpenOptions::new().append(true).open(&self.path) {
             Ok(file) => file,
@@ -106,8 +186,10 @@ impl Writer for CSVWriter {
             }
         };
 
-        // If file is smaller than log_size_cutoff
-        if file.metadata().unwrap().len() < (self.log_size_cutoff * 1_000_000) as u64 {
+        // If file is smaller than log_size_cutoff, rotation already keeps it under its own max
+        if self.max_size_mb.is_some()
+            || file.metadata().unwrap().len() < (self.log_size_cutoff * 1_000_000) as u64
+        {
             // Try to write the cpus
             match write!(file, "{}", lines) {
                 Ok(_) => {}
@@ -171,3 +253,51 @@ impl Writer for CSVWriter {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn rotated_path_unit_test() {
+        assert_eq!(rotated_path("/tmp/acs.csv", 1), "/tmp/acs.1.csv");
+        assert_eq!(rotated_path("/tmp/acs.csv", 12), "/tmp/acs.12.csv");
+        assert_eq!(rotated_path("/tmp/acs", 2), "/tmp/acs.2");
+    }
+
+    #[test]
+    fn needs_rotation_unit_test() {
+        let header = CSV_HEADER.len() as u64 + 1;
+        let max = header + 100;
+        assert!(!needs_rotation(header + 10, 10, max));
+        assert!(needs_rotation(header + 10, 100, max));
+        // Only the header, the rows get written even when they are over the max
+        assert!(!needs_rotation(header, 1000, max));
+        assert!(!needs_rotation(0, 1000, max));
+    }
+
+    #[test]
+    fn rotate_unit_test() -> std::io::Result<()> {
+        let dir = TestDir::new("csv");
+        let path = dir.join("acs.csv").to_string_lossy().to_string();
+
+        // Three rotations with two kept drops the first file
+        for content in ["first", "second", "third"] {
+            fs::write(&path, content)?;
+            rotate(&path, 2)?;
+        }
+        create_with_header(&path)?;
+
+        let active = fs::read_to_string(&path)?;
+        let first = fs::read_to_string(rotated_path(&path, 1))?;
+        let second = fs::read_to_string(rotated_path(&path, 2))?;
+        let third_exists = Path::new(&rotated_path(&path, 3)).exists();
+
+        assert_eq!(active, format!("{}\n", CSV_HEADER));
+        assert_eq!(first, "third");
+        assert_eq!(second, "second");
+        assert!(!third_exists);
+        Ok(())
+    }
+}
@@ -29,36 +29,72 @@
 //! When enabled by the user the daemon will log all of the cpu data to a csv file.
 
 use std::convert::TryInto;
+use std::path::Path;
 use std::process::exit;
+use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::{Arc, Mutex};
-use std::time::SystemTime;
+use std::time::{Instant, SystemTime};
 use std::{thread, time};
 
-use efcl::{color, Color};
+use crate::theme::paint;
+use chrono::{Local, Timelike};
+use efcl::Color;
 use nix::unistd::Uid;
 use serde::Serialize;
 
-use crate::config::Config;
-use crate::cpu::{Speed, CPU};
+use crate::config::check::{validate_config, Hardware};
+use crate::config::{parse_core_range, Config, FreqLimits, InfluxConfig};
+use crate::cpu::{
+    get_turbo, hardware_freq_limits, is_core_online, online_cores, scaling_freq_limits, set_epp,
+    set_governor_for_core, set_max_freq, set_min_freq, set_turbo, Speed, CPU,
+};
 use crate::csv::{gen_writer, CSVWriter, Writer};
-use crate::display::{print_battery_status, print_turbo_status};
+use crate::display::{
+    format_freq, print_battery_status, print_turbo_status, render_fan_speed, render_gpu_clock,
+    render_throttling,
+};
 use crate::error::Error;
+use crate::fan::{list_fans, read_fan_speeds, Fan};
 use crate::gov::Gov;
+use crate::gpu::{list_gpus, read_gpu_clocks, Gpu};
+use crate::graph::svg::Sample;
 use crate::graph::{Graph, GraphType, Grapher};
+use crate::history::History;
+use crate::hooks::{self, Event, HookInfo};
+use crate::interactive::{
+    cycle_governor, restore_terminal, toggle_turbo, CoreTable, Key, KeyListener, SortKey,
+};
 use crate::logger;
 use crate::logger::Interface;
-use crate::network::{hook, listen};
+#[cfg(feature = "dbus")]
+use crate::network::dbus;
+#[cfg(feature = "metrics")]
+use crate::network::metrics;
+#[cfg(feature = "remote")]
+use crate::network::remote;
+use crate::network::{hook, influx, listen, SOCKET_PATH};
+use crate::notify::{self, thermal_event, ThermalEvent};
+use crate::poll::{AdaptivePoll, PollSample};
 use crate::power::battery::{has_battery, Battery};
 use crate::power::lid::{Lid, LidRetriever, LidState};
+use crate::power::rapl::{to_watt_hours, to_watts, Rapl};
 use crate::power::{Power, PowerRetriever};
 use crate::proc::{parse_proc_file, read_proc_stat_file, ProcStat};
+use crate::profile::{apply_profile, find_profile, scheduled_profile};
 use crate::settings::Settings;
 use crate::setup::{inside_docker_message, inside_wsl_message};
+use crate::signals::{block_signals, handle_signals};
+use crate::sysfs;
 use crate::system::{
-    check_available_governors, check_cpu_freq, check_cpu_temperature, check_cpu_usage,
-    get_highest_temp, inside_docker, inside_wsl, list_cpus,
+    app_governor, calculate_utilization, capabilities, check_available_governors, check_cpu_freq,
+    check_cpu_temperature, check_cpu_usage, get_highest_temp, inside_docker, inside_wsl, list_cpus,
+    list_processes, read_cpus, FreqWindow,
+};
+use crate::thermal::{
+    apply_core_temps, next_freq_cap, read_thermal_zones, zone_temp, ThrottleSteps, ThrottleTracker,
 };
 use crate::warn_user;
+use crate::watchdog::{self, Heartbeat};
 
 /// Describes the state of the machine
 ///
@@ -110,6 +146,9 @@ pub trait Checker {
     ) -> Result<(), Error>;
 
     fn init(&mut self);
+    fn apply_config(&mut self, config: Config);
+    fn log_config_problems(&mut self);
+    fn restore_startup_state(&mut self);
 
     fn start_loop(&mut self) -> Result<(), Error>;
     fn end_loop(&mut self);
@@ -118,6 +157,15 @@ pub trait Checker {
     fn single_monit(&mut self) -> Result<(), Error>;
 
     fn update_all(&mut self) -> Result<(), Error>;
+    fn update_package_power(&mut self);
+    fn update_online_cores(&mut self);
+    fn read_temp(&mut self) -> i32;
+    fn update_throttling(&mut self);
+    fn notify_thermal(&mut self);
+    fn run_hook(&mut self, event: Event, profile: Option<String>);
+    fn log_hook_exits(&mut self);
+    fn load_history(&mut self);
+    fn save_history(&mut self, force: bool);
 
     fn run_state_machine(&mut self) -> State;
 
@@ -126,6 +174,61 @@ pub trait Checker {
     fn print(&mut self);
 
     fn set_govs(&mut self, gov: String) -> Result<(), Error>;
+    fn set_core_govs(&mut self);
+    fn set_epps(&mut self, gov: &str);
+    fn apply_charge_threshold(&mut self);
+    fn apply_battery_turbo(&mut self);
+    fn apply_freq_limits(&mut self);
+    fn apply_thermal_cap(&mut self);
+    fn lift_thermal_cap(&mut self);
+    fn lift_freq_limits(&mut self);
+    fn online_core_numbers(&self) -> Vec<usize>;
+    fn apply_schedule(&mut self);
+    fn apply_named_profile(&mut self, name: &str) -> Result<(), Error>;
+    fn reapply(&mut self, reason: &str) -> Result<(), Error>;
+    fn check_app_governors(&mut self) -> Option<(String, String)>;
+
+    fn next_timeout(&mut self) -> time::Duration;
+
+    fn handle_key(&mut self, key: &Key);
+}
+
+/// How long an error from a key press stays at the bottom of the live view
+const KEY_ERROR_DURATION: time::Duration = time::Duration::from_secs(5);
+
+/// Cores the live view always shows, even when the terminal is too short for them
+const MIN_CORE_ROWS: usize = 4;
+
+/// How far the wall clock has to get ahead of the monotonic clock between two iterations to
+/// count as a suspend, the monotonic clock stops while the system is asleep
+const SUSPEND_GAP: time::Duration = time::Duration::from_secs(10);
+
+/// The settings the cpu had before the daemon started changing them
+#[derive(Clone, Debug)]
+pub struct StartupState {
+    /// The governor of each online core
+    pub governors: Vec<(usize, String)>,
+    pub turbo: Option<bool>,
+}
+
+impl StartupState {
+    /// Read the current governor of every online core and the turbo state
+    pub fn capture(cpus: &[CPU]) -> StartupState {
+        let governors = cpus
+            .iter()
+            .filter(|cpu| is_core_online(cpu.number as usize))
+            .filter_map(|cpu| {
+                let mut cpu = cpu.clone();
+                cpu.get_gov().ok()?;
+                Some((cpu.number as usize, cpu.gov))
+            })
+            .collect();
+
+        StartupState {
+            governors,
+            turbo: get_turbo().ok(),
+        }
+    }
 }
 
 /// The daemon structure which contains information about the auto clock speed instance
@@ -144,24 +247,103 @@ pub struct Daemon {
     pub grapher: Graph,
 
     pub cpus: Vec<CPU>,
+    /// If a core came online since the last iteration and still needs the daemon's settings
+    pub cores_added: bool,
     pub last_proc: Vec<ProcStat>,
+    /// GPUs whose clock is shown in the live view
+    pub gpus: Vec<Gpu>,
+    /// Fans whose speed is shown in the live view
+    pub fans: Vec<Fan>,
+    /// The last `config.avg_window` frequencies of each core
+    pub freq_window: FreqWindow,
+    /// Readings kept for `history_max_age_hours`, saved to `history_file` when it is set
+    pub history: History,
+    /// When the history file was last written
+    pub history_saved: Instant,
     pub message: String,
     pub charging: bool,
     pub charge: i8,
     pub usage: f32,
     pub last_below_cpu_usage_percent: Option<SystemTime>,
+    /// When the daemon last switched the governor, used to hold a governor for a minimum time
+    pub last_gov_change: Option<Instant>,
     pub graph: String,
     /// Highest temperature seen last update cycle (highest of any cpu core)
     pub temp_max: i8,
+    /// If the thermal zones from the config could not be found last update cycle
+    pub thermal_zones_missing: bool,
+    /// The throttle counter of each core at the last update cycle
+    pub throttle: ThrottleTracker,
+    /// The cores whose throttle counter went up last update cycle
+    pub throttling: Vec<String>,
+    /// If desktop notifications can be sent, false once it turns out there is nowhere to send them
+    pub do_notify: bool,
+    /// If the last thermal notification was for going over notify_temp
+    pub notified_hot: bool,
     /// The hash that is gathered at build time - used for testing versions
     pub commit_hash: String,
     pub paused: bool,
     pub do_update_battery: bool,
+    /// If the charge threshold from the config should be kept applied
+    pub do_charge_threshold: bool,
+    /// If turbo can be turned off on battery, false once it turns out to be unsupported
+    pub do_battery_turbo: bool,
+    /// If turbo was enabled before it got turned off for being on battery
+    pub turbo_before_battery: Option<bool>,
+    /// If the frequency limits of the power sources can be applied, false once writing them failed
+    pub do_freq_limits: bool,
+    /// The power source the frequency limits were applied for, true when charging
+    pub freq_source: Option<bool>,
+    /// The frequency limits in MHz that were applied
+    pub freq_applied: FreqLimits,
+    /// If active_throttle can step the max frequency, false once writing it failed
+    pub do_thermal_cap: bool,
+    /// The max frequency in kHz the cores are stepped down to for being too hot
+    pub thermal_cap: Option<i32>,
+    /// If the max frequency was written over since the thermal cap was, so it has to be again
+    pub thermal_rewrite: bool,
+    /// The max frequency in kHz each core had before the thermal cap, written back when it lifts
+    pub thermal_saved: Vec<(usize, i32)>,
+    /// If the schedule in the config can be used, false once it turns out to be invalid
+    pub do_schedule: bool,
+    /// The time window of the schedule that was active last iteration
+    pub schedule_window: Option<String>,
+    /// The profile applied last, by the schedule or by hand through the daemon
+    pub active_profile: Option<String>,
+    /// The governor wanted by a running app last iteration, None when no rule matched
+    pub app_governor: Option<String>,
+    /// When the last iteration ran, by the monotonic and the wall clock, to see a resume
+    pub last_iteration: Option<(Instant, SystemTime)>,
     pub csv_writer: CSVWriter,
+    /// Readings sent here get pushed to InfluxDB, set when the config has an influx section
+    pub influx: Option<Sender<String>>,
+    /// Finished hooks send their exit here to get logged
+    pub hook_sender: Sender<(String, logger::Severity)>,
+    pub hook_receiver: Receiver<(String, logger::Severity)>,
     /// How often to timeout per cycle when plugged in
     pub timeout: time::Duration,
     /// How often to timeout per cycle when on battery
     pub timeout_battery: time::Duration,
+    /// Changes the timeout based on the readings, used instead of the timeouts when set
+    pub poll: Option<AdaptivePoll>,
+    /// What to put back when the daemon exits, only captured in edit mode
+    pub startup_state: Option<StartupState>,
+    /// The package energy counter, if the system has one that can be read
+    pub rapl: Option<Rapl>,
+    /// The last energy counter reading and when it was read
+    pub last_energy: Option<(u64, Instant)>,
+    /// Package power in watts averaged over the last update cycle
+    pub package_power: Option<f32>,
+    /// Package energy used since the daemon started in microjoules, None without a counter
+    pub session_energy_uj: Option<u64>,
+    /// When the daemon started, for the power averaged over the session
+    pub started: Instant,
+    /// If key presses are read while the live view is shown
+    pub keys_enabled: bool,
+    /// How the per core table of the live view is sorted and scrolled
+    pub core_table: CoreTable,
+    /// The last error caused by a key press and when it happened
+    pub key_error: Option<(String, Instant)>,
 }
 
 fn make_gov_powersave(cpu: &mut CPU) -> Result<(), Error> {
@@ -179,13 +361,141 @@ fn make_gov_schedutil(cpu: &mut CPU) -> Result<(), Error> {
     Ok(())
 }
 
-/// Finds the average cpu usage based on a vector of CPUs
-fn calculate_average_usage(cpus: &Vec<CPU>) -> f32 {
-    let mut sum = 0.0;
-    for cpu in cpus {
-        sum += cpu.cur_usage;
+/// A frequency limit to write when switching power sources
+#[derive(Debug, PartialEq, Eq)]
+pub enum FreqTarget {
+    Khz(i32),
+    /// Back to what the hardware allows, for a limit only the last source had
+    Hardware,
+}
+
+/// What to write for a limit in MHz the last power source had and the new one has
+pub fn freq_target(applied: Option<i32>, wanted: Option<i32>) -> Option<FreqTarget> {
+    match (applied, wanted) {
+        (_, Some(mhz)) => Some(FreqTarget::Khz(mhz * 1000)),
+        (Some(_), None) => Some(FreqTarget::Hardware),
+        (None, None) => None,
+    }
+}
+
+/// Write the frequency limits of a core
+///
+/// A min that goes down is written first so the max can go under the old min, otherwise the max
+/// goes first so the min can go over the old max
+pub fn write_freq_limits(
+    core: usize,
+    min: &Option<FreqTarget>,
+    max: &Option<FreqTarget>,
+) -> Result<(), Error> {
+    let (cur_min, _) = scaling_freq_limits(core)?;
+    let needs_hardware = [min, max].contains(&&Some(FreqTarget::Hardware));
+    let (hw_min, hw_max) = if needs_hardware {
+        hardware_freq_limits(core)?
+    } else {
+        (0, 0)
+    };
+    let khz = |target: &FreqTarget, hardware: i32| match target {
+        FreqTarget::Khz(khz) => *khz,
+        FreqTarget::Hardware => hardware,
+    };
+    let min = min.as_ref().map(|target| khz(target, hw_min));
+    let max = max.as_ref().map(|target| khz(target, hw_max));
+
+    let write_min = || min.map_or(Ok(()), |min| set_min_freq(core, min));
+    let write_max = || max.map_or(Ok(()), |max| set_max_freq(core, max));
+    if min.is_some_and(|min| min < cur_min) {
+        write_min()?;
+        write_max()
+    } else {
+        write_max()?;
+        write_min()
+    }
+}
+
+/// Write the same limits to each of the cores, read and write are the sysfs ones outside of tests
+///
+/// When a core fails, the cores written before it get the limits they had back so no core is left
+/// with limits the others do not have. The error comes with the core that failed.
+fn write_cores_freq_limits(
+    cores: &[usize],
+    min: &Option<FreqTarget>,
+    max: &Option<FreqTarget>,
+    read: impl Fn(usize) -> Result<(i32, i32), Error>,
+    mut write: impl FnMut(usize, &Option<FreqTarget>, &Option<FreqTarget>) -> Result<(), Error>,
+) -> Result<(), (usize, Error)> {
+    let mut written = Vec::new();
+    for &core in cores {
+        let result = read(core).and_then(|before| {
+            write(core, min, max)?;
+            Ok(before)
+        });
+        match result {
+            Ok(before) => written.push((core, before)),
+            Err(e) => {
+                for (core, (min, max)) in written.into_iter().rev() {
+                    write(
+                        core,
+                        &Some(FreqTarget::Khz(min)),
+                        &Some(FreqTarget::Khz(max)),
+                    )
+                    .ok();
+                }
+                return Err((core, e));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write back the max frequency in kHz each core had before the thermal cap
+///
+/// A core that went offline since is skipped, every other one is tried even after one fails
+fn restore_max_freqs(
+    saved: &[(usize, i32)],
+    mut write: impl FnMut(usize, i32) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut result = Ok(());
+    for (core, khz) in saved {
+        match write(*core, *khz) {
+            Ok(_) | Err(Error::CoreOffline(_)) => {}
+            Err(e) => result = Err(e),
+        }
+    }
+    result
+}
+
+/// A limit for the journal, empty when there is none
+fn describe_mhz(mhz: Option<i32>) -> String {
+    mhz.map(|mhz| mhz.to_string()).unwrap_or_default()
+}
+
+/// Create the adaptive poll if it is turned on in the config
+fn make_poll(config: &Config) -> Option<AdaptivePoll> {
+    if !config.adaptive_polling {
+        return None;
+    }
+    Some(AdaptivePoll::new(
+        time::Duration::from_millis(config.min_poll_ms),
+        time::Duration::from_millis(config.max_poll_ms),
+    ))
+}
+
+/// Checks if the system was asleep between two iterations
+fn resumed_from_suspend(last: (Instant, SystemTime), now: (Instant, SystemTime)) -> bool {
+    let monotonic = now.0.duration_since(last.0);
+    match now.1.duration_since(last.1) {
+        Ok(wall) => wall > monotonic + SUSPEND_GAP,
+        // The wall clock was set back, that is not a suspend
+        Err(_) => false,
+    }
+}
+
+/// Checks if a governor that was set at `last_change` has been kept for `hold_secs`
+fn hold_elapsed(last_change: Option<Instant>, hold_secs: u64) -> bool {
+    match last_change {
+        Some(last) => last.elapsed().as_secs() >= hold_secs,
+        None => true,
     }
-    sum / (cpus.len() as f32)
 }
 
 impl Checker for Daemon {
@@ -212,7 +522,9 @@ impl Checker for Daemon {
                 self.last_below_cpu_usage_percent = Some(SystemTime::now());
             }
 
-            if self.usage <= self.config.high_cpu_threshold.into() {
+            // Usage has to drop under the low threshold before it stops counting as high, this
+            // keeps the governor from flipping when usage hovers around the high threshold
+            if self.usage <= self.config.low_cpu_threshold.into() {
                 self.last_below_cpu_usage_percent = None;
             }
 
@@ -263,7 +575,15 @@ impl Checker for Daemon {
         // The timeout when the device is connected to a power source
         self.timeout = time::Duration::from_millis(self.settings.delay);
 
+        self.poll = make_poll(&self.config);
+        sysfs::set_debounce(time::Duration::from_millis(self.config.sysfs_debounce_ms));
+
+        if self.settings.edit && !self.settings.testing {
+            self.startup_state = Some(StartupState::capture(&self.cpus));
+        }
+
         self.csv_writer.init(&mut self.logger);
+        self.load_history();
 
         if inside_wsl() {
             self.logger
@@ -275,25 +595,146 @@ impl Checker for Daemon {
         }
     }
 
+    /// Switch to a new config while running
+    fn apply_config(&mut self, config: Config) {
+        if config.metrics_port != self.config.metrics_port {
+            self.logger.log(
+                "metrics_port only changes when the daemon is restarted",
+                logger::Severity::Warning,
+            );
+        }
+
+        if config.remote_status_port != self.config.remote_status_port
+            || config.remote_status_bind != self.config.remote_status_bind
+        {
+            self.logger.log(
+                "remote_status_port only changes when the daemon is restarted",
+                logger::Severity::Warning,
+            );
+        }
+
+        if config.influx != self.config.influx {
+            self.logger.log(
+                "influx only changes when the daemon is restarted",
+                logger::Severity::Warning,
+            );
+        }
+
+        if config.log_backend != self.config.log_backend {
+            match logger::Backend::from_config(config.log_backend.as_deref()) {
+                Ok(backend) => self.logger.set_backend(backend),
+                Err(e) => self.logger.log(
+                    &format!("Invalid log_backend in config: {:?}", e),
+                    logger::Severity::Warning,
+                ),
+            }
+        }
+
+        if config.avg_window != self.config.avg_window {
+            self.freq_window = FreqWindow::new(config.avg_window);
+        }
+
+        // With a new schedule the profile of the current window is applied again, otherwise a
+        // profile applied by hand stays until the next window starts
+        if config.schedule != self.config.schedule {
+            self.schedule_window = None;
+        }
+
+        self.poll = make_poll(&config);
+        sysfs::set_debounce(time::Duration::from_millis(config.sysfs_debounce_ms));
+        self.config = config;
+        self.log_config_problems();
+
+        // Features that were found to be unsupported get checked again
+        self.do_charge_threshold = self.do_update_battery;
+        self.do_battery_turbo = true;
+        self.do_freq_limits = true;
+        self.do_thermal_cap = true;
+        self.do_notify = true;
+        self.do_schedule = true;
+
+        // The state machine picks the state again with the new rules on the next iteration
+        self.state = State::Unknown;
+    }
+
+    /// Log what is wrong with the config for this system, e.g. a governor the driver does not offer
+    ///
+    /// The daemon keeps running with it, setting a governor that is not offered gets skipped
+    fn log_config_problems(&mut self) {
+        for problem in validate_config(&self.config, &Hardware::detect()) {
+            self.logger.log(
+                &format!("Config problem, {}", problem),
+                logger::Severity::Warning,
+            );
+        }
+    }
+
+    /// Put the governors and turbo back to how they were when the daemon started
+    fn restore_startup_state(&mut self) {
+        if !self.config.restore_on_exit {
+            return;
+        }
+
+        self.lift_freq_limits();
+
+        let startup_state = match self.startup_state.take() {
+            Some(startup_state) => startup_state,
+            None => return,
+        };
+
+        for (core, gov) in startup_state.governors {
+            if let Err(e) = set_governor_for_core(core, &gov) {
+                warn_user!(format!(
+                    "Could not restore governor of cpu{}: {:?}",
+                    core, e
+                ));
+            }
+        }
+
+        if let Some(turbo) = startup_state.turbo {
+            if let Err(e) = set_turbo(turbo) {
+                warn_user!(format!("Could not restore turbo: {:?}", e));
+            }
+        }
+    }
+
     /// This function is run at the start of each iteration of ACS
     fn start_loop(&mut self) -> Result<(), Error> {
         // Update all the values for each cpu before they get used
         self.update_all()?;
 
+        self.log_hook_exits();
+
         // Update current states
-        self.charging = self.power.read_power_source().unwrap_or(true);
+        let charging = self.power.read_power_source().unwrap_or(true);
         self.charge = self.battery.capacity;
+        if charging != self.charging {
+            self.charging = charging;
+            self.run_hook(Event::from_charging(charging), None);
+        }
         self.lid_state = self.lid.read_lid_state()?;
-        self.usage = calculate_average_usage(&self.cpus) * 100.0;
 
         self.csv_writer
             .write(self.cpus.iter().map(|c| c as _), &mut self.logger);
 
+        if let Some(influx) = &self.influx {
+            let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?;
+            // Sending only fails once the push thread is gone, which it logged already
+            influx
+                .send(influx::line_protocol(&self.cpus, timestamp.as_nanos()))
+                .ok();
+        }
+
         Ok(())
     }
 
     /// This function is run at the end of each iteration of ACS
     fn end_loop(&mut self) {
+        for message in sysfs::take_dry_run_log() {
+            self.logger.log(&message, logger::Severity::Log);
+        }
+        self.save_history(false);
+
         // Print the each cpu, each iteration
         if self.settings.verbose {
             self.print();
@@ -304,21 +745,122 @@ impl Checker for Daemon {
     fn single_edit(&mut self) -> Result<(), Error> {
         self.start_loop()?;
 
+        // The kernel and firmware can reset governors, frequency limits and turbo on resume
+        let now = (Instant::now(), SystemTime::now());
+        let resumed = self
+            .last_iteration
+            .is_some_and(|last| resumed_from_suspend(last, now));
+        self.last_iteration = Some(now);
+
+        // A write that fails, e.g. while the hardware is still waking up, is logged and skipped
+        let reason = if self.paused {
+            None
+        } else if resumed {
+            Some("the system resumed from suspend")
+        } else if self.cores_added {
+            // A core brought back online starts out with the kernel's defaults
+            Some("a core came online")
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            if let Err(e) = self.reapply(reason) {
+                self.logger.log(
+                    &format!("Could not apply the settings again: {:?}", e),
+                    logger::Severity::Warning,
+                );
+            }
+        }
+        self.cores_added = false;
+
         if !self.paused {
+            // Some firmware resets the threshold after a suspend, so it gets checked every time
+            self.apply_charge_threshold();
+
+            self.apply_battery_turbo();
+            self.apply_freq_limits();
+            self.apply_schedule();
+            self.apply_thermal_cap();
+
             let state = self.run_state_machine();
 
-            // Check if the state has changed since the last time we checked
-            if self.state != state {
-                self.logger.log(
-                    &format!("State changed: {:?} -> {:?}", self.state, state,),
+            // A running app from the rules picks the governor, unless the cpu is overheating
+            let app = match state {
+                State::Overheating => None,
+                _ => self.check_app_governors(),
+            };
+            let app_gov = app.as_ref().map(|(_, gov)| gov.clone());
+
+            let last_gov = self
+                .app_governor
+                .clone()
+                .unwrap_or_else(|| get_governor(&self.state).to_string());
+            let gov = app_gov
+                .clone()
+                .unwrap_or_else(|| get_governor(&state).to_string());
+            let gov_changed = last_gov != gov;
+
+            // Overheating is switched to right away to protect the hardware
+            let held = gov_changed
+                && state != State::Overheating
+                && !hold_elapsed(self.last_gov_change, self.config.min_governor_hold_secs);
+
+            if held {
+                // Keep the old state so the change gets tried again next iteration
+                self.end_loop();
+                return Ok(());
+            }
+
+            // Check if the state or the running apps changed since the last time we checked
+            let overheated = state == State::Overheating && self.state != State::Overheating;
+
+            if self.state != state || self.app_governor != app_gov {
+                let message = match &app {
+                    Some((pattern, _)) => format!(
+                        "State changed: {:?} -> {:?}, {} is running",
+                        self.state, state, pattern
+                    ),
+                    None => format!("State changed: {:?} -> {:?}", self.state, state),
+                };
+
+                self.logger.log_with(
+                    &message,
                     logger::Severity::Log,
+                    &[
+                        ("GOVERNOR", gov.to_string()),
+                        (
+                            "FREQ_MHZ",
+                            ((check_cpu_freq(&self.cpus) / 1000.0) as i32).to_string(),
+                        ),
+                    ],
                 );
+                if let Err(e) = self.set_govs(gov.clone()) {
+                    self.logger.log(
+                        &format!("Could not set the {} governor: {:?}", gov, e),
+                        logger::Severity::Warning,
+                    );
+                }
+
+                // Cores pinned in the config keep their own governor
+                self.set_core_govs();
 
-                // Change the cpu governor based on the state
-                self.set_govs(get_governor(&state).to_string())?;
+                // Switch the energy performance preference along with the governor
+                self.set_epps(&gov);
+
+                if gov_changed {
+                    self.last_gov_change = Some(Instant::now());
+                }
             }
 
             self.state = state;
+            self.app_governor = app_gov;
+
+            if overheated {
+                self.run_hook(Event::Overheat, None);
+            }
+            if gov_changed {
+                self.run_hook(Event::GovernorChange, None);
+            }
         }
 
         self.end_loop();
@@ -348,19 +890,37 @@ impl Checker for Daemon {
             }
         }
 
+        self.update_online_cores();
+
         let cur_proc = parse_proc_file(read_proc_stat_file()?);
+        let utilization = calculate_utilization(&self.last_proc, &cur_proc);
         for cpu in self.cpus.iter_mut() {
             cpu.update()?;
-            for (i, proc) in self.last_proc.iter().enumerate() {
-                if cpu.name == proc.cpu_name {
-                    cpu.update_usage(proc, &cur_proc[i])?;
-                }
+            // Without a delta, e.g. on the first sample, the last usage is kept
+            if let Some((_, usage)) = utilization.cores.iter().find(|(name, _)| *name == cpu.name) {
+                cpu.cur_usage = *usage;
             }
         }
+        apply_core_temps(&mut self.cpus);
+        if let Some(overall) = utilization.overall {
+            self.usage = overall * 100.0;
+        }
 
         self.last_proc = cur_proc;
+        self.freq_window.push(&self.cpus);
+
+        self.update_package_power();
+
+        self.temp_max = (self.read_temp() / 1000) as i8;
+        self.notify_thermal();
+        self.update_throttling();
 
-        self.temp_max = (get_highest_temp(&self.cpus) / 1000) as i8;
+        self.history.push(Sample {
+            time: Local::now(),
+            freq_mhz: check_cpu_freq(&self.cpus) as f64 / 1000.0,
+            temp_c: check_cpu_temperature(&self.cpus) as f64 / 1000.0,
+            usage: self.usage as f64,
+        });
 
         // Update the data in the graph and render it
         if self.settings.graph == GraphType::Usage {
@@ -380,6 +940,236 @@ impl Checker for Daemon {
         Ok(())
     }
 
+    /// Follow cores being taken offline and brought back, e.g. with 'acs set core'
+    fn update_online_cores(&mut self) {
+        let online = match online_cores() {
+            Ok(online) => online,
+            Err(_) => return,
+        };
+        let known: Vec<usize> = self.cpus.iter().map(|cpu| cpu.number as usize).collect();
+        if online == known {
+            return;
+        }
+
+        // A core that can not be read yet is left out and tried again on the next iteration
+        let cpus = read_cpus();
+        let read: Vec<usize> = cpus.iter().map(|cpu| cpu.number as usize).collect();
+        if read == known {
+            return;
+        }
+
+        self.cores_added = read.iter().any(|core| !known.contains(core));
+        self.cpus = cpus;
+        self.logger.log(
+            &format!("The online cores changed to {:?}", read),
+            logger::Severity::Log,
+        );
+    }
+
+    /// Log when the cores start and stop throttling, only the change gets logged
+    fn update_throttling(&mut self) {
+        let throttling = self.throttle.update(&self.cpus);
+        if !throttling.is_empty() && self.throttling.is_empty() {
+            self.logger.log(
+                &format!(
+                    "Thermal throttling on {} at {}C",
+                    throttling.join(", "),
+                    self.temp_max
+                ),
+                logger::Severity::Warning,
+            );
+        } else if throttling.is_empty() && !self.throttling.is_empty() {
+            self.logger
+                .log("Thermal throttling stopped", logger::Severity::Log);
+        }
+        self.throttling = throttling;
+    }
+
+    /// The temperature that decides overheating, from the zones in the config or else the cores
+    fn read_temp(&mut self) -> i32 {
+        if self.config.thermal_zones.is_empty() {
+            return get_highest_temp(&self.cpus);
+        }
+
+        let zones = read_thermal_zones().unwrap_or_default();
+        match zone_temp(&zones, &self.config.thermal_zones) {
+            Some(temp) => {
+                self.thermal_zones_missing = false;
+                temp
+            }
+            None => {
+                // Only log when the zones go missing, not every iteration
+                if !self.thermal_zones_missing {
+                    self.thermal_zones_missing = true;
+                    self.logger.log(
+                        &format!(
+                            "None of the thermal zones {:?} were found, using the cores",
+                            self.config.thermal_zones
+                        ),
+                        logger::Severity::Warning,
+                    );
+                }
+                get_highest_temp(&self.cpus)
+            }
+        }
+    }
+
+    /// Send a desktop notification when the temperature crosses notify_temp
+    fn notify_thermal(&mut self) {
+        let threshold = match self.config.notify_temp {
+            Some(threshold) => threshold,
+            None => return,
+        };
+        if !self.do_notify {
+            return;
+        }
+
+        let event = match thermal_event(self.notified_hot, self.temp_max, threshold) {
+            Some(event) => event,
+            None => return,
+        };
+        self.notified_hot = event == ThermalEvent::Hot;
+
+        if !notify::available() {
+            self.do_notify = false;
+            self.logger.log(
+                "notify_temp is set but notifications can not be sent, needs the notify feature, notify-send and a session bus",
+                logger::Severity::Warning,
+            );
+            return;
+        }
+
+        if let Err(e) = notify::send(event.summary(), &event.body(self.temp_max, threshold)) {
+            self.do_notify = false;
+            self.logger.log(
+                &format!("Could not send a notification: {:?}", e),
+                logger::Severity::Error,
+            );
+        }
+    }
+
+    /// Start the script set for an event in the hooks table, if there is one
+    fn run_hook(&mut self, event: Event, profile: Option<String>) {
+        let path = match self.config.hooks.get(event.name()) {
+            Some(path) => path.clone(),
+            None => return,
+        };
+
+        // A hook can change anything, so a dry run only logs it
+        if sysfs::dry_run() {
+            self.logger.log(
+                &format!("Dry run, not running hook {} for {}", path, event.name()),
+                logger::Severity::Log,
+            );
+            return;
+        }
+
+        let info = HookInfo {
+            governor: self
+                .app_governor
+                .clone()
+                .unwrap_or_else(|| get_governor(&self.state).to_string()),
+            state: format!("{:?}", self.state),
+            temp: self.temp_max,
+            charging: self.charging,
+            battery_percent: self.charge,
+            profile,
+        };
+        if let Err(e) = hooks::run(event, &path, &info, self.hook_sender.clone()) {
+            self.logger.log(
+                &format!("Could not run hook {} for {}: {:?}", path, event.name(), e),
+                logger::Severity::Error,
+            );
+        }
+    }
+
+    /// Pick up the readings from before the last restart, the live graph starts with them too
+    fn load_history(&mut self) {
+        let path = match &self.config.history_file {
+            Some(path) => path.clone(),
+            None => return,
+        };
+        let max_age = time::Duration::from_secs(self.config.history_max_age_hours * 3600);
+
+        match History::load(Path::new(&path), max_age) {
+            Ok(history) => self.history = history,
+            // Nothing has been saved yet
+            Err(Error::IO(e)) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => self.logger.log(
+                &format!(
+                    "Starting a new history, {} could not be read: {:?}",
+                    path, e
+                ),
+                logger::Severity::Warning,
+            ),
+        }
+
+        let graph = &self.settings.graph;
+        self.grapher.vals = self
+            .history
+            .samples()
+            .iter()
+            .map(|sample| sample.value(graph))
+            .collect();
+    }
+
+    /// Write the history file once history_flush_secs passed since the last time, or right away
+    fn save_history(&mut self, force: bool) {
+        let path = match &self.config.history_file {
+            Some(path) => path.clone(),
+            None => return,
+        };
+        let flush = time::Duration::from_secs(self.config.history_flush_secs);
+        if !force && self.history_saved.elapsed() < flush {
+            return;
+        }
+        self.history_saved = Instant::now();
+
+        if let Err(e) = self.history.save(Path::new(&path)) {
+            self.logger.log(
+                &format!("Could not save the history to {}: {:?}", path, e),
+                logger::Severity::Error,
+            );
+        }
+    }
+
+    /// Log the exits of the hooks that finished since the last iteration
+    fn log_hook_exits(&mut self) {
+        while let Ok((message, severity)) = self.hook_receiver.try_recv() {
+            self.logger.log(&message, severity);
+        }
+    }
+
+    /// Compute the package power from the energy used since the last update
+    fn update_package_power(&mut self) {
+        let rapl = match &self.rapl {
+            Some(rapl) => rapl,
+            None => return,
+        };
+
+        let energy = match rapl.read_energy() {
+            Ok(energy) => energy,
+            Err(e) => {
+                // Most likely not running as root, so stop trying
+                self.logger.log(
+                    &format!("Could not read package energy: {:?}", e),
+                    logger::Severity::Warning,
+                );
+                self.rapl = None;
+                return;
+            }
+        };
+        let now = Instant::now();
+
+        if let Some((last_energy, last_time)) = self.last_energy {
+            let delta = rapl.energy_delta(last_energy, energy);
+            self.package_power = Some(to_watts(delta, now.duration_since(last_time)));
+            self.session_energy_uj = Some(self.session_energy_uj.unwrap_or(0) + delta);
+        }
+
+        self.last_energy = Some((energy, now));
+    }
+
     /// All text is rendered before anything is printed
     /// This method of rendering text reduces lag and fixes a flickering problem from before 0.1.8
     /// This section is just a chunk of the text that gets rendered
@@ -387,19 +1177,108 @@ impl Checker for Daemon {
         let message = format!("{}\n", self.message);
         let title = "Name\tMax\tMin\tFreq\tTemp\tUsage\tGovernor\n";
 
-        // Render each line of cpu core
+        // Render each line of cpu core that fits, marking the hottest and fastest when they differ
+        let highest = |values: Vec<i32>| {
+            let max = values.iter().max().copied();
+            (values.iter().min().copied() != max)
+                .then_some(max)
+                .flatten()
+        };
+        let hottest = highest(self.cpus.iter().map(|c| c.cur_temp).collect());
+        let fastest = highest(self.cpus.iter().map(|c| c.cur_freq).collect());
+
         let mut cpus = String::new();
-        for c in &self.cpus {
-            cpus.push_str(&c.to_string());
+        for c in self.core_table.visible(&self.cpus) {
+            let mut marks = Vec::new();
+            if hottest == Some(c.cur_temp) {
+                marks.push(paint(Color::RED, "hottest"));
+            }
+            if fastest == Some(c.cur_freq) {
+                marks.push(paint(Color::GREEN, "fastest"));
+            }
+            let line = c.to_string();
+            if marks.is_empty() {
+                cpus.push_str(&line);
+            } else {
+                cpus.push_str(&format!("{}\t{}\n", line.trim_end(), marks.join(" ")));
+            }
         }
 
+        let total = self.cpus.len();
+        if self.core_table.has_info(total) {
+            let shown = self.core_table.lines(total) - 1;
+            let first = if shown == total {
+                1
+            } else {
+                self.core_table.offset + 1
+            };
+            cpus.push_str(&format!(
+                "Cores {}-{} of {} by {}\n",
+                first,
+                first + shown - 1,
+                total,
+                self.core_table.sort.name()
+            ));
+        }
+
+        let throttling = if self.throttling.is_empty() {
+            String::new()
+        } else {
+            format!("{}\n", render_throttling(&self.throttling))
+        };
+        let thermal_cap = match self.thermal_cap {
+            Some(khz) => format!("Thermal cap: {}\n", format_freq(khz as f64)),
+            None => String::new(),
+        };
+
+        let freq_window = match self.freq_window.mean() {
+            Some(avg) => format!(
+                "Freq: {} now, {} averaged over {} readings\n",
+                format_freq(check_cpu_freq(&self.cpus) as f64),
+                format_freq(avg as f64),
+                self.freq_window.samples()
+            ),
+            None => String::new(),
+        };
+
+        let gpus: String = read_gpu_clocks(&self.gpus)
+            .iter()
+            .map(|gpu| format!("{}\n", render_gpu_clock(gpu)))
+            .collect();
+
+        let fans: String = read_fan_speeds(&self.fans)
+            .iter()
+            .map(|fan| format!("{}\n", render_fan_speed(fan)))
+            .collect();
+
+        // Shown under the frequency of each core when it can be read
+        let package_power = match (self.package_power, self.session_energy_uj) {
+            (Some(watts), Some(energy)) => format!(
+                "Package power: {:.2}W, {:.3}Wh used this session\n",
+                watts,
+                to_watt_hours(energy)
+            ),
+            (Some(watts), None) => format!("Package power: {:.2}W\n", watts),
+            _ => String::new(),
+        };
+
         // Prints battery percent or N/A if not
         let battery_status = print_battery_status(&self.battery);
         let battery_condition = format!("Condition: {}%", self.battery.condition);
 
         format!(
-            "{}{}{}\n{}\n{}\n",
-            message, title, cpus, battery_status, battery_condition
+            "{}{}{}{}{}{}{}{}{}\n{}\n{}\n",
+            message,
+            title,
+            cpus,
+            throttling,
+            thermal_cap,
+            freq_window,
+            gpus,
+            fans,
+            package_power,
+            battery_status,
+            battery_condition
         )
     }
 
@@ -421,7 +1300,14 @@ impl Checker for Daemon {
             String::from("")
         };
 
-        let stop_message = String::from("ctrl+c to stop running");
+        let stop_message = if self.keys_enabled {
+            String::from(
+                "g: cycle governor, t: toggle turbo, s: sort cores, up and down: scroll cores, \
+                 q or ctrl+c to stop running",
+            )
+        } else {
+            String::from("ctrl+c to stop running")
+        };
 
         // render all of the logs, e.g.
         // notice: 2022-01-13 00:02:17 -> Governor set to performance because battery is charging
@@ -442,9 +1328,17 @@ impl Checker for Daemon {
             String::from("")
         };
 
+        // Errors from key presses only stay on screen for a moment
+        let key_error = match &self.key_error {
+            Some((error, time)) if time.elapsed() < KEY_ERROR_DURATION => {
+                format!("\n{}", paint(Color::RED, error.as_str()))
+            }
+            _ => String::new(),
+        };
+
         format!(
-            "{}\n{}\n\n{}\n\n{}\n\n{}",
-            graph_type, graph, commit, stop_message, logs
+            "{}\n{}\n\n{}\n\n{}\n\n{}{}",
+            graph_type, graph, commit, stop_message, logs, key_error
         )
     }
 
@@ -457,14 +1351,30 @@ impl Checker for Daemon {
             self.graph = self.grapher.update_one(&mut self.grapher.vals.clone());
         }
 
-        let (term_width, _term_height) = termion::terminal_size().unwrap_or((80, 80));
+        let (term_width, term_height) = termion::terminal_size().unwrap_or((80, 80));
 
         // Render two sections of the output
         // Rendering before screen is cleared reduces the time between clear and print
         // This reduces and completely avoids all flickering
-        let preprint = self.preprint_render();
+        let mut preprint = self.preprint_render();
         let postprint = self.postprint_render();
 
+        // Only show as many cores as fit, checked every time so a resized terminal gets used
+        let table_lines = self.core_table.lines(cores);
+        // The line left by clearing the screen and the turbo status
+        let other_lines = preprint.lines().count() + postprint.lines().count() + 2 - table_lines;
+        let free = (term_height as usize).saturating_sub(other_lines);
+        let rows = if cores + (self.core_table.sort != SortKey::Core) as usize <= free {
+            None
+        } else {
+            // One line goes to saying which cores are shown
+            Some(free.saturating_sub(1).max(MIN_CORE_ROWS))
+        };
+        if rows != self.core_table.rows {
+            self.core_table.rows = rows;
+            preprint = self.preprint_render();
+        }
+
         let mut effective_delay = self.timeout_battery;
         if self.charging {
             effective_delay = self.timeout;
@@ -484,7 +1394,7 @@ impl Checker for Daemon {
         print!("{}", preprint);
 
         print_turbo_status(
-            cores,
+            self.core_table.lines(cores),
             self.settings.animation,
             term_width.into(),
             delay_in_millis,
@@ -495,6 +1405,13 @@ impl Checker for Daemon {
     }
 
     fn set_govs(&mut self, gov: String) -> Result<(), Error> {
+        // The error lists the governors the driver does offer
+        if let Ok(govs) = check_available_governors() {
+            if !govs.contains(&gov) {
+                return Err(Error::InvalidValue(gov, govs));
+            }
+        }
+
         // Apply the governor provided, to the CPUs
         if gov == *"performance" {
             return self.apply_to_cpus(&make_gov_performance);
@@ -509,21 +1426,484 @@ impl Checker for Daemon {
             return self.apply_to_cpus(&make_gov_schedutil);
         }
 
-        if let Ok(govs) = check_available_governors() {
-            // If the coverner provided DOES exist for the machine, but is not supported, give a
-            // message saying it's not supported
-            if govs.contains(&gov) {
+        // Any other governor the driver offers, e.g. ondemand or conservative
+        for cpu in &self.cpus {
+            match set_governor_for_core(cpu.number as usize, &gov) {
+                Ok(_) | Err(Error::CoreOffline(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the per-core governors from `config.core_governors`
+    ///
+    /// Cores that are offline or a range that fails to parse get logged and skipped so one bad
+    /// entry does not stop the rest of the mapping from being applied
+    fn set_core_govs(&mut self) {
+        for (range, gov) in self.config.core_governors.clone() {
+            let (first, last) = match parse_core_range(&range) {
+                Ok(r) => r,
+                Err(_) => {
+                    self.logger.log(
+                        &format!("Invalid core range \"{}\" in config", range),
+                        logger::Severity::Warning,
+                    );
+                    continue;
+                }
+            };
+
+            for core in first..=last {
+                if let Err(e) = set_governor_for_core(core, &gov) {
+                    self.logger.log_with(
+                        &format!("Could not set cpu{} to {}: {:?}", core, gov, e),
+                        logger::Severity::Warning,
+                        &[("CORE", core.to_string()), ("GOVERNOR", gov.clone())],
+                    );
+                }
+            }
+        }
+    }
+
+    /// Apply the energy performance preference from the config that matches the governor
+    fn set_epps(&mut self, gov: &str) {
+        let epp = match gov {
+            "performance" => self.config.performance_epp.clone(),
+            _ => self.config.powersave_epp.clone(),
+        };
+
+        let epp = match epp {
+            Some(epp) => epp,
+            None => return,
+        };
+        // Without a driver that has it there is nothing to set on any core
+        if !capabilities().epp {
+            return;
+        }
+
+        for cpu in self.cpus.clone() {
+            if let Err(e) = set_epp(cpu.number as usize, &epp) {
+                self.logger.log(
+                    &format!("Could not set epp of {} to {}: {:?}", cpu.name, epp, e),
+                    logger::Severity::Warning,
+                );
+            }
+        }
+    }
+
+    /// Write the charge threshold from the config if the battery is not already using it
+    fn apply_charge_threshold(&mut self) {
+        let percent = match self.config.charge_threshold {
+            Some(percent) if self.do_charge_threshold => percent,
+            _ => return,
+        };
+
+        if self.battery.get_charge_threshold().ok() == Some(percent) {
+            return;
+        }
+
+        match self.battery.set_charge_threshold(percent) {
+            Ok(_) => self.logger.log(
+                &format!("Battery charge threshold set to {}%", percent),
+                logger::Severity::Log,
+            ),
+            Err(e) => {
+                // Stop trying on hardware without a threshold instead of logging every cycle
+                self.do_charge_threshold = false;
+                self.logger.log(
+                    &format!("Could not set battery charge threshold: {:?}", e),
+                    logger::Severity::Warning,
+                );
+            }
+        }
+    }
+
+    /// Turn turbo off when unplugged and put it back the way it was when plugged in
+    fn apply_battery_turbo(&mut self) {
+        if !self.config.disable_turbo_on_battery || !self.do_battery_turbo {
+            return;
+        }
+
+        let result = if !self.charging && self.turbo_before_battery.is_none() {
+            get_turbo().and_then(|enabled| {
+                set_turbo(false)?;
+                self.turbo_before_battery = Some(enabled);
                 self.logger
-                    .log("Other governors not supported yet", logger::Severity::Log);
-            } else {
-                // The governor doesn't exist for the machine - cannot be set
-                warn_user!("Governor not available.");
+                    .log("Turbo disabled while on battery", logger::Severity::Log);
+                Ok(())
+            })
+        } else if self.charging {
+            match self.turbo_before_battery.take() {
+                Some(enabled) => set_turbo(enabled),
+                None => Ok(()),
             }
         } else {
-            warn_user!(format!("Error checking \"{}\" governor.", gov));
+            Ok(())
+        };
+
+        if let Err(e) = result {
+            self.do_battery_turbo = false;
+            self.logger.log(
+                &format!("Could not switch turbo for battery: {:?}", e),
+                logger::Severity::Warning,
+            );
+        }
+    }
+
+    /// Apply the frequency limits of the power source to every online core
+    ///
+    /// Only writes when the power source or its limits change, so a limit set by hand in between
+    /// is kept. A limit only the last source had goes back to what the hardware allows.
+    fn apply_freq_limits(&mut self) {
+        if !self.do_freq_limits {
+            return;
+        }
+
+        let limits = self.config.freq_limits(self.charging);
+        if self.freq_source == Some(self.charging) && limits == self.freq_applied {
+            return;
+        }
+        self.freq_source = Some(self.charging);
+        if limits.is_empty() && self.freq_applied.is_empty() {
+            return;
+        }
+
+        let min = freq_target(self.freq_applied.min_freq, limits.min_freq);
+        let max = freq_target(self.freq_applied.max_freq, limits.max_freq);
+        let cores = self.online_core_numbers();
+        // The cores keep the limits they had when one fails, so freq_applied stays true
+        if let Err((core, e)) =
+            write_cores_freq_limits(&cores, &min, &max, scaling_freq_limits, write_freq_limits)
+        {
+            self.do_freq_limits = false;
+            self.logger.log_with(
+                &format!("Could not set the frequency limits: {:?}", e),
+                logger::Severity::Warning,
+                &[("CORE", core.to_string())],
+            );
+            return;
+        }
+
+        let source = if self.charging { "AC" } else { "battery" };
+        let describe = |name: &str, mhz: Option<i32>| {
+            mhz.map(|mhz| format!("{} {}", name, format_freq(mhz as f64 * 1000.0)))
+        };
+        let set: Vec<String> = [
+            describe("min", limits.min_freq),
+            describe("max", limits.max_freq),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if set.is_empty() {
+            self.logger.log(
+                &format!("Frequency limits lifted on {}", source),
+                logger::Severity::Log,
+            );
+        } else {
+            self.logger.log_with(
+                &format!("Frequency limits on {}: {}", source, set.join(", ")),
+                logger::Severity::Log,
+                &[
+                    ("MIN_FREQ_MHZ", describe_mhz(limits.min_freq)),
+                    ("MAX_FREQ_MHZ", describe_mhz(limits.max_freq)),
+                ],
+            );
+        }
+        self.freq_applied = limits;
+        self.thermal_rewrite = true;
+    }
+
+    /// Step the max frequency of every online core down while over throttle_temp and back up
+    /// once under throttle_recover_temp, only with active_throttle on
+    ///
+    /// The max frequency each core had before the first step is kept and written back once the
+    /// cap is lifted, so a max set by a profile or the power source is not lost
+    fn apply_thermal_cap(&mut self) {
+        if !self.do_thermal_cap {
+            return;
+        }
+        // Nothing to read or write until it is too hot, or when turned off without a cap
+        let hot = self.config.active_throttle && self.temp_max > self.config.throttle_temp;
+        if self.thermal_cap.is_none() && !hot {
+            self.thermal_rewrite = false;
+            return;
+        }
+
+        // What is there before the first step, or after something else wrote the max, is kept
+        if self.thermal_cap.is_none() || self.thermal_rewrite {
+            self.thermal_saved.clear();
         }
+        let mut cores = Vec::new();
+        for cpu in &self.cpus {
+            let core = cpu.number as usize;
+            if !is_core_online(core) {
+                continue;
+            }
+            let (min, max) = match scaling_freq_limits(core) {
+                Ok(limits) => limits,
+                Err(e) => {
+                    self.do_thermal_cap = false;
+                    self.logger.log_with(
+                        &format!("Could not read the frequency limits: {:?}", e),
+                        logger::Severity::Warning,
+                        &[("CORE", core.to_string())],
+                    );
+                    return;
+                }
+            };
+            // A core that came online since the first step has never been capped
+            let saved = match self.thermal_saved.iter().find(|(c, _)| *c == core) {
+                Some((_, saved)) => *saved,
+                None => {
+                    self.thermal_saved.push((core, max));
+                    max
+                }
+            };
+            cores.push((core, min, saved.max(min)));
+        }
+        let floor = cores.iter().map(|(_, min, _)| *min).min().unwrap_or(0);
+        let ceiling = cores.iter().map(|(_, _, max)| *max).max().unwrap_or(0);
+
+        let cap = if self.config.active_throttle {
+            let steps = ThrottleSteps {
+                throttle_temp: self.config.throttle_temp,
+                recover_temp: self.config.throttle_recover_temp,
+                step_khz: self.config.throttle_step_mhz * 1000,
+            };
+            next_freq_cap(self.thermal_cap, self.temp_max, &steps, floor, ceiling)
+        } else {
+            None
+        };
+        if cap == self.thermal_cap && !self.thermal_rewrite {
+            return;
+        }
+
+        let written = match cap {
+            Some(cap) => cores
+                .iter()
+                .try_for_each(|(core, min, max)| set_max_freq(*core, cap.clamp(*min, *max))),
+            None => restore_max_freqs(&self.thermal_saved, set_max_freq),
+        };
+        if let Err(e) = written {
+            self.do_thermal_cap = false;
+            self.logger.log(
+                &format!("Could not set the thermal frequency cap: {:?}", e),
+                logger::Severity::Warning,
+            );
+            return;
+        }
+
+        if cap != self.thermal_cap {
+            let message = match cap {
+                Some(khz) => format!(
+                    "Thermal cap at {} for {}C",
+                    format_freq(khz as f64),
+                    self.temp_max
+                ),
+                None => format!("Thermal cap lifted at {}C", self.temp_max),
+            };
+            self.logger.log(&message, logger::Severity::Log);
+        }
+        if cap.is_none() {
+            self.thermal_saved.clear();
+        }
+        self.thermal_cap = cap;
+        self.thermal_rewrite = false;
+    }
+
+    /// Lift the frequency limits of the power source, for when the daemon stops
+    fn lift_freq_limits(&mut self) {
+        if self.freq_applied.is_empty() {
+            return;
+        }
+        let min = freq_target(self.freq_applied.min_freq, None);
+        let max = freq_target(self.freq_applied.max_freq, None);
+        let cores = self.online_core_numbers();
+        match write_cores_freq_limits(&cores, &min, &max, scaling_freq_limits, write_freq_limits) {
+            Ok(_) => self.freq_applied = FreqLimits::default(),
+            Err((core, e)) => warn_user!(format!(
+                "Could not lift the frequency limits of cpu{}: {:?}",
+                core, e
+            )),
+        }
+    }
+
+    /// The numbers of the cores that are online right now
+    fn online_core_numbers(&self) -> Vec<usize> {
+        self.cpus
+            .iter()
+            .map(|cpu| cpu.number as usize)
+            .filter(|&core| is_core_online(core))
+            .collect()
+    }
+
+    /// Put back the max frequency the cores had before the thermal cap, for when the daemon stops
+    fn lift_thermal_cap(&mut self) {
+        if self.thermal_cap.take().is_none() {
+            return;
+        }
+        if let Err(e) = restore_max_freqs(&self.thermal_saved, set_max_freq) {
+            warn_user!(format!("Could not lift the thermal cap: {:?}", e));
+        }
+        self.thermal_saved.clear();
+    }
+
+    /// Apply the profile of the time window the local time is in when a new window starts
+    fn apply_schedule(&mut self) {
+        if !self.do_schedule || self.config.schedule.is_empty() {
+            return;
+        }
+
+        let now = Local::now();
+        let minute = (now.hour() * 60 + now.minute()) as u16;
+
+        let (window, profile) = match scheduled_profile(&self.config, minute) {
+            Ok(Some((window, profile))) => (Some(window.to_string()), Some(profile.to_string())),
+            Ok(None) => (None, None),
+            Err(e) => {
+                self.do_schedule = false;
+                self.logger.log(
+                    &format!("Not using the schedule: {:?}", e),
+                    logger::Severity::Error,
+                );
+                return;
+            }
+        };
+
+        if window == self.schedule_window {
+            return;
+        }
+        self.schedule_window = window;
+
+        let name = match profile {
+            Some(name) => name,
+            None => return,
+        };
+
+        match self.apply_named_profile(&name) {
+            Ok(_) => self.logger.log(
+                &format!("Applied scheduled profile '{}'", name),
+                logger::Severity::Log,
+            ),
+            Err(e) => self.logger.log(
+                &format!("Could not apply scheduled profile '{}': {:?}", name, e),
+                logger::Severity::Warning,
+            ),
+        }
+    }
+
+    /// Apply a profile from the config and keep it as the one to apply again after a resume
+    fn apply_named_profile(&mut self, name: &str) -> Result<(), Error> {
+        find_profile(&self.config, name).and_then(apply_profile)?;
+        self.active_profile = Some(name.to_string());
+        // A thermal cap keeps the max of the profile to put back
+        self.thermal_rewrite = true;
+        self.run_hook(Event::ProfileChange, Some(name.to_string()));
         Ok(())
     }
+
+    /// Write everything the daemon manages again, for when something else, like a resume, reset it
+    ///
+    /// Everything is written even when the governor fails, the error is returned after
+    fn reapply(&mut self, reason: &str) -> Result<(), Error> {
+        self.logger.log(
+            &format!("Applying settings again since {}", reason),
+            logger::Severity::Log,
+        );
+
+        // Nothing has been applied before the state machine picked a state
+        let mut result = Ok(());
+        if self.state != State::Unknown {
+            let gov = self
+                .app_governor
+                .clone()
+                .unwrap_or_else(|| get_governor(&self.state).to_string());
+            result = self.set_govs(gov.clone());
+            self.set_core_govs();
+            self.set_epps(&gov);
+        }
+
+        // The profile applied last stays, even one applied by hand inside a scheduled window
+        if let Some(name) = self.active_profile.clone() {
+            if let Err(e) = self.apply_named_profile(&name) {
+                self.logger.log(
+                    &format!("Could not apply profile '{}' again: {:?}", name, e),
+                    logger::Severity::Warning,
+                );
+                result = result.and(Err(e));
+            }
+        }
+
+        if !self.charging && self.turbo_before_battery.is_some() {
+            if let Err(e) = set_turbo(false) {
+                self.logger.log(
+                    &format!("Could not disable turbo again: {:?}", e),
+                    logger::Severity::Warning,
+                );
+            }
+        }
+
+        // The edge triggered settings get written on the next iteration
+        self.do_charge_threshold = self.do_update_battery;
+        self.freq_source = None;
+        self.thermal_rewrite = true;
+        result
+    }
+
+    /// Find the rule from `config.app_governors` that matches a running process
+    fn check_app_governors(&mut self) -> Option<(String, String)> {
+        if self.config.app_governors.is_empty() {
+            return None;
+        }
+
+        let processes = list_processes();
+        app_governor(&self.config.app_governors, &processes)
+            .map(|(pattern, gov)| (pattern.to_string(), gov.to_string()))
+    }
+
+    /// How long to wait before the next iteration
+    fn next_timeout(&mut self) -> time::Duration {
+        let sample = PollSample {
+            freq: check_cpu_freq(&self.cpus),
+            temp: self.temp_max,
+            usage: self.usage,
+        };
+
+        match &mut self.poll {
+            Some(poll) => poll.next(sample),
+            None if self.charging => self.timeout,
+            None => self.timeout_battery,
+        }
+    }
+
+    /// Apply the action of a key pressed in the live view
+    ///
+    /// The new values get shown the next time the cpus are updated
+    fn handle_key(&mut self, key: &Key) {
+        let result = match key {
+            Key::CycleGovernor => {
+                cycle_governor(&self.cpus).map(|gov| format!("Governor set to {}", gov))
+            }
+            Key::ToggleTurbo => toggle_turbo()
+                .map(|enabled| format!("Turbo {}", if enabled { "enabled" } else { "disabled" })),
+            Key::ScrollUp | Key::ScrollDown | Key::CycleSort => {
+                self.core_table.scroll(key);
+                return;
+            }
+            Key::Quit | Key::Other => return,
+        };
+
+        match result {
+            Ok(message) => self.logger.log(&message, logger::Severity::Log),
+            Err(e) => {
+                let mut error = format!("{:?}", e);
+                // Debug output of errors ends with a newline
+                error.pop();
+                self.key_error = Some((error, Instant::now()));
+            }
+        }
+    }
 }
 
 /// Message at the header of autoclockspeed - rendered before auto clock speed loop starts
@@ -535,9 +1915,9 @@ fn format_message(
     delay_battery: u64,
 ) -> String {
     let message = if started_as_edit != edit {
-        color!(
+        paint(
             Color::RED,
-            format!("\nForced to monitor mode because {}!", forced_reason).as_str()
+            format!("\nForced to monitor mode because {}!", forced_reason).as_str(),
         )
     } else {
         "".to_string()
@@ -548,9 +1928,9 @@ fn format_message(
     format!(
         "Auto Clock Speed daemon has been initialized in {} mode with a delay of {}ms normally and {}ms when on battery{}\n",
         if edit {
-            color!(Color::RED, "edit")
+            paint(Color::RED, "edit")
         } else {
-            color!(Color::YELLOW, "monitor")
+            paint(Color::YELLOW, "monitor")
         },
         delay,
         delay_battery,
@@ -569,14 +1949,14 @@ pub fn daemon_init(settings: Settings, config: Config) -> Arc<Mutex<Daemon>> {
         forced_reason = "the device has no battery".to_string();
     }
 
-    // Check if effective permissions are enough for edit
-    if edit {
+    // Check if effective permissions are enough for edit, a dry run does not write anything
+    if edit && !sysfs::dry_run() {
         // If not running as root, tell the user and force to monitor
         if !Uid::effective().is_root() {
             if !settings.testing {
                 println!(
                 "In order to properly run the daemon in edit mode you must give the executable root privileges.\n{}",
-                color!(Color::RED, "Continuing anyway in 5 seconds...")
+                paint(Color::RED, "Continuing anyway in 5 seconds...")
             );
 
                 let timeout = time::Duration::from_millis(5000);
@@ -587,7 +1967,7 @@ pub fn daemon_init(settings: Settings, config: Config) -> Arc<Mutex<Daemon>> {
         }
     }
 
-    let message = format_message(
+    let mut message = format_message(
         edit, // Use current edit because settings.edit has not changed
         started_as_edit,
         forced_reason,
@@ -595,6 +1975,15 @@ pub fn daemon_init(settings: Settings, config: Config) -> Arc<Mutex<Daemon>> {
         settings.delay_battery,
     );
 
+    // The writes go to the log, printing them would break up the live view
+    if sysfs::dry_run() {
+        sysfs::queue_dry_run_log();
+        message.push_str(&format!(
+            "{}, nothing gets written and the writes are logged\n",
+            paint(Color::YELLOW, "Dry run")
+        ));
+    }
+
     let new_settings = Settings {
         verbose: settings.verbose,
         delay: settings.delay,
@@ -618,6 +2007,11 @@ pub fn daemon_init(settings: Settings, config: Config) -> Arc<Mutex<Daemon>> {
     let power = Power::new();
     let lid = Lid::new();
 
+    let csv_writer = gen_writer(&settings, &config);
+    let log_backend = logger::Backend::from_config(config.log_backend.as_deref());
+
+    let (hook_sender, hook_receiver) = channel();
+
     // Create a new Daemon
     let mut daemon: Daemon = Daemon {
         battery: {
@@ -626,7 +2020,15 @@ pub fn daemon_init(settings: Settings, config: Config) -> Arc<Mutex<Daemon>> {
             battery.unwrap_or_default()
         },
         cpus: Vec::<CPU>::new(),
+        cores_added: false,
         last_proc: Vec::<ProcStat>::new(),
+        freq_window: FreqWindow::new(config.avg_window),
+        history: History::new(time::Duration::from_secs(
+            config.history_max_age_hours * 3600,
+        )),
+        history_saved: Instant::now(),
+        gpus: list_gpus(),
+        fans: list_fans(),
         message,
         lid_state: LidState::Unknown,
         lid,
@@ -640,26 +2042,66 @@ pub fn daemon_init(settings: Settings, config: Config) -> Arc<Mutex<Daemon>> {
         power,
         charge: 100,
         usage: 0.0,
-        logger: logger::Logger {
-            logs: Vec::<logger::Log>::new(),
-        },
+        logger: logger::Logger::new(*log_backend.as_ref().unwrap_or(&logger::Backend::Stdout)),
         config,
         last_below_cpu_usage_percent: None,
+        last_gov_change: None,
         graph: String::new(),
         grapher: Graph::new(),
         temp_max: 0,
+        thermal_zones_missing: false,
+        throttle: ThrottleTracker::default(),
+        throttling: Vec::new(),
+        do_notify: true,
+        notified_hot: false,
         commit_hash: String::new(),
         timeout: time::Duration::from_millis(1),
         timeout_battery: time::Duration::from_millis(2),
+        poll: None,
+        startup_state: None,
         state: State::Unknown,
         settings: new_settings,
         paused: false,
         do_update_battery: true,
-        csv_writer: gen_writer(&settings),
+        do_charge_threshold: true,
+        do_battery_turbo: true,
+        turbo_before_battery: None,
+        do_freq_limits: true,
+        freq_source: None,
+        freq_applied: FreqLimits::default(),
+        do_thermal_cap: true,
+        thermal_cap: None,
+        thermal_rewrite: false,
+        thermal_saved: Vec::new(),
+        do_schedule: true,
+        schedule_window: None,
+        active_profile: None,
+        app_governor: None,
+        last_iteration: None,
+        csv_writer,
+        influx: None,
+        hook_sender,
+        hook_receiver,
+        rapl: Rapl::new().ok(),
+        last_energy: None,
+        package_power: None,
+        session_energy_uj: None,
+        started: Instant::now(),
+        keys_enabled: false,
+        core_table: CoreTable::default(),
+        key_error: None,
     };
 
+    if let Err(e) = log_backend {
+        daemon.logger.log(
+            &format!("Invalid log_backend in config: {:?}", e),
+            logger::Severity::Warning,
+        );
+    }
+
     if !battery_present {
         daemon.do_update_battery = false;
+        daemon.do_charge_threshold = false;
         daemon.logger.log(
             "Failed to detect a laptop battery",
             logger::Severity::Warning,
@@ -678,26 +2120,90 @@ pub fn daemon_init(settings: Settings, config: Config) -> Arc<Mutex<Daemon>> {
         daemon.cpus.push(cpu);
     }
 
-    let daemon_mutex = Arc::new(Mutex::new(daemon));
+    daemon.log_config_problems();
 
-    let c_daemon_mutex = Arc::clone(&daemon_mutex);
-    if settings.edit {
-        // Listen for acs clients
-        listen::listen("/tmp/acs.sock", c_daemon_mutex);
-    } else {
-        // Broadcast hello message
-        if settings.hook {
-            hook::hook("/tmp/acs.sock", c_daemon_mutex);
+    Arc::new(Mutex::new(daemon))
+}
+
+#[cfg(feature = "metrics")]
+fn start_metrics(port: u16, daemon: &mut Daemon, daemon_mutex: &Arc<Mutex<Daemon>>) {
+    daemon.logger.log(
+        &format!("Serving metrics on port {}", port),
+        logger::Severity::Log,
+    );
+    metrics::serve(port, Arc::clone(daemon_mutex));
+}
+
+#[cfg(feature = "remote")]
+fn start_remote(port: u16, daemon: &mut Daemon, daemon_mutex: &Arc<Mutex<Daemon>>) {
+    let bind = daemon.config.remote_status_bind.clone();
+    daemon.logger.log(
+        &format!("Serving the status on {}:{}", bind, port),
+        logger::Severity::Log,
+    );
+    remote::serve(bind, port, Arc::clone(daemon_mutex));
+}
+
+#[cfg(not(feature = "remote"))]
+fn start_remote(_port: u16, daemon: &mut Daemon, _daemon_mutex: &Arc<Mutex<Daemon>>) {
+    daemon.logger.log(
+        "remote_status_port is set but acs was built without the remote feature",
+        logger::Severity::Warning,
+    );
+}
+
+#[cfg(not(feature = "metrics"))]
+fn start_metrics(_port: u16, daemon: &mut Daemon, _daemon_mutex: &Arc<Mutex<Daemon>>) {
+    daemon.logger.log(
+        "metrics_port is set but acs was built without the metrics feature",
+        logger::Severity::Warning,
+    );
+}
+
+fn start_influx(config: InfluxConfig, daemon: &mut Daemon, daemon_mutex: &Arc<Mutex<Daemon>>) {
+    let url = config.url.clone();
+    match influx::start(config, Arc::clone(daemon_mutex)) {
+        Ok(sender) => {
+            daemon.logger.log(
+                &format!("Pushing readings to InfluxDB at {}", url),
+                logger::Severity::Log,
+            );
+            daemon.influx = Some(sender);
         }
+        Err(e) => daemon.logger.log(
+            &format!("Not pushing to InfluxDB: {:?}", e),
+            logger::Severity::Error,
+        ),
     }
+}
 
-    daemon_mutex
+/// Restore what the daemon changed and exit
+pub fn shutdown(daemon: &mut Daemon) -> ! {
+    daemon.save_history(true);
+    daemon.lift_thermal_cap();
+    daemon.restore_startup_state();
+    restore_terminal();
+    exit(0);
 }
 
 pub fn run(daemon_mutex: Arc<Mutex<Daemon>>) -> Result<(), Error> {
+    // Before any thread gets spawned so that every thread leaves the signals to the handler
+    block_signals();
+
     // Aquire the lock for a bit
     let mut daemon = daemon_mutex.lock().unwrap();
 
+    let c_daemon_mutex = Arc::clone(&daemon_mutex);
+    if daemon.settings.edit {
+        // Listen for acs clients
+        listen::listen(SOCKET_PATH, c_daemon_mutex);
+    } else {
+        // Broadcast hello message
+        if daemon.settings.hook {
+            hook::hook(SOCKET_PATH, c_daemon_mutex);
+        }
+    }
+
     daemon.init();
 
     if daemon.settings.testing {
@@ -724,32 +2230,71 @@ pub fn run(daemon_mutex: Arc<Mutex<Daemon>>) -> Result<(), Error> {
             exit(0);
         }
 
+        // Keys only make sense when the live view is being shown
+        let keys = if daemon.settings.verbose {
+            KeyListener::new()
+        } else {
+            None
+        };
+        daemon.keys_enabled = keys.is_some();
+
+        handle_signals(Arc::clone(&daemon_mutex));
+
+        if let Some(port) = daemon.config.metrics_port {
+            start_metrics(port, &mut daemon, &daemon_mutex);
+        }
+
+        if let Some(port) = daemon.config.remote_status_port {
+            start_remote(port, &mut daemon, &daemon_mutex);
+        }
+
+        if let Some(config) = daemon.config.influx.clone() {
+            start_influx(config, &mut daemon, &daemon_mutex);
+        }
+
+        // Changing the governor over D-Bus needs the same permissions as edit mode
+        #[cfg(feature = "dbus")]
+        if daemon.settings.edit {
+            dbus::serve(Arc::clone(&daemon_mutex));
+        }
+
+        // Checked from its own thread without the lock, a stuck iteration would be holding it
+        let heartbeat = Heartbeat::new();
+        watchdog::start(
+            Arc::clone(&heartbeat),
+            time::Duration::from_secs(daemon.config.watchdog_secs),
+            daemon.config.watchdog_exit,
+        );
+
         drop(daemon);
 
-        // Choose which mode acs runs in
-        if mode {
-            loop {
-                let mut daemon = daemon_mutex.lock().unwrap();
+        // The unit is Type=notify so systemd waits for this before counting acs as started
+        watchdog::sd_notify("READY=1").ok();
+
+        loop {
+            let mut daemon = daemon_mutex.lock().unwrap();
+
+            // Choose which mode acs runs in
+            if mode {
                 daemon.single_edit()?;
-                let effective_timeout = if daemon.charging {
-                    daemon.timeout
-                } else {
-                    daemon.timeout_battery
-                };
-                drop(daemon);
-                thread::sleep(effective_timeout);
-            }
-        } else {
-            loop {
-                let mut daemon = daemon_mutex.lock().unwrap();
+            } else {
                 daemon.single_monit()?;
-                let effective_timeout = if daemon.charging {
-                    daemon.timeout
-                } else {
-                    daemon.timeout_battery
-                };
-                drop(daemon);
-                thread::sleep(effective_timeout);
+            }
+
+            heartbeat.beat();
+            let effective_timeout = daemon.next_timeout();
+            drop(daemon);
+
+            match &keys {
+                Some(keys) => {
+                    if let Some(key) = keys.wait(effective_timeout) {
+                        if key == Key::Quit {
+                            shutdown(&mut daemon_mutex.lock().unwrap());
+                        }
+                        daemon_mutex.lock().unwrap().handle_key(&key);
+                    }
+                }
+                None => thread::sleep(effective_timeout),
             }
         }
     }
@@ -762,6 +2307,114 @@ mod tests {
     use super::*;
     use crate::settings::DefaultTesting;
 
+    #[test]
+    fn write_cores_freq_limits_unit_test() {
+        use std::cell::RefCell;
+
+        let limits = RefCell::new(vec![(800_000, 3_000_000); 4]);
+        let read = |core: usize| Ok(limits.borrow()[core]);
+        let write = |core: usize, min: &Option<FreqTarget>, max: &Option<FreqTarget>| {
+            if core == 2 {
+                return Err(Error::HdwNotFound);
+            }
+            let khz = |target: &Option<FreqTarget>| match target {
+                Some(FreqTarget::Khz(khz)) => Some(*khz),
+                _ => None,
+            };
+            let mut limits = limits.borrow_mut();
+            limits[core].0 = khz(min).unwrap_or(limits[core].0);
+            limits[core].1 = khz(max).unwrap_or(limits[core].1);
+            Ok(())
+        };
+
+        let max = Some(FreqTarget::Khz(2_000_000));
+        assert!(write_cores_freq_limits(&[0, 1], &None, &max, read, write).is_ok());
+        assert_eq!(limits.borrow()[1], (800_000, 2_000_000));
+
+        // Cores 0 and 1 get their limits back once core 2 fails
+        let max = Some(FreqTarget::Khz(1_600_000));
+        let result = write_cores_freq_limits(&[0, 1, 2, 3], &None, &max, read, write);
+        assert!(matches!(result, Err((2, Error::HdwNotFound))));
+        assert_eq!(
+            *limits.borrow(),
+            vec![
+                (800_000, 2_000_000),
+                (800_000, 2_000_000),
+                (800_000, 3_000_000),
+                (800_000, 3_000_000),
+            ]
+        );
+    }
+
+    #[test]
+    fn restore_max_freqs_unit_test() {
+        let saved = [(0, 3_000_000), (1, 2_400_000), (2, 3_000_000)];
+        let mut written = Vec::new();
+        let result = restore_max_freqs(&saved, |core, khz| {
+            written.push((core, khz));
+            match core {
+                1 => Err(Error::CoreOffline(1)),
+                _ => Ok(()),
+            }
+        });
+        assert!(result.is_ok());
+        assert_eq!(written, saved.to_vec());
+
+        // The rest still get their max back after one fails
+        let mut written = Vec::new();
+        let result = restore_max_freqs(&saved, |core, khz| {
+            written.push((core, khz));
+            match core {
+                0 => Err(Error::HdwNotFound),
+                _ => Ok(()),
+            }
+        });
+        assert!(result.is_err());
+        assert_eq!(written.len(), 3);
+    }
+
+    #[test]
+    fn freq_target_unit_test() {
+        assert_eq!(
+            freq_target(None, Some(2400)),
+            Some(FreqTarget::Khz(2_400_000))
+        );
+        assert_eq!(
+            freq_target(Some(2400), Some(3000)),
+            Some(FreqTarget::Khz(3_000_000))
+        );
+        // A limit only the last source had is lifted, one neither had is left alone
+        assert_eq!(freq_target(Some(2400), None), Some(FreqTarget::Hardware));
+        assert_eq!(freq_target(None, None), None);
+    }
+
+    #[test]
+    fn resumed_from_suspend_unit_test() {
+        let mono = Instant::now();
+        let wall = SystemTime::now();
+        let later = |mono_secs, wall_secs| {
+            (
+                mono + time::Duration::from_secs(mono_secs),
+                wall + time::Duration::from_secs(wall_secs),
+            )
+        };
+
+        assert!(!resumed_from_suspend((mono, wall), later(5, 5)));
+        assert!(!resumed_from_suspend((mono, wall), later(5, 12)));
+        assert!(resumed_from_suspend((mono, wall), later(5, 600)));
+        assert!(!resumed_from_suspend(
+            (mono, wall),
+            (mono, wall - time::Duration::from_secs(600))
+        ));
+    }
+
+    #[test]
+    fn hold_elapsed_unit_test() {
+        assert!(hold_elapsed(None, 30));
+        assert!(hold_elapsed(Some(Instant::now()), 0));
+        assert!(!hold_elapsed(Some(Instant::now()), 30));
+    }
+
     #[test]
     fn daemon_init_force_to_monit_integration_test() {
         let settings = Settings::default_testing();
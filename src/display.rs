@@ -1,35 +1,159 @@
 #![forbid(unsafe_code)]
-use efcl::{color, Color};
+use crate::theme::paint;
+use efcl::Color;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
 use std::thread;
+use std::time::Duration;
 
-use super::config::Config;
+use super::bench::BenchSummary;
+use super::config::{Config, Profile};
 use super::cpu::CPU;
+use super::fan::{FanSpeed, Pwm};
+use super::gov::TunableValue;
+use super::gpu::GpuClock;
+use super::msr::VoltagePlane;
 use super::power::lid::LidState;
-use super::system::check_turbo_enabled;
-use crate::power::battery::{has_battery, Battery, BatteryStatus};
+use super::power::rapl::{to_watt_hours, to_watts};
+use super::snapshot::Difference;
+use super::sysfs::CoreCStates;
+use super::system::{check_turbo_enabled, Capabilities, PackageSummary};
+use super::thermal::ThermalZone;
+use crate::power::battery::{has_battery, Battery, BatteryStatus, TimeEstimate};
+use std::collections::HashMap;
+
+/// The format that the get commands print in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// Human readable text, or the plain values when raw is used
+    #[default]
+    Human,
+    /// A single JSON object per command
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "human" => Ok(Format::Human),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("Unknown format \"{}\", can be human or json", s)),
+        }
+    }
+}
+
+/// The unit frequencies are shown in, picked with `--freq-unit`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FreqUnit {
+    #[default]
+    Mhz = 0,
+    Ghz = 1,
+}
+
+impl FromStr for FreqUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mhz" => Ok(FreqUnit::Mhz),
+            "ghz" => Ok(FreqUnit::Ghz),
+            _ => Err(format!(
+                "Unknown frequency unit \"{}\", can be mhz or ghz",
+                s
+            )),
+        }
+    }
+}
+
+static FREQ_UNIT: AtomicU8 = AtomicU8::new(FreqUnit::Mhz as u8);
+
+pub fn set_freq_unit(unit: FreqUnit) {
+    FREQ_UNIT.store(unit as u8, Ordering::Relaxed);
+}
+
+pub fn freq_unit() -> FreqUnit {
+    match FREQ_UNIT.load(Ordering::Relaxed) {
+        1 => FreqUnit::Ghz,
+        _ => FreqUnit::Mhz,
+    }
+}
+
+/// A frequency in kHz, the unit sysfs uses, as e.g. "3700 MHz" or "3.70 GHz"
+///
+/// Every frequency shown to people goes through this so they all use the same unit
+pub fn format_freq(khz: f64) -> String {
+    format_freq_in(khz, freq_unit())
+}
+
+pub fn format_freq_in(khz: f64, unit: FreqUnit) -> String {
+    match unit {
+        FreqUnit::Mhz => format!("{:.0} MHz", khz / 1000.0),
+        FreqUnit::Ghz => format!("{:.2} GHz", khz / 1_000_000.0),
+    }
+}
+
+/// A frequency as the kHz integer sysfs has, for `--raw`
+pub fn format_freq_raw(khz: f64) -> String {
+    format!("{:.0}", khz)
+}
+
+/// Everything shown by `acs get status`
+#[derive(Serialize, Deserialize)]
+pub struct Status {
+    pub cores: Vec<CPU>,
+    pub avg_freq_mhz: f32,
+    /// The governor shared by every core, or "mixed" if they differ
+    pub governor: String,
+    pub temp_c: f32,
+    /// Only shown when the cores are spread over more than one package
+    pub packages: Vec<PackageSummary>,
+    pub gpus: Vec<GpuClock>,
+    pub battery_percent: Option<i8>,
+    pub battery_time: Option<TimeEstimate>,
+    /// The cores whose throttle counter went up since the last reading
+    #[serde(default)]
+    pub throttling: Vec<String>,
+}
+
+fn print_json(value: Value) {
+    println!("{}", value);
+}
 
 #[macro_export]
 macro_rules! warn_user {
     ($a:expr) => {{
-        use efcl::{color, Color};
-        println!("{}: {}", color!(Color::YELLOW, "WARN"), $a,);
+        eprintln!(
+            "{}: {}",
+            $crate::theme::paint(efcl::Color::YELLOW, "WARN"),
+            $a,
+        );
     }};
 }
 
 #[macro_export]
 macro_rules! print_error {
     ($a:expr) => {{
-        use efcl::{color, Color};
-        println!("{}: {}", color!(Color::RED, "ERROR"), $a,);
+        eprintln!(
+            "{}: {}",
+            $crate::theme::paint(efcl::Color::RED, "ERROR"),
+            $a,
+        );
     }};
 }
 
 #[macro_export]
 macro_rules! print_done {
     ($a:expr) => {{
-        use efcl::{color, Color};
-        println!("{}: {}", color!(Color::GREEN, "DONE"), $a,);
+        println!(
+            "{}: {}",
+            $crate::theme::paint(efcl::Color::GREEN, "DONE"),
+            $a,
+        );
     }};
 }
 
@@ -47,32 +171,117 @@ pub fn show_config(config: &Config) {
     println!("{}", config);
 }
 
-pub fn print_freq(f: f32, raw: bool) {
-    if raw {
-        println!("{}", f);
+/// Print the frequency, with the average over the last `samples` readings when there is one
+pub fn print_freq(f: f32, window: Option<(f32, usize)>, raw: bool, format: Format) {
+    if format == Format::Json {
+        match window {
+            Some((avg, samples)) => print_json(json!({
+                "avg_freq_mhz": f / 1000.0,
+                "window_freq_mhz": avg / 1000.0,
+                "window_samples": samples,
+            })),
+            None => print_json(json!({ "avg_freq_mhz": f / 1000.0 })),
+        }
+    } else if raw {
+        match window {
+            Some((avg, _)) => println!(
+                "{} {}",
+                format_freq_raw(f as f64),
+                format_freq_raw(avg as f64)
+            ),
+            None => println!("{}", format_freq_raw(f as f64)),
+        }
     } else {
-        println!("CPU freq is {} MHz", f / 1000.0)
+        match window {
+            Some((avg, samples)) => println!(
+                "CPU freq is {}, {} averaged over {} readings",
+                format_freq(f as f64),
+                format_freq(avg as f64),
+                samples
+            ),
+            None => println!("CPU freq is {}", format_freq(f as f64)),
+        }
     }
 }
 
-pub fn print_power(lid: LidState, bat: i8, plugged: bool, raw: bool) {
-    if raw {
-        println!("{} {} {}", lid, bat, plugged);
+pub fn print_power(
+    lid: LidState,
+    bat: i8,
+    plugged: bool,
+    package_power: Option<f32>,
+    raw: bool,
+    format: Format,
+) {
+    if format == Format::Json {
+        print_json(json!({
+            "lid": lid.to_string(),
+            "battery": bat,
+            "plugged": plugged,
+            "package_power_w": package_power,
+        }));
+    } else if raw {
+        match package_power {
+            Some(watts) => println!("{} {} {} {}", lid, bat, plugged, watts),
+            None => println!("{} {} {}", lid, bat, plugged),
+        }
     } else {
-        println!("Lid: {} Battery: {} Plugged: {}", lid, bat, plugged);
+        match package_power {
+            Some(watts) => println!(
+                "Lid: {} Battery: {} Plugged: {} Package power: {:.2}W",
+                lid, bat, plugged, watts
+            ),
+            None => println!("Lid: {} Battery: {} Plugged: {}", lid, bat, plugged),
+        }
     }
 }
 
-pub fn print_bat_cond(c: i8, raw: bool) {
-    if raw {
+pub fn print_bat_cond(c: i8, raw: bool, format: Format) {
+    if format == Format::Json {
+        print_json(json!({ "battery_condition": c }));
+    } else if raw {
         println!("{}", c);
     } else {
         println!("{:.2}%", c)
     }
 }
 
-pub fn print_turbo(t: bool, raw: bool) {
-    if raw {
+pub fn print_charge_limit(percent: u8, raw: bool, format: Format) {
+    if format == Format::Json {
+        print_json(json!({ "charge_limit": percent }));
+    } else if raw {
+        println!("{}", percent);
+    } else {
+        println!("Battery stops charging at {}%", percent)
+    }
+}
+
+/// Print the energy used over a session of `secs` seconds
+pub fn print_energy(energy_uj: u64, secs: u64, raw: bool, format: Format) {
+    let watt_hours = to_watt_hours(energy_uj);
+    let avg_watts = to_watts(energy_uj, Duration::from_secs(secs));
+    if format == Format::Json {
+        print_json(json!({
+            "energy_wh": watt_hours,
+            "session_secs": secs,
+            "avg_power_w": avg_watts,
+        }));
+    } else if raw {
+        println!("{}", watt_hours);
+    } else {
+        println!(
+            "Package used {:.3} Wh in {}m {}s, {:.2} W on average",
+            watt_hours,
+            secs / 60,
+            secs % 60,
+            avg_watts
+        )
+    }
+}
+
+pub fn print_turbo(t: bool, raw: bool, format: Format) {
+    if format == Format::Json {
+        print_json(json!({ "turbo": t }));
+    } else if raw {
         println!("{}", t);
     } else {
         println!(
@@ -86,15 +295,28 @@ pub fn print_turbo(t: bool, raw: bool) {
     }
 }
 
+/// E.g. "2h 05m until empty"
+pub fn render_time_estimate(estimate: &TimeEstimate) -> String {
+    let (secs, until) = match estimate {
+        TimeEstimate::ToEmpty(secs) => (secs, "empty"),
+        TimeEstimate::ToFull(secs) => (secs, "full"),
+    };
+    format!("{}h {:02}m until {}", secs / 3600, secs % 3600 / 60, until)
+}
+
 pub fn print_battery_status(battery: &Battery) -> String {
     if has_battery() {
         format!(
-            "Battery: {}",
+            "Battery: {}{}",
             if battery.status == BatteryStatus::Charging {
-                color!(Color::GREEN, format!("{}%", battery.capacity).as_str())
+                paint(Color::GREEN, format!("{}%", battery.capacity).as_str())
             } else {
-                color!(Color::RED, format!("{}%", battery.capacity).as_str())
+                paint(Color::RED, format!("{}%", battery.capacity).as_str())
             },
+            match &battery.time_estimate {
+                Some(estimate) => format!(", {}", render_time_estimate(estimate)),
+                None => String::new(),
+            }
         )
     } else {
         format!("Battery: {}", "N/A")
@@ -138,12 +360,48 @@ pub fn print_turbo_status(cores: usize, animation: bool, term_width: usize, dela
                 println!("Turbo: {}", enabled_message);
             }
         }
-        Err(..) => eprintln!("Could not check turbo. Expected for AMD.\n"),
+        Err(..) => eprintln!("Could not check turbo, it is not supported on this cpu.\n"),
     }
 }
 
-fn print_vec<T: Display>(t: Vec<T>, raw: bool) {
-    if raw {
+pub fn print_usage(percent: String, raw: bool, format: Format) {
+    if format == Format::Json {
+        print_json(json!({ "usage": percent.parse::<f32>().unwrap_or(0.0) }));
+    } else if raw {
+        println!("{}", percent)
+    } else {
+        println!("CPU is at {}%", percent)
+    }
+}
+
+pub fn print_thermal_zones(zones: Vec<ThermalZone>, raw: bool, format: Format) {
+    if format == Format::Json {
+        print_json(json!({ "zones": zones }));
+    } else if raw {
+        println!("{:?}", zones)
+    } else {
+        for zone in zones {
+            println!("{}", zone);
+        }
+    }
+}
+
+pub fn print_zone_temp(zone: &str, temp: i32, raw: bool, format: Format) {
+    if format == Format::Json {
+        print_json(json!({ "zone": zone, "temp_c": temp as f32 / 1000.0 }));
+    } else if raw {
+        println!("{}", temp);
+    } else {
+        println!("{}: {}C", zone, temp as f32 / 1000.0);
+    }
+}
+
+fn print_vec<T: Display + Serialize>(t: Vec<T>, key: &str, raw: bool, format: Format) {
+    if format == Format::Json {
+        let mut object = serde_json::Map::new();
+        object.insert(key.to_string(), json!(t));
+        print_json(Value::Object(object));
+    } else if raw {
         for x in t {
             println!("{}", x);
         }
@@ -155,33 +413,398 @@ fn print_vec<T: Display>(t: Vec<T>, raw: bool) {
     }
 }
 
-pub fn print_available_governors(available_governors: Vec<String>, raw: bool) {
-    print_vec(available_governors, raw);
+pub fn print_available_governors(available_governors: Vec<String>, raw: bool, format: Format) {
+    print_vec(available_governors, "available_governors", raw, format);
 }
 
-pub fn print_cpus(cpus: Vec<CPU>, name: String, raw: bool) {
-    if raw {
+/// Print the governors per group of cores that offer the same ones
+pub fn print_governors_by_core(groups: &[(Vec<String>, Vec<usize>)], raw: bool, format: Format) {
+    let cores = |cores: &[usize]| {
+        cores
+            .iter()
+            .map(|c| format!("cpu{}", c))
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+
+    if format == Format::Json {
+        let groups: Vec<Value> = groups
+            .iter()
+            .map(|(govs, cores)| json!({ "governors": govs, "cores": cores }))
+            .collect();
+        print_json(json!({ "same_on_every_core": groups.len() == 1, "groups": groups }));
+    } else if raw {
+        for (govs, group) in groups {
+            println!("{} {}", cores(group).replace(", ", ","), govs.join(" "));
+        }
+    } else if let [(govs, _)] = groups {
+        println!("Every core offers: {}", govs.join(" "));
+    } else {
+        println!("The cores offer different governors");
+        for (govs, group) in groups {
+            println!("{}: {}", cores(group), govs.join(" "));
+        }
+    }
+}
+
+pub fn print_cpus(cpus: Vec<CPU>, name: String, raw: bool, format: Format) {
+    if format == Format::Json {
+        print_json(json!({ "name": name, "cores": cpus }));
+    } else if raw {
         for x in cpus {
             println!("{} {}", x.name, x.cur_freq);
         }
     } else {
         println!("Name: {}", name);
         for x in cpus {
-            println!("{}:\t{} MHz", x.name, x.cur_freq / 1000);
+            println!("{}:\t{}", x.name, format_freq(x.cur_freq as f64));
+        }
+    }
+}
+
+pub fn print_cpu_speeds(cpu_speeds: Vec<i32>, raw: bool, format: Format) {
+    if format == Format::Json {
+        let mhz: Vec<i32> = cpu_speeds.iter().map(|x| x / 1000).collect();
+        print_vec(mhz, "freq_mhz", raw, format);
+    } else if raw {
+        print_vec(cpu_speeds, "freq_khz", raw, format);
+    } else {
+        let speeds: Vec<String> = cpu_speeds.iter().map(|x| format_freq(*x as f64)).collect();
+        println!("{}", speeds.join(", "));
+    }
+}
+
+pub fn print_cpu_temp(cpu_temp: Vec<i32>, raw: bool, format: Format) {
+    if format == Format::Json {
+        let celsius: Vec<f32> = cpu_temp.iter().map(|x| *x as f32 / 1000.0).collect();
+        print_vec(celsius, "temp_c", raw, format);
+    } else {
+        print_vec(cpu_temp, "temp", raw, format);
+    }
+}
+
+pub fn print_cpu_governors(cpu_governors: Vec<String>, raw: bool, format: Format) {
+    print_vec(cpu_governors, "governors", raw, format);
+}
+
+pub fn print_cpu_epps(cpu_epps: Vec<String>, raw: bool, format: Format) {
+    print_vec(cpu_epps, "epps", raw, format);
+}
+
+pub fn print_cstates(cores: Vec<CoreCStates>, raw: bool, format: Format) {
+    if format == Format::Json {
+        print_json(json!({ "cstates": cores }));
+    } else if raw {
+        for core in cores {
+            for state in core.states {
+                println!(
+                    "cpu{} {} {} {}",
+                    core.core, state.name, state.time_us, state.usage
+                );
+            }
+        }
+    } else if cores.is_empty() {
+        println!("No cpuidle states found");
+    } else {
+        for core in cores {
+            println!("cpu{}:", core.core);
+            let total: u64 = core.states.iter().map(|s| s.time_us).sum();
+            for state in core.states {
+                let percent = if total > 0 {
+                    state.time_us as f64 / total as f64 * 100.0
+                } else {
+                    0.0
+                };
+                println!(
+                    "  {:<8} {:>12.2}s {:>6.2}% {:>10} entries",
+                    state.name,
+                    state.time_us as f64 / 1_000_000.0,
+                    percent,
+                    state.usage
+                );
+            }
+        }
+    }
+}
+
+pub fn print_profiles(profiles: &HashMap<String, Profile>, raw: bool, format: Format) {
+    let mut names: Vec<&String> = profiles.keys().collect();
+    names.sort();
+
+    if format == Format::Json {
+        print_json(json!({ "profiles": profiles }));
+    } else if raw {
+        for name in names {
+            println!("{}", name);
+        }
+    } else if names.is_empty() {
+        println!("No profiles defined in the config");
+    } else {
+        for name in names {
+            println!("{}: {:?}", name, profiles[name]);
+        }
+    }
+}
+
+pub fn print_status(status: Status, raw: bool, format: Format) {
+    if format == Format::Json {
+        print_json(json!(status));
+    } else if raw {
+        println!(
+            "{} {} {}",
+            status.avg_freq_mhz, status.governor, status.temp_c
+        );
+    } else {
+        println!("Name\tMax\tMin\tFreq\tTemp\tUsage\tGovernor");
+        for core in &status.cores {
+            print!("{}", core);
+        }
+        if status.packages.len() > 1 {
+            for package in &status.packages {
+                println!(
+                    "Package {}: {} cores, average freq: {} Governor: {} Temp: {:.1}C",
+                    package.package,
+                    package.cores.len(),
+                    format_freq(package.avg_freq_mhz as f64 * 1000.0),
+                    describe_governor(&package.governor),
+                    package.temp_c
+                );
+            }
+        }
+        println!(
+            "Average freq: {} Governor: {} Temp: {}C",
+            format_freq(status.avg_freq_mhz as f64 * 1000.0),
+            status.governor,
+            status.temp_c
+        );
+        if !status.throttling.is_empty() {
+            println!("{}", render_throttling(&status.throttling));
+        }
+        for gpu in &status.gpus {
+            println!("{}", render_gpu_clock(gpu));
+        }
+        if let Some(percent) = status.battery_percent {
+            match &status.battery_time {
+                Some(estimate) => {
+                    println!("Battery: {}%, {}", percent, render_time_estimate(estimate))
+                }
+                None => println!("Battery: {}%", percent),
+            }
+        }
+    }
+}
+
+/// The flag shown while cores are throttling, e.g. "THROTTLING: cpu0, cpu3"
+pub fn render_throttling(cores: &[String]) -> String {
+    format!("{}: {}", paint(Color::RED, "THROTTLING"), cores.join(", "))
+}
+
+/// One line for a GPU, e.g. "GPU card0 (Intel): 350 MHz of 1300 MHz"
+pub fn render_gpu_clock(gpu: &GpuClock) -> String {
+    match gpu.max_freq_mhz {
+        Some(max) => format!(
+            "GPU {} ({:?}): {} of {}",
+            gpu.card,
+            gpu.kind,
+            format_freq(gpu.cur_freq_mhz as f64 * 1000.0),
+            format_freq(max as f64 * 1000.0)
+        ),
+        None => format!(
+            "GPU {} ({:?}): {}",
+            gpu.card,
+            gpu.kind,
+            format_freq(gpu.cur_freq_mhz as f64 * 1000.0)
+        ),
+    }
+}
+
+pub fn print_gpu_clocks(clocks: &[GpuClock], raw: bool, format: Format) {
+    if format == Format::Json {
+        print_json(json!(clocks));
+    } else if raw {
+        for gpu in clocks {
+            println!("{} {}", gpu.card, gpu.cur_freq_mhz);
+        }
+    } else {
+        for gpu in clocks {
+            println!("{}", render_gpu_clock(gpu));
+        }
+    }
+}
+
+/// One line for a fan, e.g. "Fan CPU Fan (thinkpad): 2100 RPM"
+pub fn render_fan_speed(fan: &FanSpeed) -> String {
+    format!("Fan {} ({}): {} RPM", fan.label, fan.device, fan.rpm)
+}
+
+pub fn print_fans(fans: &[FanSpeed], pwms: &[Pwm], raw: bool, format: Format) {
+    if format == Format::Json {
+        print_json(json!({ "fans": fans, "pwms": pwms }));
+    } else if raw {
+        for fan in fans {
+            println!("{} {}", fan.label, fan.rpm);
+        }
+    } else {
+        for fan in fans {
+            println!("{}", render_fan_speed(fan));
+        }
+        for pwm in pwms {
+            println!(
+                "Control {} ({}): {}/255 {}",
+                pwm.name,
+                pwm.device,
+                pwm.value,
+                if pwm.manual {
+                    "set by hand"
+                } else {
+                    "automatic"
+                }
+            );
+        }
+    }
+}
+
+pub fn print_voltage_offsets(offsets: &[(VoltagePlane, i32)], raw: bool, format: Format) {
+    if format == Format::Json {
+        let offsets: serde_json::Map<String, Value> = offsets
+            .iter()
+            .map(|(plane, mv)| (plane.to_string(), json!(mv)))
+            .collect();
+        print_json(json!({ "voltage_offsets_mv": offsets }));
+    } else if raw {
+        for (plane, mv) in offsets {
+            println!("{} {}", plane, mv);
+        }
+    } else {
+        for (plane, mv) in offsets {
+            println!("{}: {} mV", plane, mv);
+        }
+    }
+}
+
+pub fn print_tunables(tunables: &[TunableValue], raw: bool, format: Format) {
+    if format == Format::Json {
+        print_json(json!({ "tunables": tunables }));
+    } else if raw {
+        for t in tunables {
+            println!("{} {} {} {}", t.policy, t.governor, t.name, t.value);
+        }
+    } else {
+        for t in tunables {
+            println!("{}: {} {} = {}", t.policy, t.governor, t.name, t.value);
+        }
+    }
+}
+
+/// What `acs info` shows, with why a feature is missing where it is known
+pub fn print_info(caps: &Capabilities, raw: bool, format: Format) {
+    if format == Format::Json {
+        print_json(json!(caps));
+        return;
+    }
+
+    let model = caps.model.as_deref().unwrap_or("unknown");
+    let driver = caps.driver.as_deref().unwrap_or("none");
+    let flags = [
+        ("hwp", caps.hwp),
+        ("turbo", caps.turbo),
+        ("epp", caps.epp),
+        ("rapl", caps.rapl),
+    ];
+    if raw {
+        println!("model {}", model);
+        println!("driver {}", driver);
+        println!("cores {}", caps.cores);
+        println!("threads {}", caps.threads);
+        for (name, supported) in flags {
+            println!("{} {}", name, supported);
+        }
+        return;
+    }
+
+    let yes_no = |supported: bool| {
+        if supported {
+            paint(Color::GREEN, "yes")
+        } else {
+            paint(Color::RED, "no")
         }
+    };
+    println!("Model: {}", model);
+    match &caps.driver {
+        Some(driver) => println!("Driver: {}", driver),
+        None => println!(
+            "Driver: {}, the frequency and governor can not be changed",
+            paint(Color::RED, "none")
+        ),
     }
+    println!("Cores: {}, threads: {}", caps.cores, caps.threads);
+    println!("HWP: {}", yes_no(caps.hwp));
+    println!("Turbo: {}", yes_no(caps.turbo));
+    println!("EPP: {}", yes_no(caps.epp));
+    println!("RAPL: {}", yes_no(caps.rapl));
 }
 
-pub fn print_cpu_speeds(cpu_speeds: Vec<i32>, raw: bool) {
-    print_vec(cpu_speeds, raw);
+/// The values that changed since a snapshot, e.g. "cpu0 governor: powersave -> performance"
+pub fn print_differences(differences: &[Difference], raw: bool, format: Format) {
+    if format == Format::Json {
+        print_json(json!({ "differences": differences }));
+    } else if raw {
+        for d in differences {
+            println!("{}\t{}\t{}", d.key, d.before, d.after);
+        }
+    } else if differences.is_empty() {
+        println!("Nothing changed since the snapshot");
+    } else {
+        for d in differences {
+            println!(
+                "{}: {} -> {}",
+                d.key,
+                d.before,
+                paint(Color::YELLOW, &d.after)
+            );
+        }
+    }
 }
 
-pub fn print_cpu_temp(cpu_temp: Vec<i32>, raw: bool) {
-    print_vec(cpu_temp, raw);
+/// "all performance" when every core shares the governor, otherwise "mixed"
+fn describe_governor(governor: &str) -> String {
+    match governor {
+        "mixed" | "unknown" => governor.to_string(),
+        _ => format!("all {}", governor),
+    }
 }
 
-pub fn print_cpu_governors(cpu_governors: Vec<String>, raw: bool) {
-    print_vec(cpu_governors, raw);
+pub fn print_bench_summary(summary: &BenchSummary, format: Format) {
+    if format == Format::Json {
+        print_json(json!(summary));
+        return;
+    }
+
+    println!("Threads: {}, samples: {}", summary.threads, summary.samples);
+    println!(
+        "Frequency: min {}, max {}, avg {}",
+        format_freq(summary.min_freq_mhz * 1000.0),
+        format_freq(summary.max_freq_mhz * 1000.0),
+        format_freq(summary.avg_freq_mhz * 1000.0)
+    );
+    println!("Peak temp: {:.1}C", summary.peak_temp_c);
+    if let Some(power) = summary.avg_power_w {
+        println!("Average package power: {:.2} W", power);
+    }
+
+    let events = match summary.throttle_events {
+        Some(events) => format!(" ({} throttle events)", events),
+        None => String::new(),
+    };
+    if summary.throttled {
+        println!("{}{}", paint(Color::RED, "Throttling detected"), events);
+    } else {
+        println!(
+            "{}{}",
+            paint(Color::GREEN, "No throttling detected"),
+            events
+        );
+    }
 }
 
 #[cfg(test)]
@@ -206,4 +829,21 @@ mod tests {
         assert!(out.contains("Unknown"));
         assert!(out.contains("cpu1"));
     }
+
+    #[test]
+    fn format_from_str_unit_test() {
+        assert_eq!("json".parse::<Format>(), Ok(Format::Json));
+        assert_eq!("Human".parse::<Format>(), Ok(Format::Human));
+        assert!("yaml".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn format_freq_unit_test() {
+        assert_eq!(format_freq_in(3_700_000.0, FreqUnit::Mhz), "3700 MHz");
+        assert_eq!(format_freq_in(3_700_000.0, FreqUnit::Ghz), "3.70 GHz");
+        assert_eq!(format_freq_in(799_999.0, FreqUnit::Ghz), "0.80 GHz");
+        assert_eq!(format_freq_raw(2_400_000.4), "2400000");
+        assert_eq!("GHz".parse::<FreqUnit>(), Ok(FreqUnit::Ghz));
+        assert!("khz".parse::<FreqUnit>().is_err());
+    }
 }
@@ -1,5 +1,7 @@
 #![forbid(unsafe_code)]
 use std::fmt;
+use std::io::ErrorKind;
+use std::path::Path;
 
 const BUG_REPORT: &str = "\nGive us a bug report by opening an issue at
 https://github.com/JakeRoggenbuck/auto-clock-speed/issues/new/choose";
@@ -11,6 +13,34 @@ pub enum Error {
     HdwNotFound,
     Unknown,
     DivisionByZero,
+    CoreOffline(usize),
+    /// A core the kernel does not let go offline, like cpu0
+    CoreNotHotpluggable(usize),
+    InvalidValue(String, Vec<String>),
+    UnknownProfile(String),
+    OutOfRange(i64, i64, i64),
+    Unsupported(String),
+    InvalidConfig(String),
+    /// A governor that has to be in use and the ones that are
+    GovernorNotActive(String, String),
+    /// A file that needs root to be written, e.g. anything in cpufreq
+    PermissionDenied {
+        path: String,
+    },
+}
+
+impl Error {
+    /// Turn the error from writing a file into what it means, a file that is not there is a
+    /// feature the hardware or driver does not have
+    pub fn from_io(e: std::io::Error, path: &Path) -> Error {
+        match e.kind() {
+            ErrorKind::PermissionDenied => Error::PermissionDenied {
+                path: path.display().to_string(),
+            },
+            ErrorKind::NotFound => Error::HdwNotFound,
+            _ => Error::IO(e),
+        }
+    }
 }
 
 impl From<std::io::Error> for Error {
@@ -44,6 +74,35 @@ impl fmt::Debug for Error {
                 Error::Unknown => "Unknown error occured".to_string(),
                 Error::DivisionByZero => "Division by zero occured".to_string(),
                 Error::Parse => "Failed to parse data".to_string(),
+                Error::CoreOffline(core) => format!("Core {} is offline", core),
+                Error::CoreNotHotpluggable(core) => {
+                    format!("Core {} can not be taken offline or brought online", core)
+                }
+                Error::UnknownProfile(name) => format!(
+                    "Profile \"{}\" is not defined in the config, see 'acs profile' for the list",
+                    name
+                ),
+                Error::Unsupported(feature) => {
+                    format!("{} is not supported on this hardware", feature)
+                }
+                Error::InvalidConfig(reason) => format!("Config is not valid: {}", reason),
+                Error::GovernorNotActive(wanted, active) => format!(
+                    "This only works while {} is the governor, the cores use {}, set it with 'acs set gov {}' first",
+                    wanted, active, wanted
+                ),
+                Error::PermissionDenied { path } => format!(
+                    "Permission denied writing {}, run as root or change it through the acs daemon",
+                    path
+                ),
+                Error::OutOfRange(value, min, max) => format!(
+                    "{} is out of range, expected a value from {} to {}",
+                    value, min, max
+                ),
+                Error::InvalidValue(value, available) => format!(
+                    "\"{}\" is not a valid value, expected one of: {}",
+                    value,
+                    available.join(", ")
+                ),
             }
         )
     }
@@ -0,0 +1,307 @@
+#![forbid(unsafe_code)]
+//! Fan speeds and fan control from the hwmon sysfs interface
+//!
+//! Every hwmon device (`/sys/class/hwmon/hwmonN`) can have `fanM_input` files with the speed in
+//! RPM and `pwmM` files with the duty cycle from 0 to 255. Device names depend on the driver
+//! (thinkpad, dell_smm, asus, nct6775, amdgpu, ...), some devices have no name at all and older
+//! drivers keep the files in the `device` directory under the hwmon one, so every place is
+//! checked and nothing in the names is relied on.
+use crate::error::Error;
+use crate::sysfs;
+use crate::system::{read_int, read_str};
+use serde::Serialize;
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+
+const HWMON_DIR: &str = "/sys/class/hwmon/";
+
+/// pwmM_enable value for setting the duty cycle by hand
+const PWM_MANUAL: i32 = 1;
+/// pwmM_enable value for letting the firmware or driver control the fan, the most common one
+const PWM_AUTOMATIC: i32 = 2;
+
+/// A fan speed sensor
+#[derive(Clone, Debug, Serialize)]
+pub struct Fan {
+    /// The hwmon directory, e.g. hwmon3
+    pub hwmon: String,
+    /// The name of the driver, e.g. thinkpad
+    pub device: String,
+    pub index: u32,
+    /// From fanM_label, or fanM when the driver does not give one
+    pub label: String,
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+/// A reading of a fan speed in RPM
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FanSpeed {
+    pub hwmon: String,
+    pub device: String,
+    pub label: String,
+    pub rpm: u32,
+}
+
+/// A fan control that takes a duty cycle
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Pwm {
+    /// How it is named for `acs set fan`, e.g. hwmon3/pwm1
+    pub name: String,
+    pub device: String,
+    /// The duty cycle from 0 to 255
+    pub value: u8,
+    /// If the duty cycle is set by hand instead of by the firmware or driver
+    pub manual: bool,
+}
+
+/// Get M from a file named like `fanM_input` or `pwmM`
+pub fn parse_index(file_name: &str, prefix: &str, suffix: &str) -> Option<u32> {
+    let number = file_name.strip_prefix(prefix)?.strip_suffix(suffix)?;
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    number.parse().ok()
+}
+
+/// Split a control named like `hwmon3/pwm1` into its hwmon directory and index
+pub fn parse_pwm_name(name: &str) -> Option<(&str, u32)> {
+    let (hwmon, pwm) = name.split_once('/')?;
+    parse_index(hwmon, "hwmon", "")?;
+    Some((hwmon, parse_index(pwm, "pwm", "")?))
+}
+
+/// The directories of a hwmon device that can hold its sensor files
+fn sensor_dirs(hwmon: &Path) -> [PathBuf; 2] {
+    [hwmon.to_path_buf(), hwmon.join("device")]
+}
+
+fn device_name(hwmon: &Path) -> String {
+    sensor_dirs(hwmon)
+        .iter()
+        .find_map(|dir| read_str(&dir.join("name").to_string_lossy()).ok())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Find the directory of a hwmon device that has a file, if any does
+fn find_file(hwmon: &Path, file: &str) -> Option<PathBuf> {
+    sensor_dirs(hwmon)
+        .into_iter()
+        .find(|dir| dir.join(file).exists())
+}
+
+/// The hwmon devices, sorted by their number
+fn hwmon_devices(dir: &Path) -> Vec<(String, PathBuf)> {
+    let entries = match read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut devices: Vec<(u32, String, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let hwmon = entry.file_name().to_string_lossy().to_string();
+            let number = parse_index(&hwmon, "hwmon", "")?;
+            Some((number, hwmon, entry.path()))
+        })
+        .collect();
+    devices.sort_by_key(|(number, _, _)| *number);
+    devices
+        .into_iter()
+        .map(|(_, hwmon, path)| (hwmon, path))
+        .collect()
+}
+
+/// The index of every file in a hwmon device matching the prefix and suffix
+fn indices(hwmon: &Path, prefix: &str, suffix: &str) -> Vec<u32> {
+    let mut found: Vec<u32> = sensor_dirs(hwmon)
+        .iter()
+        .filter_map(|dir| read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten())
+        .filter_map(|entry| parse_index(&entry.file_name().to_string_lossy(), prefix, suffix))
+        .collect();
+    found.sort_unstable();
+    found.dedup();
+    found
+}
+
+/// Find the fan speed sensors of every hwmon device in a directory
+pub fn list_fans_in(dir: &Path) -> Vec<Fan> {
+    let mut fans = Vec::new();
+    for (hwmon, path) in hwmon_devices(dir) {
+        let device = device_name(&path);
+        for index in indices(&path, "fan", "_input") {
+            let input = format!("fan{}_input", index);
+            let files = match find_file(&path, &input) {
+                Some(files) => files,
+                None => continue,
+            };
+            let label = read_str(&files.join(format!("fan{}_label", index)).to_string_lossy())
+                .ok()
+                .filter(|label| !label.trim().is_empty())
+                .unwrap_or_else(|| format!("fan{}", index));
+
+            fans.push(Fan {
+                hwmon: hwmon.clone(),
+                device: device.clone(),
+                index,
+                label: label.trim().to_string(),
+                path: files.join(input),
+            });
+        }
+    }
+    fans
+}
+
+pub fn list_fans() -> Vec<Fan> {
+    list_fans_in(Path::new(HWMON_DIR))
+}
+
+/// Read the speed of every fan, skipping ones that can not be read right now
+pub fn read_fan_speeds(fans: &[Fan]) -> Vec<FanSpeed> {
+    fans.iter()
+        .filter_map(|fan| {
+            let rpm = read_int(&fan.path.to_string_lossy()).ok()?;
+            Some(FanSpeed {
+                hwmon: fan.hwmon.clone(),
+                device: fan.device.clone(),
+                label: fan.label.clone(),
+                rpm: rpm.max(0) as u32,
+            })
+        })
+        .collect()
+}
+
+/// Find the fan controls that can be switched to a duty cycle set by hand
+///
+/// Only controls with both `pwmM` and `pwmM_enable` count, without the enable file there is
+/// no way to hand the fan back to the firmware
+pub fn list_pwms_in(dir: &Path) -> Vec<Pwm> {
+    let mut pwms = Vec::new();
+    for (hwmon, path) in hwmon_devices(dir) {
+        let device = device_name(&path);
+        for index in indices(&path, "pwm", "") {
+            let files = match find_file(&path, &format!("pwm{}_enable", index)) {
+                Some(files) => files,
+                None => continue,
+            };
+            let read = |file: String| read_int(&files.join(file).to_string_lossy()).ok();
+            let (value, enable) = match (
+                read(format!("pwm{}", index)),
+                read(format!("pwm{}_enable", index)),
+            ) {
+                (Some(value), Some(enable)) => (value, enable),
+                _ => continue,
+            };
+
+            pwms.push(Pwm {
+                name: format!("{}/pwm{}", hwmon, index),
+                device: device.clone(),
+                value: value.clamp(0, 255) as u8,
+                manual: enable == PWM_MANUAL,
+            });
+        }
+    }
+    pwms
+}
+
+pub fn list_pwms() -> Vec<Pwm> {
+    list_pwms_in(Path::new(HWMON_DIR))
+}
+
+/// Set the duty cycle of a fan control from 0 to 255, or hand it back to the firmware with None
+///
+/// Experimental, a fan left spinning too slow can overheat the hardware
+pub fn set_fan_in(dir: &Path, pwm: &str, value: Option<u8>) -> Result<(), Error> {
+    let available = || list_pwms_in(dir).into_iter().map(|p| p.name).collect();
+    let (hwmon, index) =
+        parse_pwm_name(pwm).ok_or_else(|| Error::InvalidValue(pwm.to_string(), available()))?;
+    let enable = format!("pwm{}_enable", index);
+    let files = find_file(&dir.join(hwmon), &enable)
+        .filter(|files| files.join(format!("pwm{}", index)).exists())
+        .ok_or_else(|| Error::InvalidValue(pwm.to_string(), available()))?;
+
+    match value {
+        Some(value) => {
+            sysfs::write(&files.join(&enable), &PWM_MANUAL.to_string())?;
+            sysfs::write(&files.join(format!("pwm{}", index)), &value.to_string())
+        }
+        None => sysfs::write(&files.join(&enable), &PWM_AUTOMATIC.to_string()),
+    }
+}
+
+pub fn set_fan(pwm: &str, value: Option<u8>) -> Result<(), Error> {
+    set_fan_in(Path::new(HWMON_DIR), pwm, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use std::fs;
+
+    #[test]
+    fn parse_index_unit_test() {
+        assert_eq!(parse_index("fan1_input", "fan", "_input"), Some(1));
+        assert_eq!(parse_index("fan12_input", "fan", "_input"), Some(12));
+        assert_eq!(parse_index("fan1_label", "fan", "_input"), None);
+        assert_eq!(parse_index("pwm2", "pwm", ""), Some(2));
+        assert_eq!(parse_index("pwm2_enable", "pwm", ""), None);
+        assert_eq!(parse_index("pwm", "pwm", ""), None);
+
+        assert_eq!(parse_pwm_name("hwmon3/pwm1"), Some(("hwmon3", 1)));
+        assert_eq!(parse_pwm_name("hwmon3/../pwm1"), None);
+        assert_eq!(parse_pwm_name("pwm1"), None);
+    }
+
+    #[test]
+    fn fans_unit_test() -> Result<(), Error> {
+        let dir = TestDir::new("fan");
+        let named = dir.join("hwmon2");
+        let old = dir.join("hwmon10/device");
+        fs::create_dir_all(&named)?;
+        fs::create_dir_all(&old)?;
+        fs::write(named.join("name"), "thinkpad\n")?;
+        fs::write(named.join("fan1_input"), "2100\n")?;
+        fs::write(named.join("fan1_label"), "CPU Fan\n")?;
+        fs::write(named.join("pwm1"), "128\n")?;
+        fs::write(named.join("pwm1_enable"), "2\n")?;
+        // The files of older drivers are in device and there is no label
+        fs::write(old.join("name"), "dell_smm\n")?;
+        fs::write(old.join("fan2_input"), "0\n")?;
+
+        let speeds = read_fan_speeds(&list_fans_in(&dir));
+        let pwms = list_pwms_in(&dir);
+        let unknown = set_fan_in(&dir, "hwmon10/pwm1", Some(100)).is_err();
+        set_fan_in(&dir, "hwmon2/pwm1", Some(100))?;
+        let set = (
+            fs::read_to_string(named.join("pwm1"))?,
+            fs::read_to_string(named.join("pwm1_enable"))?,
+        );
+        set_fan_in(&dir, "hwmon2/pwm1", None)?;
+        let automatic = fs::read_to_string(named.join("pwm1_enable"))?;
+
+        assert_eq!(speeds.len(), 2);
+        assert_eq!(
+            speeds[0],
+            FanSpeed {
+                hwmon: "hwmon2".to_string(),
+                device: "thinkpad".to_string(),
+                label: "CPU Fan".to_string(),
+                rpm: 2100,
+            }
+        );
+        assert_eq!(speeds[1].device, "dell_smm");
+        assert_eq!(speeds[1].label, "fan2");
+
+        assert_eq!(pwms.len(), 1);
+        assert_eq!(pwms[0].name, "hwmon2/pwm1");
+        assert_eq!(pwms[0].value, 128);
+        assert!(!pwms[0].manual);
+
+        assert!(unknown);
+        assert_eq!(set, ("100".to_string(), "1".to_string()));
+        assert_eq!(automatic, "2");
+        Ok(())
+    }
+}
@@ -1,5 +1,10 @@
 #![forbid(unsafe_code)]
+use crate::error::Error;
+use crate::sysfs;
+use serde::Serialize;
 use std::fmt;
+use std::fs::{self, read_dir};
+use std::path::{Path, PathBuf};
 
 /// Governor
 ///
@@ -21,3 +26,208 @@ impl fmt::Display for Gov {
         write!(f, "{}", name)
     }
 }
+
+/// Where cpufreq keeps the policies and the tunables shared by all of them
+const CPUFREQ_DIR: &str = "/sys/devices/system/cpu/cpufreq/";
+
+/// A setting that only exists while its governor is in use
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tunable {
+    /// Shortest time in µs between two frequency changes
+    SchedutilRateLimit,
+    /// Load in percent over which ondemand goes to the highest frequency
+    OndemandUpThreshold,
+    /// How often in µs ondemand checks the load
+    OndemandSamplingRate,
+}
+
+pub const TUNABLES: [Tunable; 3] = [
+    Tunable::SchedutilRateLimit,
+    Tunable::OndemandUpThreshold,
+    Tunable::OndemandSamplingRate,
+];
+
+impl Tunable {
+    pub fn governor(self) -> &'static str {
+        match self {
+            Tunable::SchedutilRateLimit => "schedutil",
+            Tunable::OndemandUpThreshold | Tunable::OndemandSamplingRate => "ondemand",
+        }
+    }
+
+    pub fn file(self) -> &'static str {
+        match self {
+            Tunable::SchedutilRateLimit => "rate_limit_us",
+            Tunable::OndemandUpThreshold => "up_threshold",
+            Tunable::OndemandSamplingRate => "sampling_rate",
+        }
+    }
+
+    /// Check a value before writing it, the kernel only gives a bare EINVAL otherwise
+    pub fn validate(self, value: u64) -> Result<(), Error> {
+        match self {
+            Tunable::OndemandUpThreshold if !(1..=100).contains(&value) => {
+                Err(Error::OutOfRange(value as i64, 1, 100))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A tunable as read from one policy
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TunableValue {
+    /// The cpufreq policy, e.g. policy0
+    pub policy: String,
+    pub governor: String,
+    pub name: String,
+    pub value: u64,
+}
+
+/// The policies and the governor each one uses, sorted by their number
+fn policy_governors(dir: &Path) -> Vec<(String, String)> {
+    let mut policies: Vec<(u32, String, String)> = match read_dir(dir) {
+        Ok(entries) => entries
+            .flatten()
+            .filter_map(|entry| {
+                let policy = entry.file_name().to_string_lossy().to_string();
+                let number = policy.strip_prefix("policy")?.parse::<u32>().ok()?;
+                let gov = fs::read_to_string(entry.path().join("scaling_governor")).ok()?;
+                Some((number, policy, gov.trim().to_string()))
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    policies.sort();
+    policies
+        .into_iter()
+        .map(|(_, policy, gov)| (policy, gov))
+        .collect()
+}
+
+/// The file of a tunable for each policy using its governor
+///
+/// Most drivers give every policy its own tunables, with others ondemand shares one set for
+/// all of them in the cpufreq directory
+fn tunable_files(dir: &Path, tunable: Tunable) -> Result<Vec<(String, PathBuf)>, Error> {
+    let policies = policy_governors(dir);
+    let files: Vec<(String, PathBuf)> = policies
+        .iter()
+        .filter(|(_, gov)| gov == tunable.governor())
+        .filter_map(|(policy, gov)| {
+            let own = dir.join(policy).join(gov).join(tunable.file());
+            let shared = dir.join(gov).join(tunable.file());
+            [own, shared]
+                .into_iter()
+                .find(|path| path.exists())
+                .map(|path| (policy.clone(), path))
+        })
+        .collect();
+
+    if files.is_empty() {
+        let mut active: Vec<String> = policies.into_iter().map(|(_, gov)| gov).collect();
+        active.sort();
+        active.dedup();
+        let active = if active.is_empty() {
+            "no governor".to_string()
+        } else {
+            active.join(", ")
+        };
+        return Err(Error::GovernorNotActive(
+            tunable.governor().to_string(),
+            active,
+        ));
+    }
+    Ok(files)
+}
+
+pub fn get_tunable_in(dir: &Path, tunable: Tunable) -> Result<Vec<TunableValue>, Error> {
+    tunable_files(dir, tunable)?
+        .into_iter()
+        .map(|(policy, path)| {
+            let value = fs::read_to_string(path)?.trim().parse::<u64>()?;
+            Ok(TunableValue {
+                policy,
+                governor: tunable.governor().to_string(),
+                name: tunable.file().to_string(),
+                value,
+            })
+        })
+        .collect()
+}
+
+/// Read a tunable from every policy that uses its governor
+pub fn get_tunable(tunable: Tunable) -> Result<Vec<TunableValue>, Error> {
+    get_tunable_in(Path::new(CPUFREQ_DIR), tunable)
+}
+
+/// Every tunable of the governors in use
+pub fn list_tunables() -> Vec<TunableValue> {
+    TUNABLES
+        .iter()
+        .filter_map(|tunable| get_tunable(*tunable).ok())
+        .flatten()
+        .collect()
+}
+
+pub fn set_tunable_in(dir: &Path, tunable: Tunable, value: u64) -> Result<(), Error> {
+    tunable.validate(value)?;
+    let mut files = tunable_files(dir, tunable)?;
+    // Policies sharing the tunables of ondemand would write the same file again
+    files.dedup_by(|a, b| a.1 == b.1);
+    for (_, path) in files {
+        sysfs::write(&path, &value.to_string())?;
+    }
+    Ok(())
+}
+
+/// Set a tunable on every policy that uses its governor, an error when none does
+pub fn set_tunable(tunable: Tunable, value: u64) -> Result<(), Error> {
+    set_tunable_in(Path::new(CPUFREQ_DIR), tunable, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn tunables_unit_test() -> Result<(), Error> {
+        let dir = TestDir::new("gov");
+        for (policy, gov) in [("policy0", "schedutil"), ("policy4", "schedutil")] {
+            fs::create_dir_all(dir.join(policy).join(gov))?;
+            fs::write(
+                dir.join(policy).join("scaling_governor"),
+                format!("{}\n", gov),
+            )?;
+            fs::write(dir.join(policy).join(gov).join("rate_limit_us"), "1000\n")?;
+        }
+        let shared = dir.join("shared");
+        fs::create_dir_all(shared.join("policy0"))?;
+        fs::create_dir_all(shared.join("ondemand"))?;
+        fs::write(shared.join("policy0/scaling_governor"), "ondemand\n")?;
+        fs::write(shared.join("ondemand/up_threshold"), "95\n")?;
+
+        set_tunable_in(&dir, Tunable::SchedutilRateLimit, 10000)?;
+        let rates = get_tunable_in(&dir, Tunable::SchedutilRateLimit)?;
+        let not_active = get_tunable_in(&dir, Tunable::OndemandUpThreshold);
+        let out_of_range = set_tunable_in(&shared, Tunable::OndemandUpThreshold, 150);
+        set_tunable_in(&shared, Tunable::OndemandUpThreshold, 80)?;
+        let threshold = get_tunable_in(&shared, Tunable::OndemandUpThreshold)?;
+
+        assert_eq!(
+            rates
+                .iter()
+                .map(|r| (r.policy.as_str(), r.value))
+                .collect::<Vec<_>>(),
+            vec![("policy0", 10000), ("policy4", 10000)]
+        );
+        assert!(matches!(
+            not_active,
+            Err(Error::GovernorNotActive(gov, active)) if gov == "ondemand" && active == "schedutil"
+        ));
+        assert!(matches!(out_of_range, Err(Error::OutOfRange(150, 1, 100))));
+        assert_eq!(threshold[0].value, 80);
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+#![forbid(unsafe_code)]
+//! Clock readings of integrated and discrete GPUs from the drm sysfs interface
+//!
+//! Intel (i915) exposes the current frequency in `gt_cur_freq_mhz` on the card, amdgpu lists
+//! the shader clock levels in `pp_dpm_sclk` on the device with the active one marked by `*`.
+//! Cards with neither are left out.
+use crate::error::Error;
+use crate::system::read_int;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, read_dir};
+use std::path::{Path, PathBuf};
+
+const DRM_DIR: &str = "/sys/class/drm/";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GpuKind {
+    Intel,
+    Amd,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Gpu {
+    /// The drm card, e.g. card0
+    pub card: String,
+    pub kind: GpuKind,
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+/// A reading of the clock of one GPU in MHz
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GpuClock {
+    pub card: String,
+    pub kind: GpuKind,
+    pub cur_freq_mhz: u32,
+    pub max_freq_mhz: Option<u32>,
+}
+
+/// The clock levels in MHz from pp_dpm_sclk and the index of the active one
+///
+/// Lines look like `1: 1200Mhz *`
+pub fn parse_pp_dpm(text: &str) -> (Vec<u32>, Option<usize>) {
+    let mut levels = Vec::new();
+    let mut active = None;
+    for line in text.lines() {
+        let freq = line
+            .split_whitespace()
+            .nth(1)
+            .map(|f| f.trim_end_matches(|c: char| c.is_ascii_alphabetic()))
+            .and_then(|f| f.parse::<u32>().ok());
+        if let Some(freq) = freq {
+            if line.trim_end().ends_with('*') {
+                active = Some(levels.len());
+            }
+            levels.push(freq);
+        }
+    }
+    (levels, active)
+}
+
+impl Gpu {
+    /// Work out which interface a card has, None when it has no supported one
+    fn detect(path: &Path) -> Option<GpuKind> {
+        if path.join("gt_cur_freq_mhz").exists() {
+            Some(GpuKind::Intel)
+        } else if path.join("device/pp_dpm_sclk").exists() {
+            Some(GpuKind::Amd)
+        } else {
+            None
+        }
+    }
+
+    pub fn read_clock(&self) -> Result<GpuClock, Error> {
+        let (cur, max) = match self.kind {
+            GpuKind::Intel => {
+                let path = |file: &str| self.path.join(file).to_string_lossy().to_string();
+                let cur = read_int(&path("gt_cur_freq_mhz"))? as u32;
+                let max = read_int(&path("gt_max_freq_mhz")).ok().map(|m| m as u32);
+                (cur, max)
+            }
+            GpuKind::Amd => {
+                let text = fs::read_to_string(self.path.join("device/pp_dpm_sclk"))?;
+                let (levels, active) = parse_pp_dpm(&text);
+                let cur = active.and_then(|i| levels.get(i)).ok_or(Error::Parse)?;
+                (*cur, levels.iter().max().copied())
+            }
+        };
+
+        Ok(GpuClock {
+            card: self.card.clone(),
+            kind: self.kind,
+            cur_freq_mhz: cur,
+            max_freq_mhz: max,
+        })
+    }
+}
+
+/// Find the cards with a supported clock interface
+pub fn list_gpus() -> Vec<Gpu> {
+    let entries = match read_dir(DRM_DIR) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut gpus: Vec<Gpu> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let card = entry.file_name().to_string_lossy().to_string();
+            // Connectors like card0-eDP-1 and render nodes are not cards
+            let number = card.strip_prefix("card")?;
+            if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+
+            let path = entry.path();
+            let kind = Gpu::detect(&path)?;
+            Some(Gpu { card, kind, path })
+        })
+        .collect();
+    gpus.sort_by(|a, b| a.card.cmp(&b.card));
+    gpus
+}
+
+/// Read the clock of every supported GPU, skipping ones that can not be read right now
+pub fn read_gpu_clocks(gpus: &[Gpu]) -> Vec<GpuClock> {
+    gpus.iter()
+        .filter_map(|gpu| gpu.read_clock().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn parse_pp_dpm_unit_test() {
+        let (levels, active) = parse_pp_dpm("0: 500Mhz\n1: 1200Mhz *\n2: 2100Mhz\n");
+        assert_eq!(levels, vec![500, 1200, 2100]);
+        assert_eq!(active, Some(1));
+
+        // Some cards list a deep sleep level first
+        let (levels, active) = parse_pp_dpm("S: 19Mhz *\n0: 500Mhz\n1: 2100Mhz\n");
+        assert_eq!(levels, vec![19, 500, 2100]);
+        assert_eq!(active, Some(0));
+
+        assert_eq!(parse_pp_dpm(""), (vec![], None));
+    }
+
+    #[test]
+    fn read_clock_unit_test() -> Result<(), Error> {
+        let dir = TestDir::new("gpu");
+        let intel = dir.join("card0");
+        let amd = dir.join("card1");
+        fs::create_dir_all(&intel)?;
+        fs::create_dir_all(amd.join("device"))?;
+        fs::write(intel.join("gt_cur_freq_mhz"), "350\n")?;
+        fs::write(intel.join("gt_max_freq_mhz"), "1300\n")?;
+        fs::write(amd.join("device/pp_dpm_sclk"), "0: 500Mhz\n1: 1800Mhz *\n")?;
+
+        let kinds = (Gpu::detect(&intel), Gpu::detect(&amd), Gpu::detect(&dir));
+        let gpus = [
+            Gpu {
+                card: "card0".to_string(),
+                kind: GpuKind::Intel,
+                path: intel,
+            },
+            Gpu {
+                card: "card1".to_string(),
+                kind: GpuKind::Amd,
+                path: amd,
+            },
+        ];
+        let clocks = read_gpu_clocks(&gpus);
+
+        assert_eq!(kinds, (Some(GpuKind::Intel), Some(GpuKind::Amd), None));
+        assert_eq!(clocks[0].cur_freq_mhz, 350);
+        assert_eq!(clocks[0].max_freq_mhz, Some(1300));
+        assert_eq!(clocks[1].cur_freq_mhz, 1800);
+        assert_eq!(clocks[1].max_freq_mhz, Some(1800));
+        Ok(())
+    }
+}
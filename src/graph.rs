@@ -3,6 +3,8 @@ use rasciigraph::{plot, Config};
 use std::default::Default;
 use std::fmt;
 
+pub mod svg;
+
 pub trait Grapher {
     fn update_all(&mut self);
     fn update_one(&self, vec: &mut Vec<f64>) -> String;
@@ -48,8 +50,9 @@ impl Grapher for Graph {
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Debug)]
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
 pub enum GraphType {
+    #[default]
     Hidden,
     Frequency,
     Usage,
@@ -57,12 +60,6 @@ pub enum GraphType {
     Unknown,
 }
 
-impl Default for GraphType {
-    fn default() -> GraphType {
-        GraphType::Hidden
-    }
-}
-
 impl fmt::Display for GraphType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -0,0 +1,267 @@
+#![forbid(unsafe_code)]
+//! Write graphs of sampled values to an SVG file so they can be shared, e.g. in a bug report
+//!
+//! Each metric gets its own chart stacked on top of the others since they use different units.
+use super::GraphType;
+use crate::cpu::{Speed, CPU};
+use crate::error::Error;
+use crate::proc::{parse_proc_file, read_proc_stat_file};
+use crate::system::{calculate_cpu_percent, check_cpu_freq, check_cpu_temperature, list_cpus};
+use chrono::{DateTime, Local};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::{thread, time};
+
+const WIDTH: f64 = 800.0;
+const CHART_HEIGHT: f64 = 240.0;
+const MARGIN_LEFT: f64 = 70.0;
+const MARGIN_RIGHT: f64 = 20.0;
+const MARGIN_TOP: f64 = 30.0;
+const MARGIN_BOTTOM: f64 = 40.0;
+const Y_TICKS: usize = 5;
+const X_TICKS: usize = 6;
+
+/// The values read at one point in time
+#[derive(Clone, Debug)]
+pub struct Sample {
+    pub time: DateTime<Local>,
+    pub freq_mhz: f64,
+    pub temp_c: f64,
+    pub usage: f64,
+}
+
+impl Sample {
+    pub fn value(&self, metric: &GraphType) -> f64 {
+        match metric {
+            GraphType::Frequency => self.freq_mhz,
+            GraphType::Temperature => self.temp_c,
+            GraphType::Usage => self.usage,
+            GraphType::Hidden | GraphType::Unknown => 0.0,
+        }
+    }
+}
+
+/// Parse a comma separated list of metrics like "freq,temp"
+pub fn parse_metrics(metrics: &str) -> Result<Vec<GraphType>, Error> {
+    metrics
+        .split(',')
+        .map(|m| match super::get_graph_type(m.trim()) {
+            GraphType::Hidden | GraphType::Unknown => Err(Error::InvalidValue(
+                m.trim().to_string(),
+                vec!["freq".to_string(), "usage".to_string(), "temp".to_string()],
+            )),
+            metric => Ok(metric),
+        })
+        .collect()
+}
+
+fn title(metric: &GraphType) -> &'static str {
+    match metric {
+        GraphType::Frequency => "Average frequency (MHz)",
+        GraphType::Temperature => "Average temperature (°C)",
+        GraphType::Usage => "CPU usage (%)",
+        GraphType::Hidden | GraphType::Unknown => "",
+    }
+}
+
+/// Read a sample every interval until the duration has passed
+pub fn sample(duration: time::Duration, interval: time::Duration) -> Result<Vec<Sample>, Error> {
+    let mut cpus: Vec<CPU> = list_cpus();
+    let mut last_proc = parse_proc_file(read_proc_stat_file()?);
+    let mut samples = Vec::new();
+    let start = time::Instant::now();
+
+    while start.elapsed() < duration {
+        thread::sleep(interval);
+
+        for cpu in cpus.iter_mut() {
+            cpu.update()?;
+        }
+        let cur_proc = parse_proc_file(read_proc_stat_file()?);
+
+        samples.push(Sample {
+            time: Local::now(),
+            freq_mhz: check_cpu_freq(&cpus) as f64 / 1000.0,
+            temp_c: check_cpu_temperature(&cpus) as f64 / 1000.0,
+            // The first line of /proc/stat is the total of every core
+            usage: calculate_cpu_percent(&last_proc[0], &cur_proc[0]) as f64 * 100.0,
+        });
+        last_proc = cur_proc;
+    }
+
+    Ok(samples)
+}
+
+/// Find the range shown on the y axis, with some room above and below the values
+fn y_range(values: &[f64]) -> (f64, f64) {
+    let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+
+    if !min.is_finite() || !max.is_finite() {
+        return (0.0, 1.0);
+    }
+    if (max - min).abs() < f64::EPSILON {
+        return (min - 1.0, max + 1.0);
+    }
+
+    let padding = (max - min) * 0.1;
+    (min - padding, max + padding)
+}
+
+/// Draw one chart with its top edge at y_offset
+fn render_chart(svg: &mut String, metric: &GraphType, samples: &[Sample], y_offset: f64) {
+    let plot_width = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+    let plot_height = CHART_HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+    let top = y_offset + MARGIN_TOP;
+    let bottom = top + plot_height;
+
+    let values: Vec<f64> = samples.iter().map(|s| s.value(metric)).collect();
+    let (low, high) = y_range(&values);
+
+    let x = |i: usize| {
+        if samples.len() < 2 {
+            MARGIN_LEFT
+        } else {
+            MARGIN_LEFT + plot_width * i as f64 / (samples.len() - 1) as f64
+        }
+    };
+    let y = |v: f64| bottom - plot_height * (v - low) / (high - low);
+
+    writeln!(
+        svg,
+        r#"<text x="{}" y="{}" font-size="14" font-weight="bold">{}</text>"#,
+        MARGIN_LEFT,
+        y_offset + 20.0,
+        title(metric)
+    )
+    .ok();
+
+    // Grid lines and y axis labels
+    for i in 0..=Y_TICKS {
+        let value = low + (high - low) * i as f64 / Y_TICKS as f64;
+        let ty = y(value);
+        writeln!(
+            svg,
+            r##"<line x1="{}" y1="{:.1}" x2="{}" y2="{:.1}" stroke="#ddd"/>"##,
+            MARGIN_LEFT,
+            ty,
+            MARGIN_LEFT + plot_width,
+            ty
+        )
+        .ok();
+        writeln!(
+            svg,
+            r#"<text x="{}" y="{:.1}" font-size="11" text-anchor="end">{:.1}</text>"#,
+            MARGIN_LEFT - 6.0,
+            ty + 4.0,
+            value
+        )
+        .ok();
+    }
+
+    // Timestamps along the x axis
+    if !samples.is_empty() {
+        let ticks = X_TICKS.min(samples.len() - 1);
+        for t in 0..=ticks {
+            let i = ((samples.len() - 1) * t).checked_div(ticks).unwrap_or(0);
+            writeln!(
+                svg,
+                r#"<text x="{:.1}" y="{}" font-size="11" text-anchor="middle">{}</text>"#,
+                x(i),
+                bottom + 16.0,
+                samples[i].time.format("%H:%M:%S")
+            )
+            .ok();
+        }
+    }
+
+    writeln!(
+        svg,
+        r#"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="black"/>"#,
+        MARGIN_LEFT, top, plot_width, plot_height
+    )
+    .ok();
+
+    let points: Vec<String> = values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| format!("{:.1},{:.1}", x(i), y(*v)))
+        .collect();
+    writeln!(
+        svg,
+        r##"<polyline points="{}" fill="none" stroke="#1f77b4" stroke-width="2"/>"##,
+        points.join(" ")
+    )
+    .ok();
+}
+
+/// Render the samples as an SVG document with a chart for each metric
+pub fn render_svg(metrics: &[GraphType], samples: &[Sample]) -> String {
+    let height = CHART_HEIGHT * metrics.len() as f64;
+    let mut svg = String::new();
+
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif">"#,
+        WIDTH, height
+    )
+    .ok();
+    writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#).ok();
+
+    for (i, metric) in metrics.iter().enumerate() {
+        render_chart(&mut svg, metric, samples, CHART_HEIGHT * i as f64);
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Sample the metrics for the duration and write the graph to the output file
+pub fn graph_to_file(
+    output: &Path,
+    metrics: &[GraphType],
+    duration: time::Duration,
+    interval: time::Duration,
+) -> Result<(), Error> {
+    let samples = sample(duration, interval)?;
+    fs::write(output, render_svg(metrics, &samples))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_metrics_unit_test() {
+        assert_eq!(
+            parse_metrics("freq, temp").unwrap(),
+            vec![GraphType::Frequency, GraphType::Temperature]
+        );
+        assert!(parse_metrics("freq,power").is_err());
+    }
+
+    #[test]
+    fn render_svg_unit_test() {
+        let samples: Vec<Sample> = (0..10)
+            .map(|i| Sample {
+                time: Local::now(),
+                freq_mhz: 2000.0 + i as f64 * 100.0,
+                temp_c: 50.0,
+                usage: 10.0,
+            })
+            .collect();
+
+        let svg = render_svg(&[GraphType::Frequency, GraphType::Temperature], &samples);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert!(svg.contains("Average frequency (MHz)"));
+        assert!(svg.contains("Average temperature (°C)"));
+        assert_eq!(svg.matches("<polyline").count(), 2);
+
+        // Nothing sampled still makes a valid document
+        let svg = render_svg(&[GraphType::Usage], &[]);
+        assert!(svg.contains("CPU usage (%)"));
+    }
+}
@@ -0,0 +1,190 @@
+#![forbid(unsafe_code)]
+//! Readings the daemon keeps over time, saved to `history_file` so a restart leaves no gap
+//!
+//! The file is CSV behind a version line, one sample per line with its unix time. The daemon
+//! writes it every `history_flush_secs` and when it exits, and loads it again when it starts.
+//! Samples older than `history_max_age_hours` are dropped, and never more than `MAX_SAMPLES`
+//! are kept. A file that does not parse, e.g. one from another version or one cut off while
+//! being written, is ignored and the history starts over.
+use crate::error::Error;
+use crate::graph::svg::Sample;
+use chrono::{DateTime, Local, TimeZone};
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// The first line of a history file, changed whenever the columns do
+pub const HEADER: &str = "# acs history v1";
+
+/// Samples kept no matter their age, about 3 MB on disk
+pub const MAX_SAMPLES: usize = 100_000;
+
+#[derive(Clone, Debug)]
+pub struct History {
+    samples: VecDeque<Sample>,
+    max_age: Duration,
+}
+
+impl History {
+    pub fn new(max_age: Duration) -> History {
+        History {
+            samples: VecDeque::new(),
+            max_age,
+        }
+    }
+
+    pub fn push(&mut self, sample: Sample) {
+        let now = sample.time;
+        self.samples.push_back(sample);
+        self.prune(now);
+    }
+
+    /// Drop the samples that are too old or over the limit
+    fn prune(&mut self, now: DateTime<Local>) {
+        let max_age = chrono::Duration::from_std(self.max_age).unwrap_or(chrono::Duration::MAX);
+        while let Some(oldest) = self.samples.front() {
+            if now - oldest.time <= max_age && self.samples.len() <= MAX_SAMPLES {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// Oldest first
+    pub fn samples(&self) -> &VecDeque<Sample> {
+        &self.samples
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut out = format!("{}\ntime,freq_mhz,temp_c,usage\n", HEADER);
+        for s in &self.samples {
+            writeln!(
+                out,
+                "{},{:.0},{:.1},{:.1}",
+                s.time.timestamp(),
+                s.freq_mhz,
+                s.temp_c,
+                s.usage
+            )
+            .ok();
+        }
+        out
+    }
+
+    /// Parse a history file, the samples older than max_age at now are left out
+    pub fn parse(text: &str, max_age: Duration, now: DateTime<Local>) -> Result<History, Error> {
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(Error::Parse);
+        }
+        // The column names
+        lines.next();
+
+        let mut history = History::new(max_age);
+        for line in lines {
+            let fields: Vec<&str> = line.split(',').collect();
+            let [time, freq, temp, usage] = fields[..] else {
+                return Err(Error::Parse);
+            };
+            let float = |field: &str| field.parse::<f64>().map_err(|_| Error::Parse);
+            history.samples.push_back(Sample {
+                time: Local
+                    .timestamp_opt(time.parse::<i64>()?, 0)
+                    .single()
+                    .ok_or(Error::Parse)?,
+                freq_mhz: float(freq)?,
+                temp_c: float(temp)?,
+                usage: float(usage)?,
+            });
+        }
+        history.prune(now);
+        Ok(history)
+    }
+
+    pub fn load(path: &Path, max_age: Duration) -> Result<History, Error> {
+        History::parse(&fs::read_to_string(path)?, max_age, Local::now())
+    }
+
+    /// Write the file next to the old one and move it over, so it is never left half written
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, self.to_csv())?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    const DAY: Duration = Duration::from_secs(24 * 3600);
+
+    fn sample(secs: i64, freq_mhz: f64) -> Sample {
+        Sample {
+            time: Local.timestamp_opt(secs, 0).unwrap(),
+            freq_mhz,
+            temp_c: 50.5,
+            usage: 12.0,
+        }
+    }
+
+    #[test]
+    fn history_prune_unit_test() {
+        let mut history = History::new(Duration::from_secs(60));
+        history.push(sample(1_000, 800.0));
+        history.push(sample(1_030, 900.0));
+        history.push(sample(1_070, 1000.0));
+        let times: Vec<i64> = history
+            .samples()
+            .iter()
+            .map(|s| s.time.timestamp())
+            .collect();
+        assert_eq!(times, vec![1_030, 1_070]);
+    }
+
+    #[test]
+    fn history_csv_unit_test() -> Result<(), Error> {
+        let mut history = History::new(DAY);
+        history.push(sample(1_700_000_000, 2400.0));
+        history.push(sample(1_700_000_060, 3100.0));
+        let csv = history.to_csv();
+        assert_eq!(
+            csv,
+            "# acs history v1\ntime,freq_mhz,temp_c,usage\n1700000000,2400,50.5,12.0\n1700000060,3100,50.5,12.0\n"
+        );
+
+        let now = Local.timestamp_opt(1_700_000_100, 0).unwrap();
+        let parsed = History::parse(&csv, DAY, now)?;
+        assert_eq!(parsed.samples().len(), 2);
+        assert_eq!(parsed.samples()[1].freq_mhz, 3100.0);
+
+        // Samples that got too old while the daemon was stopped are dropped
+        let later = Local
+            .timestamp_opt(1_700_000_000 + 2 * 24 * 3600, 0)
+            .unwrap();
+        assert!(History::parse(&csv, DAY, later)?.samples().is_empty());
+
+        // Another version or a line cut off is not used
+        assert!(History::parse("# acs history v0\n1,2,3,4\n", DAY, now).is_err());
+        assert!(History::parse(&csv[..csv.len() - 12], DAY, now).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn history_save_unit_test() -> Result<(), Error> {
+        let dir = TestDir::new("history");
+        let path = dir.join("history.csv");
+
+        let mut history = History::new(Duration::from_secs(u64::MAX));
+        history.push(sample(1_000, 800.0));
+        history.save(&path)?;
+        let loaded = History::load(&path, Duration::from_secs(u64::MAX));
+
+        assert_eq!(loaded?.samples().len(), 1);
+        Ok(())
+    }
+}
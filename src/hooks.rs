@@ -0,0 +1,195 @@
+#![forbid(unsafe_code)]
+//! Scripts the daemon runs when something happens, set in the `hooks` table of the config
+//!
+//! Each script gets what happened in environment variables, `ACS_EVENT` is always set along
+//! with `ACS_GOVERNOR`, `ACS_STATE`, `ACS_TEMP`, `ACS_CHARGING`, and `ACS_BATTERY_PERCENT`.
+//! `profile_change` adds `ACS_PROFILE`. The scripts run detached with no input or output, a
+//! thread waits on each one and hands its exit status back to the daemon to log, so a slow
+//! script never holds up the polling loop.
+use crate::error::Error;
+use crate::logger::Severity;
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc::Sender;
+use std::thread;
+
+/// The event names accepted in the hooks table
+pub const EVENTS: [&str; 5] = [
+    "on_battery",
+    "on_ac",
+    "overheat",
+    "governor_change",
+    "profile_change",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The power source switched to the battery
+    OnBattery,
+    /// The charger got plugged in
+    OnAc,
+    /// The daemon switched to the overheating state
+    Overheat,
+    GovernorChange,
+    /// A profile from the schedule got applied
+    ProfileChange,
+}
+
+impl Event {
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::OnBattery => "on_battery",
+            Event::OnAc => "on_ac",
+            Event::Overheat => "overheat",
+            Event::GovernorChange => "governor_change",
+            Event::ProfileChange => "profile_change",
+        }
+    }
+
+    pub fn from_charging(charging: bool) -> Event {
+        if charging {
+            Event::OnAc
+        } else {
+            Event::OnBattery
+        }
+    }
+}
+
+/// What the daemon knew when an event fired
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HookInfo {
+    pub governor: String,
+    pub state: String,
+    /// Highest temperature in C
+    pub temp: i8,
+    pub charging: bool,
+    pub battery_percent: i8,
+    pub profile: Option<String>,
+}
+
+/// The environment variables a hook is run with
+pub fn hook_env(event: Event, info: &HookInfo) -> Vec<(String, String)> {
+    let mut env = vec![
+        ("ACS_EVENT".to_string(), event.name().to_string()),
+        ("ACS_GOVERNOR".to_string(), info.governor.clone()),
+        ("ACS_STATE".to_string(), info.state.clone()),
+        ("ACS_TEMP".to_string(), info.temp.to_string()),
+        (
+            "ACS_CHARGING".to_string(),
+            (info.charging as u8).to_string(),
+        ),
+        (
+            "ACS_BATTERY_PERCENT".to_string(),
+            info.battery_percent.to_string(),
+        ),
+    ];
+    if let Some(profile) = &info.profile {
+        env.push(("ACS_PROFILE".to_string(), profile.clone()));
+    }
+    env
+}
+
+/// The log message for a hook that finished
+pub fn describe_exit(event: Event, path: &str, status: ExitStatus) -> (String, Severity) {
+    if status.success() {
+        (
+            format!("Hook {} for {} finished", path, event.name()),
+            Severity::Log,
+        )
+    } else {
+        (
+            format!("Hook {} for {} failed, {}", path, event.name(), status),
+            Severity::Warning,
+        )
+    }
+}
+
+/// Check that a hook can be run, it has to be an absolute path to an executable file
+pub fn validate_hook(path: &str) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if !Path::new(path).is_absolute() {
+        return Err(format!("\"{}\" has to be an absolute path", path));
+    }
+    match std::fs::metadata(path) {
+        Ok(meta) if meta.is_file() && meta.permissions().mode() & 0o111 != 0 => Ok(()),
+        Ok(_) => Err(format!("\"{}\" is not an executable file", path)),
+        Err(e) => Err(format!("\"{}\" can not be read, {}", path, e)),
+    }
+}
+
+/// Start a hook without waiting for it, its exit gets sent to `done` once it finishes
+pub fn run(
+    event: Event,
+    path: &str,
+    info: &HookInfo,
+    done: Sender<(String, Severity)>,
+) -> Result<(), Error> {
+    let mut child = Command::new(path)
+        .envs(hook_env(event, info))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let path = path.to_string();
+    thread::spawn(move || {
+        let message = match child.wait() {
+            Ok(status) => describe_exit(event, &path, status),
+            Err(e) => (
+                format!("Could not wait on hook {}: {}", path, e),
+                Severity::Error,
+            ),
+        };
+        // The daemon only drops the receiver when it exits
+        done.send(message).ok();
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    #[test]
+    fn hook_env_unit_test() {
+        let info = HookInfo {
+            governor: "powersave".to_string(),
+            state: "BatteryLow".to_string(),
+            temp: 54,
+            charging: false,
+            battery_percent: 18,
+            profile: None,
+        };
+        let env = hook_env(Event::OnBattery, &info);
+        let get = |key: &str| env.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+        assert_eq!(get("ACS_EVENT"), Some("on_battery"));
+        assert_eq!(get("ACS_GOVERNOR"), Some("powersave"));
+        assert_eq!(get("ACS_TEMP"), Some("54"));
+        assert_eq!(get("ACS_CHARGING"), Some("0"));
+        assert_eq!(get("ACS_PROFILE"), None);
+
+        let info = HookInfo {
+            profile: Some("quiet".to_string()),
+            ..info
+        };
+        let env = hook_env(Event::ProfileChange, &info);
+        assert!(env.contains(&("ACS_PROFILE".to_string(), "quiet".to_string())));
+    }
+
+    #[test]
+    fn run_hook_unit_test() -> Result<(), Error> {
+        assert!(validate_hook("hook.sh").is_err());
+        assert!(validate_hook("/etc").is_err());
+        assert!(validate_hook("/bin/sh").is_ok());
+
+        let (sender, receiver) = channel();
+        run(Event::OnAc, "/bin/false", &HookInfo::default(), sender)?;
+        let (message, severity) = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(severity, Severity::Warning);
+        assert!(message.starts_with("Hook /bin/false for on_ac failed"));
+        Ok(())
+    }
+}
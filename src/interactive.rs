@@ -1,9 +1,268 @@
 #![forbid(unsafe_code)]
 use super::config::{get_config, Config};
+use super::cpu::{set_governor_for_core, set_turbo, CPU};
+use super::display::Format;
+use super::error::Error;
 use super::interface::{DaemonControl, DaemonController, Get, Getter, Interface, Set, Setter};
 use super::settings::Settings;
-use efcl::{bold, color, Color};
-use std::io::{stdin, stdout, Write};
+use super::system::{check_available_governors, check_turbo_enabled};
+use crate::theme::{bold, paint};
+use efcl::Color;
+use nix::sys::termios::{self, LocalFlags, SetArg, SpecialCharacterIndices, Termios};
+use nix::unistd::isatty;
+use std::io::{stdin, stdout, Read, Write};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// Keys that do something in the live view of monit and run
+#[derive(Debug, PartialEq, Eq)]
+pub enum Key {
+    /// Switch every core to the next available governor
+    CycleGovernor,
+    /// Turn turbo on or off
+    ToggleTurbo,
+    /// Move the per core table, from the arrow keys or j and k
+    ScrollUp,
+    ScrollDown,
+    /// Sort the per core table by the next column
+    CycleSort,
+    /// Leave the live view, also sent for ctrl+c
+    Quit,
+    Other,
+}
+
+/// Turn a byte read from the terminal into a key
+pub fn parse_key(byte: u8) -> Key {
+    match byte {
+        b'g' => Key::CycleGovernor,
+        b't' => Key::ToggleTurbo,
+        b'k' => Key::ScrollUp,
+        b'j' => Key::ScrollDown,
+        b's' => Key::CycleSort,
+        // 3 is ctrl+c, it arrives as a byte because signals are turned off in the terminal
+        b'q' | 3 => Key::Quit,
+        _ => Key::Other,
+    }
+}
+
+/// Turns the bytes read from the terminal into keys, the arrow keys arrive as ESC [ A and ESC [ B
+#[derive(Debug, Default)]
+pub struct KeyDecoder {
+    pending: Vec<u8>,
+}
+
+impl KeyDecoder {
+    /// None while the byte is part of an escape sequence that is not done yet
+    pub fn feed(&mut self, byte: u8) -> Option<Key> {
+        const ESC: u8 = 27;
+        match (self.pending.as_slice(), byte) {
+            ([], ESC) | ([ESC], b'[') => {
+                self.pending.push(byte);
+                None
+            }
+            ([ESC, b'['], _) => {
+                self.pending.clear();
+                Some(match byte {
+                    b'A' => Key::ScrollUp,
+                    b'B' => Key::ScrollDown,
+                    _ => Key::Other,
+                })
+            }
+            // Alt and a key, or a sequence that is not one of the arrows
+            ([ESC], _) => {
+                self.pending.clear();
+                Some(Key::Other)
+            }
+            _ => Some(parse_key(byte)),
+        }
+    }
+}
+
+/// The column the per core table of the live view is sorted by
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortKey {
+    /// By core number
+    #[default]
+    Core,
+    /// Fastest first
+    Freq,
+    /// Hottest first
+    Temp,
+}
+
+impl SortKey {
+    pub fn next(self) -> SortKey {
+        match self {
+            SortKey::Core => SortKey::Freq,
+            SortKey::Freq => SortKey::Temp,
+            SortKey::Temp => SortKey::Core,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SortKey::Core => "core",
+            SortKey::Freq => "frequency",
+            SortKey::Temp => "temperature",
+        }
+    }
+}
+
+/// How the per core table of the live view is sorted and scrolled
+#[derive(Debug, Default)]
+pub struct CoreTable {
+    pub sort: SortKey,
+    /// The first row shown
+    pub offset: usize,
+    /// Rows that fit on the terminal, None shows every core
+    pub rows: Option<usize>,
+}
+
+impl CoreTable {
+    pub fn scroll(&mut self, key: &Key) {
+        match key {
+            Key::ScrollUp => self.offset = self.offset.saturating_sub(1),
+            Key::ScrollDown => self.offset += 1,
+            Key::CycleSort => self.sort = self.sort.next(),
+            _ => {}
+        }
+    }
+
+    /// Every core in the sort order
+    pub fn sorted<'a>(&self, cpus: &'a [CPU]) -> Vec<&'a CPU> {
+        let mut sorted: Vec<&CPU> = cpus.iter().collect();
+        match self.sort {
+            SortKey::Core => sorted.sort_by_key(|cpu| cpu.number),
+            SortKey::Freq => sorted.sort_by_key(|cpu| std::cmp::Reverse(cpu.cur_freq)),
+            SortKey::Temp => sorted.sort_by_key(|cpu| std::cmp::Reverse(cpu.cur_temp)),
+        }
+        sorted
+    }
+
+    /// The cores that fit in the rows, the offset is kept so that the last page stays full
+    /// when the terminal grows or cores go offline
+    pub fn visible<'a>(&mut self, cpus: &'a [CPU]) -> Vec<&'a CPU> {
+        let sorted = self.sorted(cpus);
+        let rows = match self.rows {
+            Some(rows) if rows < sorted.len() => rows,
+            _ => return sorted,
+        };
+        self.offset = self.offset.min(sorted.len() - rows);
+        sorted[self.offset..self.offset + rows].to_vec()
+    }
+
+    /// If only some of the cores fit
+    pub fn scrolled(&self, cores: usize) -> bool {
+        self.rows.is_some_and(|rows| rows < cores)
+    }
+
+    /// If the line saying which cores are shown and how is needed
+    pub fn has_info(&self, cores: usize) -> bool {
+        self.scrolled(cores) || self.sort != SortKey::Core
+    }
+
+    /// Lines the table takes for this many cores, without its title
+    pub fn lines(&self, cores: usize) -> usize {
+        self.rows.unwrap_or(cores).min(cores) + self.has_info(cores) as usize
+    }
+}
+
+/// Reads single key presses from the terminal while the live view is shown
+///
+/// The terminal is put into non-canonical mode so keys arrive without pressing enter. Output
+/// processing is left on so the rest of the printing does not need to change.
+pub struct KeyListener {
+    keys: Receiver<Key>,
+}
+
+/// The terminal settings from before the live view started listening for keys
+///
+/// Kept outside of the listener so the terminal can also be restored when exiting from a signal
+static ORIGINAL_TERMIOS: Mutex<Option<Termios>> = Mutex::new(None);
+
+/// Put the terminal back the way it was before listening for keys, if it was changed
+pub fn restore_terminal() {
+    if let Some(original) = ORIGINAL_TERMIOS.lock().unwrap().take() {
+        termios::tcsetattr(0, SetArg::TCSANOW, &original).ok();
+    }
+}
+
+impl KeyListener {
+    /// Start listening for keys, returns None when stdin is not a terminal (e.g. under systemd)
+    pub fn new() -> Option<KeyListener> {
+        if !isatty(0).unwrap_or(false) {
+            return None;
+        }
+
+        let original = termios::tcgetattr(0).ok()?;
+        let mut raw = original.clone();
+        raw.local_flags
+            .remove(LocalFlags::ICANON | LocalFlags::ECHO | LocalFlags::ISIG);
+        raw.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+        raw.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+        termios::tcsetattr(0, SetArg::TCSANOW, &raw).ok()?;
+        *ORIGINAL_TERMIOS.lock().unwrap() = Some(original);
+
+        let (sender, keys) = channel();
+        thread::spawn(move || {
+            let mut byte = [0u8; 1];
+            let mut decoder = KeyDecoder::default();
+            while let Ok(1) = stdin().read(&mut byte) {
+                let Some(key) = decoder.feed(byte[0]) else {
+                    continue;
+                };
+                if sender.send(key).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Some(KeyListener { keys })
+    }
+
+    /// Wait up to timeout for a key to be pressed
+    pub fn wait(&self, timeout: Duration) -> Option<Key> {
+        self.keys.recv_timeout(timeout).ok()
+    }
+}
+
+impl Drop for KeyListener {
+    /// The daemon loop can end with an error, which would leave the shell without echo or ctrl+c
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+/// Set every online core to the governor after the one cpu0 is using
+///
+/// Returns the name of the governor that was set
+pub fn cycle_governor(cpus: &[CPU]) -> Result<String, Error> {
+    let govs = check_available_governors()?;
+    let current = cpus.first().map(|c| c.gov.clone()).unwrap_or_default();
+
+    let next = match govs.iter().position(|g| *g == current) {
+        Some(i) => govs[(i + 1) % govs.len()].clone(),
+        None => govs.first().cloned().ok_or(Error::HdwNotFound)?,
+    };
+
+    for cpu in cpus {
+        match set_governor_for_core(cpu.number as usize, &next) {
+            // Offline cores can not have a governor
+            Ok(_) | Err(Error::CoreOffline(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(next)
+}
+
+/// Flip turbo and return if it is now enabled
+pub fn toggle_turbo() -> Result<bool, Error> {
+    let enabled = !check_turbo_enabled()?;
+    set_turbo(enabled)?;
+    Ok(enabled)
+}
 
 pub fn help() {
     const HELP_TEXT: &str = "\
@@ -12,17 +271,36 @@ pub fn help() {
 - get
   - freq
   - cpus
-  - temp
+  - temp [zone]
   - govs
+  - epp
+  - cstates
   - power
   - usage
   - turbo
   - speeds
   - available_governors
   - battery_condition
+  - charge_limit
+  - energy
+  - gpu
+  - fans
+  - tunables
+  - status
+  - profiles
 
 - set
   - gov
+  - epp
+  - profile <name>
+  - turbo on|off
+  - charge_limit <percent>
+  - max_freq <mhz>
+  - min_freq <mhz>
+
+- list
+  - governors
+  - thermal_zones
 
 - daemon
   - disable
@@ -33,25 +311,27 @@ pub fn help() {
 E.g. 'get cpus'
     ";
 
-    println!("{}{HELP_TEXT}", color!(Color::GREEN, "Help:\n"));
+    println!("{}{HELP_TEXT}", paint(Color::GREEN, "Help:\n"));
 }
 
 pub fn interactive() {
     let int = Interface {
         set: Set {},
-        get: Get {},
+        get: Get {
+            format: Format::Human,
+        },
         dec: DaemonControl {},
     };
 
     let mut input;
 
-    println!("{}", bold!("Auto Clock Speed"));
-    println!("{}", color!(Color::BLUE, "Interactive Mode"));
+    println!("{}", bold("Auto Clock Speed"));
+    println!("{}", paint(Color::BLUE, "Interactive Mode"));
 
     let set_settings = Settings::default();
 
     loop {
-        print!("{}", color!(Color::GREEN, "\n> "));
+        print!("{}", paint(Color::GREEN, "\n> "));
         stdout().flush().expect("Failed to flush stdout");
 
         input = String::new();
@@ -62,16 +342,25 @@ pub fn interactive() {
                 let new = input.as_str();
                 match new {
                     "help" => help(),
-                    "get freq" => int.get.freq(false),
+                    "get freq" => int.get.freq(false, get_config().avg_window),
                     "get power" => int.get.power(false),
                     "get usage" => int.get.usage(false, None),
                     "get turbo" => int.get.turbo(false),
                     "get available_governors" => int.get.available_govs(false),
                     "get cpus" => int.get.cpus(false),
                     "get speeds" => int.get.speeds(false),
-                    "get temp" => int.get.temp(false),
+                    "get temp" => int.get.temp(false, None),
                     "get govs" => int.get.govs(false),
+                    "get epp" => int.get.epp(false),
+                    "get cstates" => int.get.cstates(false),
                     "get battery_condition" => int.get.bat_cond(false),
+                    "get charge_limit" => int.get.charge_limit(false),
+                    "get energy" => int.get.energy(false),
+                    "get gpu" => int.get.gpu(false),
+                    "get fans" => int.get.fans(false),
+                    "get tunables" => int.get.tunables(false),
+                    "get status" => int.get.status(false),
+                    "get profiles" => int.get.profiles(&get_config(), false),
 
                     "set gov performance" => {
                         let config: Config = get_config();
@@ -86,18 +375,58 @@ pub fn interactive() {
                         int.set
                             .gov("powersave".to_string(), config, set_settings.clone());
                     }
+                    "set epp performance" => int.set.epp("performance".to_string()),
+                    "set epp balance_performance" => int.set.epp("balance_performance".to_string()),
+                    "set epp balance_power" => int.set.epp("balance_power".to_string()),
+                    "set epp power" => int.set.epp("power".to_string()),
+                    "set turbo on" => int.set.turbo(true),
+                    "set turbo off" => int.set.turbo(false),
                     "daemon disable" => int.dec.disable(),
                     "daemon enable" => int.dec.enable(),
                     "daemon status" => int.dec.status(),
                     "daemon toggle" => int.dec.toggle(),
 
+                    _ if new.starts_with("set profile ") => {
+                        let name = new.trim_start_matches("set profile ").to_string();
+                        int.set.profile(name, &get_config());
+                    }
+
+                    _ if new.starts_with("set charge_limit ") => {
+                        match new.trim_start_matches("set charge_limit ").parse::<u8>() {
+                            Ok(percent) => int.set.charge_limit(percent),
+                            Err(_) => println!("The charge limit has to be a number from 1 to 100"),
+                        }
+                    }
+
+                    _ if new.starts_with("set max_freq ") => {
+                        match new.trim_start_matches("set max_freq ").parse::<i32>() {
+                            Ok(mhz) => int.set.max_freq(mhz, None),
+                            Err(_) => println!("The frequency has to be a number in MHz"),
+                        }
+                    }
+
+                    _ if new.starts_with("set min_freq ") => {
+                        match new.trim_start_matches("set min_freq ").parse::<i32>() {
+                            Ok(mhz) => int.set.min_freq(mhz, None),
+                            Err(_) => println!("The frequency has to be a number in MHz"),
+                        }
+                    }
+
+                    _ if new.starts_with("get temp ") => {
+                        let zone = new.trim_start_matches("get temp ").to_string();
+                        int.get.temp(false, Some(zone));
+                    }
+
+                    "list governors" => int.get.governors_by_core(false),
+                    "list thermal_zones" => int.get.thermal(false),
+
                     "exit" => {
                         println!("Bye!");
                         return;
                     }
                     _ => println!(
                         "{}",
-                        color!(
+                        paint(
                             Color::RED,
                             format!("Command '{}' not found. Use 'help'.", new).as_str()
                         )
@@ -108,3 +437,75 @@ pub fn interactive() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_key_unit_test() {
+        assert_eq!(parse_key(b'g'), Key::CycleGovernor);
+        assert_eq!(parse_key(b't'), Key::ToggleTurbo);
+        assert_eq!(parse_key(b'q'), Key::Quit);
+        assert_eq!(parse_key(3), Key::Quit);
+        assert_eq!(parse_key(b'x'), Key::Other);
+    }
+
+    #[test]
+    fn key_decoder_unit_test() {
+        let mut decoder = KeyDecoder::default();
+        let keys: Vec<Key> = [27, b'[', b'A', b'j', 27, b'[', b'B', 27, b'x', b's']
+            .iter()
+            .filter_map(|&byte| decoder.feed(byte))
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                Key::ScrollUp,
+                Key::ScrollDown,
+                Key::ScrollDown,
+                Key::Other,
+                Key::CycleSort
+            ]
+        );
+    }
+
+    #[test]
+    fn core_table_unit_test() {
+        let cpu = |number: i8, cur_freq: i32, cur_temp: i32| CPU {
+            name: format!("cpu{}", number),
+            number,
+            cur_freq,
+            cur_temp,
+            ..CPU::default()
+        };
+        let cpus = vec![
+            cpu(0, 1_000_000, 70_000),
+            cpu(1, 3_000_000, 40_000),
+            cpu(2, 2_000_000, 50_000),
+            cpu(3, 4_000_000, 60_000),
+        ];
+        let names = |shown: Vec<&CPU>| shown.iter().map(|c| c.number).collect::<Vec<i8>>();
+
+        let mut table = CoreTable::default();
+        assert_eq!(names(table.visible(&cpus)), vec![0, 1, 2, 3]);
+        assert!(!table.scrolled(cpus.len()));
+
+        table.scroll(&Key::CycleSort);
+        assert_eq!(names(table.visible(&cpus)), vec![3, 1, 2, 0]);
+        table.scroll(&Key::CycleSort);
+        table.rows = Some(2);
+        assert!(table.scrolled(cpus.len()));
+        assert_eq!(names(table.visible(&cpus)), vec![0, 3]);
+
+        // Scrolling stops at the last page, also once the terminal grows
+        for _ in 0..5 {
+            table.scroll(&Key::ScrollDown);
+        }
+        assert_eq!(names(table.visible(&cpus)), vec![2, 1]);
+        table.rows = Some(3);
+        assert_eq!(names(table.visible(&cpus)), vec![3, 2, 1]);
+        table.scroll(&Key::ScrollUp);
+        assert_eq!(table.offset, 0);
+    }
+}
@@ -7,21 +7,46 @@
 //! This file offloads a lot of the work that was in src/args.rs but was too repetitive.
 //!
 use super::config::Config;
+use super::cpu::{
+    group_by_governors, is_core_online, list_available_governors, set_core_online, set_epp,
+    set_max_freq, set_min_freq, set_turbo,
+};
 use super::daemon::{daemon_init, Checker};
 use super::display::{
-    print_available_governors, print_bat_cond, print_cpu_governors, print_cpu_speeds,
-    print_cpu_temp, print_cpus, print_freq, print_power, print_turbo,
+    format_freq, print_available_governors, print_bat_cond, print_charge_limit, print_cpu_epps,
+    print_cpu_governors, print_cpu_speeds, print_cpu_temp, print_cpus, print_cstates, print_energy,
+    print_fans, print_freq, print_governors_by_core, print_gpu_clocks, print_power, print_profiles,
+    print_status, print_thermal_zones, print_tunables, print_turbo, print_usage,
+    print_voltage_offsets, print_zone_temp, Format, Status,
 };
-use super::power::battery::Battery;
+use super::error::Error;
+use super::fan::{list_fans, list_pwms, read_fan_speeds, set_fan};
+use super::gov::{list_tunables, set_tunable, Tunable};
+use super::gpu::{list_gpus, read_gpu_clocks};
+use super::msr::{get_voltage_offset, set_voltage_offset, VoltagePlane, VOLTAGE_PLANES};
+use super::power::battery::{get_charge_threshold, set_charge_threshold, Battery};
 use super::power::lid::{Lid, LidRetriever};
+use super::power::rapl::read_package_power;
 use super::power::{Power, PowerRetriever};
+use super::print_done;
+use super::profile::{apply_profile, find_profile};
 use super::settings::Settings;
 use super::system::{
-    check_available_governors, check_cpu_freq, check_cpu_name, check_turbo_enabled,
-    get_cpu_percent, list_cpu_governors, list_cpu_speeds, list_cpu_temp, list_cpus,
+    check_available_governors, check_cpu_freq, check_cpu_name, check_cpu_temperature,
+    check_turbo_enabled, get_cpu_percent, list_cpu_cstates, list_cpu_epps, list_cpu_governors,
+    list_cpu_speeds, list_cpu_temp, list_cpus, list_packages, sample_freq_window, shared_governor,
 };
-use super::thermal::read_thermal_zones;
+use super::thermal::{read_thermal_zones, zone_temp, ThrottleTracker};
 use crate::network::send::query_one;
+use crate::network::{Packet, SOCKET_PATH};
+use crate::sysfs;
+use std::time;
+
+/// Time between the readings `acs get freq` averages
+const AVG_SAMPLE_INTERVAL: time::Duration = time::Duration::from_millis(100);
+
+/// Time `acs get status` watches the throttle counters, when the cpu has them
+const THROTTLE_SAMPLE_INTERVAL: time::Duration = time::Duration::from_millis(250);
 
 pub struct DaemonControl {}
 
@@ -30,14 +55,15 @@ pub trait DaemonController {
     fn enable(&self);
     fn status(&self);
     fn toggle(&self);
+    fn reapply(&self);
 }
 
 impl DaemonController for DaemonControl {
     fn disable(&self) {
-        match query_one(
-            "/tmp/acs.sock",
-            crate::network::Packet::DaemonDisableRequest(),
-        ) {
+        if refuse_dry_run("disable itself") {
+            return;
+        }
+        match query_one(SOCKET_PATH, crate::network::Packet::DaemonDisableRequest()) {
             Ok(packet) => match packet {
                 crate::network::Packet::DaemonDisableResponse(success) => match success {
                     true => println!("The running daemon has been disabled"),
@@ -52,10 +78,10 @@ impl DaemonController for DaemonControl {
     }
 
     fn enable(&self) {
-        match query_one(
-            "/tmp/acs.sock",
-            crate::network::Packet::DaemonEnableRequest(),
-        ) {
+        if refuse_dry_run("enable itself") {
+            return;
+        }
+        match query_one(SOCKET_PATH, crate::network::Packet::DaemonEnableRequest()) {
             Ok(packet) => match packet {
                 crate::network::Packet::DaemonEnableResponse(success) => match success {
                     true => println!("The running daemon has been enabled"),
@@ -70,15 +96,20 @@ impl DaemonController for DaemonControl {
     }
 
     fn status(&self) {
-        match query_one(
-            "/tmp/acs.sock",
-            crate::network::Packet::DaemonStatusRequest(),
-        ) {
+        match query_one(SOCKET_PATH, crate::network::Packet::DaemonStatusRequest()) {
             Ok(packet) => match packet {
-                crate::network::Packet::DaemonStatusResponse(status) => match status {
-                    true => println!("The daemon is currently enabled"),
-                    false => println!("The daemon is currently disabled"),
-                },
+                crate::network::Packet::DaemonStatusResponse(status) => {
+                    match status {
+                        true => println!("The daemon is currently enabled"),
+                        false => println!("The daemon is currently disabled"),
+                    }
+
+                    if let Ok(Packet::DaemonEnergyResponse(Some(energy), secs)) =
+                        query_one(SOCKET_PATH, Packet::DaemonEnergyRequest())
+                    {
+                        print_energy(energy, secs, false, Format::Human);
+                    }
+                }
                 _ => println!("Failed: Unexpected response packet"),
             },
             Err(e) => {
@@ -88,14 +119,14 @@ impl DaemonController for DaemonControl {
     }
 
     fn toggle(&self) {
-        match query_one(
-            "/tmp/acs.sock",
-            crate::network::Packet::DaemonStatusRequest(),
-        ) {
+        if refuse_dry_run("toggle itself") {
+            return;
+        }
+        match query_one(SOCKET_PATH, crate::network::Packet::DaemonStatusRequest()) {
             Ok(packet) => match packet {
                 crate::network::Packet::DaemonStatusResponse(status) => {
                     match query_one(
-                        "/tmp/acs.sock",
+                        SOCKET_PATH,
                         match status {
                             true => crate::network::Packet::DaemonDisableRequest(),
                             false => crate::network::Packet::DaemonEnableRequest(),
@@ -122,28 +153,84 @@ impl DaemonController for DaemonControl {
             }
         }
     }
+
+    fn reapply(&self) {
+        if refuse_dry_run("apply its settings again") {
+            return;
+        }
+        match query_one(SOCKET_PATH, Packet::DaemonReapplyRequest()) {
+            Ok(packet) => match packet {
+                Packet::DaemonReapplyResponse(Ok(_)) => {
+                    println!("The running daemon applied its settings again")
+                }
+                Packet::DaemonReapplyResponse(Err(e)) => {
+                    eprint!("The running daemon could not apply its settings, {}", e)
+                }
+                _ => println!("Failed: Unexpected response packet"),
+            },
+            Err(e) => {
+                println!("{:?}", e)
+            }
+        }
+    }
 }
 
-pub struct Get {}
+/// A running daemon really writes what it is asked to, so nothing is sent to it during a dry run
+fn refuse_dry_run(action: &str) -> bool {
+    if sysfs::dry_run() {
+        eprintln!(
+            "Not asking the running daemon to {} during a dry run, it would write to sysfs",
+            action
+        );
+    }
+    sysfs::dry_run()
+}
+
+pub struct Get {
+    /// The format the values get printed in
+    pub format: Format,
+}
 
 pub trait Getter {
-    fn freq(&self, raw: bool);
+    fn freq(&self, raw: bool, avg_window: usize);
     fn power(&self, raw: bool);
     fn usage(&self, raw: bool, delay: Option<u64>);
     fn thermal(&self, raw: bool);
     fn turbo(&self, raw: bool);
     fn available_govs(&self, raw: bool);
+    fn governors_by_core(&self, raw: bool);
     fn cpus(&self, raw: bool);
     fn speeds(&self, raw: bool);
-    fn temp(&self, raw: bool);
+    fn temp(&self, raw: bool, zone: Option<String>);
     fn govs(&self, raw: bool);
+    fn epp(&self, raw: bool);
+    fn cstates(&self, raw: bool);
     fn bat_cond(&self, raw: bool);
+    fn charge_limit(&self, raw: bool);
+    fn gpu(&self, raw: bool);
+    fn fans(&self, raw: bool);
+    fn tunables(&self, raw: bool);
+    fn voltage_offset(&self, raw: bool);
+    fn energy(&self, raw: bool);
+    fn status(&self, raw: bool);
+    fn profiles(&self, config: &Config, raw: bool);
 }
 
 impl Getter for Get {
-    fn freq(&self, raw: bool) {
+    fn freq(&self, raw: bool, avg_window: usize) {
+        if avg_window == 0 {
+            print_freq(check_cpu_freq(&list_cpus()), None, raw, self.format);
+            return;
+        }
+
+        let window = sample_freq_window(avg_window, AVG_SAMPLE_INTERVAL);
         let f = check_cpu_freq(&list_cpus());
-        print_freq(f, raw);
+        print_freq(
+            f,
+            window.mean().map(|avg| (avg, window.samples())),
+            raw,
+            self.format,
+        );
     }
 
     fn power(&self, raw: bool) {
@@ -180,77 +267,111 @@ impl Getter for Get {
             }
         };
 
-        print_power(lid, battery.capacity, plugged, raw);
+        // Package power is only available with RAPL and usually needs root to read
+        let package_power = read_package_power().ok();
+
+        print_power(
+            lid,
+            battery.capacity,
+            plugged,
+            package_power,
+            raw,
+            self.format,
+        );
     }
 
     fn usage(&self, raw: bool, delay: Option<u64>) {
-        if !raw {
+        if !raw && self.format == Format::Human {
             println!(
                 "Calculating cpu percentage over {} second.",
                 delay.unwrap_or(1)
             );
         }
         let percent = get_cpu_percent(delay);
-
-        if raw {
-            println!("{}", percent)
-        } else {
-            println!("CPU is at {}%", percent)
-        }
+        print_usage(percent, raw, self.format);
     }
 
     fn thermal(&self, raw: bool) {
         let zones = match read_thermal_zones() {
             Ok(zones) => zones,
             Err(error) => {
-                println!("Error: {:?}", error);
+                eprint!("Error: {:?}", error);
                 return;
             }
         };
-        if raw {
-            println!("{:?}", zones)
-        } else {
-            for zone in zones {
-                println!("{}", zone);
-            }
-        }
+        print_thermal_zones(zones, raw, self.format);
     }
 
     fn turbo(&self, raw: bool) {
         match check_turbo_enabled() {
-            Ok(turbo_enabled) => print_turbo(turbo_enabled, raw),
-            Err(_) => println!("Failed to get turbo status"),
+            Ok(turbo_enabled) => print_turbo(turbo_enabled, raw, self.format),
+            Err(e) => eprintln!(
+                "Failed to get turbo status, {}",
+                format!("{:?}", e).trim_end()
+            ),
         };
     }
 
     fn available_govs(&self, raw: bool) {
         match check_available_governors() {
-            Ok(available_governors) => print_available_governors(available_governors, raw),
-            Err(_) => println!("Failed to get available governors"),
+            Ok(available_governors) => {
+                print_available_governors(available_governors, raw, self.format)
+            }
+            Err(_) => eprintln!("Failed to get available governors"),
         };
     }
 
+    fn governors_by_core(&self, raw: bool) {
+        let groups = group_by_governors(list_available_governors(&list_cpus()));
+        if groups.is_empty() && self.format == Format::Human {
+            eprintln!("Failed to get available governors");
+            return;
+        }
+        print_governors_by_core(&groups, raw, self.format);
+    }
+
     fn cpus(&self, raw: bool) {
         let cpus = list_cpus();
         match check_cpu_name() {
-            Ok(name) => print_cpus(cpus, name, raw),
-            Err(_) => println!("Failed get list of cpus"),
+            Ok(name) => print_cpus(cpus, name, raw, self.format),
+            Err(_) => eprintln!("Failed get list of cpus"),
         };
     }
 
     fn speeds(&self, raw: bool) {
         let speeds = list_cpu_speeds();
-        print_cpu_speeds(speeds, raw);
+        print_cpu_speeds(speeds, raw, self.format);
     }
 
-    fn temp(&self, raw: bool) {
+    fn temp(&self, raw: bool, zone: Option<String>) {
+        if let Some(zone) = zone {
+            let zones = read_thermal_zones().unwrap_or_default();
+            match zone_temp(&zones, std::slice::from_ref(&zone)) {
+                Some(temp) => print_zone_temp(&zone, temp, raw, self.format),
+                None => {
+                    let names = zones.into_iter().map(|z| z.name).collect();
+                    eprint!("{:?}", Error::InvalidValue(zone, names));
+                }
+            }
+            return;
+        }
+
         let cpu_temp = list_cpu_temp();
-        print_cpu_temp(cpu_temp, raw);
+        print_cpu_temp(cpu_temp, raw, self.format);
     }
 
     fn govs(&self, raw: bool) {
         let govs = list_cpu_governors();
-        print_cpu_governors(govs, raw);
+        print_cpu_governors(govs, raw, self.format);
+    }
+
+    fn epp(&self, raw: bool) {
+        let epps = list_cpu_epps();
+        print_cpu_epps(epps, raw, self.format);
+    }
+
+    fn cstates(&self, raw: bool) {
+        print_cstates(list_cpu_cstates(), raw, self.format);
     }
 
     fn bat_cond(&self, raw: bool) {
@@ -261,7 +382,100 @@ impl Getter for Get {
                 return;
             }
         };
-        print_bat_cond(battery.condition, raw)
+        print_bat_cond(battery.condition, raw, self.format)
+    }
+
+    fn charge_limit(&self, raw: bool) {
+        match get_charge_threshold() {
+            Ok(percent) => print_charge_limit(percent, raw, self.format),
+            Err(e) => eprintln!("Failed to get charge limit, {:?}", e),
+        }
+    }
+
+    fn gpu(&self, raw: bool) {
+        let clocks = read_gpu_clocks(&list_gpus());
+        if clocks.is_empty() && self.format == Format::Human {
+            eprintln!("No GPU with a supported clock interface was found");
+            return;
+        }
+        print_gpu_clocks(&clocks, raw, self.format);
+    }
+
+    fn fans(&self, raw: bool) {
+        let fans = read_fan_speeds(&list_fans());
+        let pwms = list_pwms();
+        if fans.is_empty() && pwms.is_empty() && self.format == Format::Human {
+            eprintln!("No fan speed sensor was found");
+            return;
+        }
+        print_fans(&fans, &pwms, raw, self.format);
+    }
+
+    fn tunables(&self, raw: bool) {
+        let tunables = list_tunables();
+        if tunables.is_empty() && self.format == Format::Human {
+            eprintln!("The governors in use have no tunables acs knows of");
+            return;
+        }
+        print_tunables(&tunables, raw, self.format);
+    }
+
+    fn voltage_offset(&self, raw: bool) {
+        let offsets: Result<Vec<(VoltagePlane, i32)>, Error> = VOLTAGE_PLANES
+            .iter()
+            .map(|plane| Ok((*plane, get_voltage_offset(*plane)?)))
+            .collect();
+        match offsets {
+            Ok(offsets) => print_voltage_offsets(&offsets, raw, self.format),
+            Err(e) => eprint!("Could not read the voltage offsets, {:?}", e),
+        }
+    }
+
+    fn energy(&self, raw: bool) {
+        // The energy is counted by the daemon over its whole run
+        match query_one(SOCKET_PATH, Packet::DaemonEnergyRequest()) {
+            Ok(Packet::DaemonEnergyResponse(Some(energy), secs)) => {
+                print_energy(energy, secs, raw, self.format)
+            }
+            Ok(Packet::DaemonEnergyResponse(None, _)) => {
+                eprintln!("The running daemon can not read the package energy counter")
+            }
+            Ok(_) => eprintln!("Failed: Unexpected response packet"),
+            Err(e) => eprintln!("Failed to get energy from the daemon, {:?}", e),
+        }
+    }
+
+    fn status(&self, raw: bool) {
+        let cpus = list_cpus();
+        let mut throttle = ThrottleTracker::default();
+        throttle.update(&cpus);
+        let throttling = if throttle.supported() {
+            std::thread::sleep(THROTTLE_SAMPLE_INTERVAL);
+            throttle.update(&cpus)
+        } else {
+            Vec::new()
+        };
+        let battery = Battery::new().and_then(|mut battery| {
+            battery.update()?;
+            Ok(battery)
+        });
+
+        let status = Status {
+            avg_freq_mhz: check_cpu_freq(&cpus) / 1000.0,
+            temp_c: check_cpu_temperature(&cpus) / 1000.0,
+            governor: shared_governor(&cpus),
+            packages: list_packages(&cpus),
+            gpus: read_gpu_clocks(&list_gpus()),
+            battery_percent: battery.as_ref().ok().map(|b| b.capacity),
+            battery_time: battery.ok().and_then(|b| b.time_estimate),
+            cores: cpus,
+            throttling,
+        };
+        print_status(status, raw, self.format);
+    }
+
+    fn profiles(&self, config: &Config, raw: bool) {
+        print_profiles(&config.profiles, raw, self.format);
     }
 }
 
@@ -269,16 +483,221 @@ pub struct Set {}
 
 pub trait Setter {
     fn gov(&self, value: String, config: Config, settings: Settings);
+    fn epp(&self, value: String);
+    fn profile(&self, name: String, config: &Config);
+    fn turbo(&self, enabled: bool);
+    fn max_freq(&self, mhz: i32, core: Option<usize>);
+    fn min_freq(&self, mhz: i32, core: Option<usize>);
+    fn core_online(&self, core: usize, online: bool);
+    fn charge_limit(&self, value: u8);
+    fn tunable(&self, tunable: Tunable, value: u64);
+    fn fan(&self, pwm: String, value: Option<u8>, config: &Config);
+    fn voltage_offset(
+        &self,
+        plane: VoltagePlane,
+        millivolts: i32,
+        confirmed: bool,
+        config: &Config,
+    );
+}
+
+/// The cores a frequency limit gets written to, one core or every online core
+fn freq_cores(core: Option<usize>) -> Vec<usize> {
+    match core {
+        Some(core) => vec![core],
+        None => list_cpus()
+            .iter()
+            .map(|cpu| cpu.number as usize)
+            .filter(|&core| is_core_online(core))
+            .collect(),
+    }
 }
 
 impl Setter for Set {
     fn gov(&self, value: String, config: Config, settings: Settings) {
+        // A running daemon would switch the governor back, so ask it to make the change, unless
+        // this is a dry run since the daemon would really write it
+        let asked = match sysfs::dry_run() {
+            true => None,
+            false => query_one(SOCKET_PATH, Packet::DaemonSetGovernorRequest(value.clone())).ok(),
+        };
+        if let Some(packet) = asked {
+            match packet {
+                Packet::DaemonSetGovernorResponse(Ok(_)) => {
+                    println!("The running daemon set the governor to {}", value)
+                }
+                Packet::DaemonSetGovernorResponse(Err(e)) => {
+                    eprint!("The running daemon could not set gov, {}", e)
+                }
+                _ => println!("Failed: Unexpected response packet"),
+            }
+            return;
+        }
+
         // Create the daemon to set the gov
         let d = daemon_init(settings, config);
         match d.lock().unwrap().set_govs(value) {
             Ok(_) => {}
             Err(e) => eprint!("Could not set gov, {:?}", e),
         };
+        // The daemon keeps the dry run writes for its log, there is no log here
+        sysfs::print_dry_run_log();
+    }
+
+    fn epp(&self, value: String) {
+        for cpu in list_cpus() {
+            if let Err(e) = set_epp(cpu.number as usize, &value) {
+                eprint!("Could not set epp for {}, {:?}", cpu.name, e);
+                return;
+            }
+        }
+    }
+
+    fn profile(&self, name: String, config: &Config) {
+        // A running daemon applies it again after a resume, so it has to know about it
+        let asked = match sysfs::dry_run() {
+            true => None,
+            false => query_one(SOCKET_PATH, Packet::DaemonApplyProfileRequest(name.clone())).ok(),
+        };
+        if let Some(packet) = asked {
+            match packet {
+                Packet::DaemonApplyProfileResponse(Ok(_)) => {
+                    print_done!(format!("The running daemon applied profile '{}'", name))
+                }
+                Packet::DaemonApplyProfileResponse(Err(e)) => {
+                    eprint!(
+                        "The running daemon could not apply profile '{}', {}",
+                        name, e
+                    )
+                }
+                _ => println!("Failed: Unexpected response packet"),
+            }
+            return;
+        }
+
+        let profile = match find_profile(config, &name) {
+            Ok(profile) => profile,
+            Err(e) => {
+                eprint!("{:?}", e);
+                return;
+            }
+        };
+
+        match apply_profile(profile) {
+            Ok(_) => print_done!(format!("Applied profile '{}'", name)),
+            Err(e) => eprint!("Could not apply profile '{}', {:?}", name, e),
+        }
+    }
+
+    fn turbo(&self, enabled: bool) {
+        match set_turbo(enabled) {
+            Ok(_) => print_done!(format!(
+                "Turbo {}",
+                if enabled { "enabled" } else { "disabled" }
+            )),
+            Err(e) => eprint!("Could not set turbo, {:?}", e),
+        }
+    }
+
+    fn max_freq(&self, mhz: i32, core: Option<usize>) {
+        for core in freq_cores(core) {
+            if let Err(e) = set_max_freq(core, mhz * 1000) {
+                eprint!("Could not set max frequency for cpu{}, {:?}", core, e);
+                return;
+            }
+        }
+        print_done!(format!(
+            "Max frequency set to {}",
+            format_freq(mhz as f64 * 1000.0)
+        ));
+    }
+
+    fn min_freq(&self, mhz: i32, core: Option<usize>) {
+        for core in freq_cores(core) {
+            if let Err(e) = set_min_freq(core, mhz * 1000) {
+                eprint!("Could not set min frequency for cpu{}, {:?}", core, e);
+                return;
+            }
+        }
+        print_done!(format!(
+            "Min frequency set to {}",
+            format_freq(mhz as f64 * 1000.0)
+        ));
+    }
+
+    fn core_online(&self, core: usize, online: bool) {
+        let state = if online { "online" } else { "offline" };
+        match set_core_online(core, online) {
+            Ok(_) => print_done!(format!("Core {} is {}", core, state)),
+            Err(e) => eprint!("Could not set core {} {}, {:?}", core, state, e),
+        }
+    }
+
+    fn charge_limit(&self, value: u8) {
+        match set_charge_threshold(value) {
+            Ok(_) => print_done!(format!("Battery will stop charging at {}%", value)),
+            Err(e) => eprint!("Could not set charge limit, {:?}", e),
+        }
+    }
+
+    fn tunable(&self, tunable: Tunable, value: u64) {
+        match set_tunable(tunable, value) {
+            Ok(_) => print_done!(format!(
+                "Set {} {} to {}",
+                tunable.governor(),
+                tunable.file(),
+                value
+            )),
+            Err(e) => eprint!("Could not set {}, {:?}", tunable.file(), e),
+        }
+    }
+
+    fn fan(&self, pwm: String, value: Option<u8>, config: &Config) {
+        if !config.experimental_fan_control {
+            eprintln!(
+                "Fan control is experimental, set experimental_fan_control = true in the config to use it"
+            );
+            return;
+        }
+
+        match set_fan(&pwm, value) {
+            Ok(_) => match value {
+                Some(value) => print_done!(format!("Set {} to {}/255", pwm, value)),
+                None => print_done!(format!("Handed {} back to automatic control", pwm)),
+            },
+            Err(e) => eprint!("Could not set the fan, {:?}", e),
+        }
+    }
+
+    fn voltage_offset(
+        &self,
+        plane: VoltagePlane,
+        millivolts: i32,
+        confirmed: bool,
+        config: &Config,
+    ) {
+        if !config.experimental_undervolt {
+            eprintln!(
+                "Undervolting is experimental, set experimental_undervolt = true in the config to use it"
+            );
+            return;
+        }
+        if !confirmed {
+            eprintln!("Too large an undervolt freezes the machine, pass --i-understand-the-risks to set it");
+            return;
+        }
+
+        match set_voltage_offset(plane, millivolts) {
+            Ok(offset) if offset == millivolts => {
+                print_done!(format!("Set the {} offset to {} mV", plane, offset))
+            }
+            // Locked cpus take the write without applying it
+            Ok(offset) => eprintln!(
+                "The cpu did not take the offset, the {} offset is still {} mV",
+                plane, offset
+            ),
+            Err(e) => eprint!("Could not set the voltage offset, {:?}", e),
+        }
     }
 }
 
@@ -1,20 +1,36 @@
+pub mod alert;
 pub mod args;
+pub mod bench;
 pub mod config;
 pub mod cpu;
 pub mod csv;
 pub mod daemon;
 pub mod display;
 pub mod error;
+pub mod fan;
 pub mod gov;
+pub mod gpu;
 pub mod graph;
+pub mod history;
+pub mod hooks;
 pub mod interactive;
 pub mod interface;
 pub mod logger;
+pub mod msr;
 pub mod network;
+pub mod notify;
+pub mod poll;
 pub mod power;
 pub mod proc;
+pub mod profile;
 pub mod settings;
 pub mod setup;
+pub mod signals;
+pub mod snapshot;
 pub mod sysfs;
 pub mod system;
+#[cfg(test)]
+mod test_dir;
+pub mod theme;
 pub mod thermal;
+pub mod watchdog;
@@ -3,13 +3,25 @@
 //! It allows the program to log messages with different severity levels (error, warning, log) and display them in a human-readable format.
 //!
 //! The log messages contain a timestamp, a severity level and the message. The logs are stored in a vector and are serializable and deserializable. The logs can also be displayed in a human-readable format.
+//!
+//! With the journald backend each log is also sent to the systemd journal with its priority and
+//! any extra fields, such as CORE or GOVERNOR, so they can be filtered with `journalctl -o json`.
 
+use crate::theme::paint;
 use chrono::{DateTime, Local};
-use efcl::{color, Color};
+use efcl::Color;
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::fs;
+use std::os::linux::fs::MetadataExt;
+use std::os::unix::net::UnixDatagram;
 use std::time::SystemTime;
 
+use crate::error::Error;
+
+/// Where journald listens for structured log entries
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
 #[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
 /// The Severity enum is used to represent the different levels of severity of a log message. It has three possible values:
 ///
@@ -24,8 +36,82 @@ pub enum Severity {
     Log,
 }
 
+impl Severity {
+    /// The syslog priority used by the journal
+    pub fn priority(&self) -> u8 {
+        match self {
+            Severity::Error => 3,
+            Severity::Warning => 4,
+            Severity::Log => 6,
+        }
+    }
+}
+
 pub trait Interface {
     fn log(&mut self, msg: &str, sev: Severity);
+    /// Log with extra fields for backends that keep them, e.g. `[("CORE", "2".to_string())]`
+    fn log_with(&mut self, msg: &str, sev: Severity, fields: &[(&str, String)]);
+}
+
+/// Where logs get sent besides being kept for the live view
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    #[default]
+    Stdout,
+    Journald,
+}
+
+impl Backend {
+    /// Pick the backend from the `log_backend` config value
+    ///
+    /// Without one, journald is used when stderr is connected to the journal
+    pub fn from_config(value: Option<&str>) -> Result<Backend, Error> {
+        match value {
+            Some("stdout") => Ok(Backend::Stdout),
+            Some("journald") => Ok(Backend::Journald),
+            Some(other) => Err(Error::InvalidValue(
+                other.to_string(),
+                vec!["stdout".to_string(), "journald".to_string()],
+            )),
+            None if connected_to_journal() => Ok(Backend::Journald),
+            None => Ok(Backend::Stdout),
+        }
+    }
+}
+
+/// Check if stderr is the stream systemd connected to the journal
+///
+/// systemd sets $JOURNAL_STREAM to the device and inode of that stream, the variable alone is
+/// not enough since a process started from a unit's shell inherits it
+fn connected_to_journal() -> bool {
+    let stream = match std::env::var("JOURNAL_STREAM") {
+        Ok(stream) => stream,
+        Err(_) => return false,
+    };
+
+    match fs::metadata("/proc/self/fd/2") {
+        Ok(meta) => stream == format!("{}:{}", meta.st_dev(), meta.st_ino()),
+        Err(_) => false,
+    }
+}
+
+/// Serialize fields in the native journal protocol
+///
+/// Values with a newline are written with their length in front since the newline would end them
+pub fn journal_entry(fields: &[(&str, String)]) -> Vec<u8> {
+    let mut entry = Vec::new();
+    for (key, value) in fields {
+        entry.extend_from_slice(key.as_bytes());
+        if value.contains('\n') {
+            entry.push(b'\n');
+            entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            entry.push(b'=');
+        }
+        entry.extend_from_slice(value.as_bytes());
+        entry.push(b'\n');
+    }
+    entry
 }
 
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -38,9 +124,9 @@ pub struct Log {
 impl fmt::Display for Log {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let severity = match &self.severity {
-            Severity::Error => color!(Color::RED, "error:"),
-            Severity::Warning => color!(Color::YELLOW, "warn:"),
-            Severity::Log => color!(Color::BLUE, "notice:"),
+            Severity::Error => paint(Color::RED, "error:"),
+            Severity::Warning => paint(Color::YELLOW, "warn:"),
+            Severity::Log => paint(Color::BLUE, "notice:"),
         };
 
         let local_time: DateTime<Local> = DateTime::<Local>::from(self.timestamp);
@@ -57,13 +143,62 @@ impl fmt::Display for Log {
 
 pub struct Logger {
     pub logs: Vec<Log>,
+    pub backend: Backend,
+    journal: Option<UnixDatagram>,
+}
+
+impl Logger {
+    pub fn new(backend: Backend) -> Logger {
+        let mut logger = Logger {
+            logs: Vec::new(),
+            backend: Backend::Stdout,
+            journal: None,
+        };
+        logger.set_backend(backend);
+        logger
+    }
+
+    /// Switch the backend, falls back to stdout if the journal can not be reached
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+        self.journal = None;
+
+        if backend == Backend::Journald {
+            match UnixDatagram::unbound().and_then(|s| s.connect(JOURNAL_SOCKET).map(|_| s)) {
+                Ok(socket) => self.journal = Some(socket),
+                Err(e) => {
+                    self.backend = Backend::Stdout;
+                    self.log(
+                        &format!("Could not connect to the journal, logging to stdout: {}", e),
+                        Severity::Warning,
+                    );
+                }
+            }
+        }
+    }
 }
 
 impl Interface for Logger {
     /// Create a Log with the timestamp from message and severity
     fn log(&mut self, msg: &str, sev: Severity) {
+        self.log_with(msg, sev, &[]);
+    }
+
+    /// Create a Log and send it to the journal with the fields when that backend is used
+    fn log_with(&mut self, msg: &str, sev: Severity, fields: &[(&str, String)]) {
         let time = SystemTime::now();
 
+        if let Some(journal) = &self.journal {
+            let mut entry = vec![
+                ("MESSAGE", msg.trim_end().to_string()),
+                ("PRIORITY", sev.priority().to_string()),
+                ("SYSLOG_IDENTIFIER", "acs".to_string()),
+            ];
+            entry.extend(fields.iter().cloned());
+            // A full journal drops the entry, it is still kept for the live view
+            journal.send(&journal_entry(&entry)).ok();
+        }
+
         let loggable = Log {
             message: msg.to_string(),
             severity: sev,
@@ -73,3 +208,36 @@ impl Interface for Logger {
         self.logs.push(loggable);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn journal_entry_unit_test() {
+        let entry = journal_entry(&[
+            ("MESSAGE", "State changed".to_string()),
+            ("CORE", "2".to_string()),
+        ]);
+        assert_eq!(entry, b"MESSAGE=State changed\nCORE=2\n");
+
+        let entry = journal_entry(&[("MESSAGE", "a\nb".to_string())]);
+        let mut expected = b"MESSAGE\n".to_vec();
+        expected.extend_from_slice(&3u64.to_le_bytes());
+        expected.extend_from_slice(b"a\nb\n");
+        assert_eq!(entry, expected);
+    }
+
+    #[test]
+    fn backend_from_config_unit_test() {
+        assert_eq!(
+            Backend::from_config(Some("journald")).unwrap(),
+            Backend::Journald
+        );
+        assert_eq!(
+            Backend::from_config(Some("stdout")).unwrap(),
+            Backend::Stdout
+        );
+        assert!(Backend::from_config(Some("syslog")).is_err());
+    }
+}
@@ -0,0 +1,265 @@
+#![forbid(unsafe_code)]
+//! Model specific registers through `/dev/cpu/N/msr`, and experimental Intel undervolting
+//!
+//! The msr kernel module has to be loaded (`modprobe msr`). Undervolting writes the voltage
+//! offset mailbox in MSR 0x150 the same way intel-undervolt and undervolt.py do: the top bit
+//! starts a request, bits 40-43 pick the plane, bit 32 makes it a write, and the offset is an
+//! 11 bit signed number of 1/1.024 mV steps in bits 21-31. Reading an offset back is a request
+//! without the write bit followed by a read of the same MSR.
+//!
+//! Too large an offset freezes the machine, and many newer cpus ignore the writes since the
+//! Plundervolt fix, which is why the offset is always read back after setting it.
+use crate::error::Error;
+use crate::sysfs;
+use crate::theme::paint;
+use efcl::Color;
+use std::fmt::Display;
+use std::fs::{self, OpenOptions};
+use std::os::unix::fs::FileExt;
+use std::str::FromStr;
+
+/// The voltage offset mailbox
+const MSR_VOLTAGE_OFFSET: u64 = 0x150;
+
+/// The largest undervolt accepted, in mV
+pub const MAX_UNDERVOLT_MV: i32 = 250;
+
+/// Intel family 6 models known to honour the voltage offset mailbox, Haswell to Comet Lake
+const SUPPORTED_MODELS: [u32; 16] = [
+    0x3c, 0x3f, 0x45, 0x46, // Haswell
+    0x3d, 0x47, 0x4f, 0x56, // Broadwell
+    0x4e, 0x5e, 0x55, // Skylake
+    0x8e, 0x9e, // Kaby Lake, Coffee Lake, Whiskey Lake
+    0xa5, 0xa6, // Comet Lake
+    0x66, // Cannon Lake
+];
+
+fn msr_path(cpu: usize) -> String {
+    format!("/dev/cpu/{}/msr", cpu)
+}
+
+fn open_error(e: std::io::Error, cpu: usize) -> Error {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => {
+            Error::Unsupported("Reading MSRs without the msr kernel module".to_string())
+        }
+        std::io::ErrorKind::PermissionDenied => Error::PermissionDenied {
+            path: msr_path(cpu),
+        },
+        _ => Error::IO(e),
+    }
+}
+
+pub fn read_msr(cpu: usize, register: u64) -> Result<u64, Error> {
+    let file = OpenOptions::new()
+        .read(true)
+        .open(msr_path(cpu))
+        .map_err(|e| open_error(e, cpu))?;
+    let mut value = [0u8; 8];
+    file.read_exact_at(&mut value, register)?;
+    Ok(u64::from_le_bytes(value))
+}
+
+pub fn write_msr(cpu: usize, register: u64, value: u64) -> Result<(), Error> {
+    let file = OpenOptions::new()
+        .write(true)
+        .open(msr_path(cpu))
+        .map_err(|e| open_error(e, cpu))?;
+    file.write_all_at(&value.to_le_bytes(), register)?;
+    Ok(())
+}
+
+/// A voltage plane that takes its own offset
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoltagePlane {
+    Core = 0,
+    Gpu = 1,
+    Cache = 2,
+    /// The system agent
+    Uncore = 3,
+    AnalogIo = 4,
+}
+
+pub const VOLTAGE_PLANES: [VoltagePlane; 5] = [
+    VoltagePlane::Core,
+    VoltagePlane::Gpu,
+    VoltagePlane::Cache,
+    VoltagePlane::Uncore,
+    VoltagePlane::AnalogIo,
+];
+
+impl Display for VoltagePlane {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            VoltagePlane::Core => "core",
+            VoltagePlane::Gpu => "gpu",
+            VoltagePlane::Cache => "cache",
+            VoltagePlane::Uncore => "uncore",
+            VoltagePlane::AnalogIo => "analogio",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for VoltagePlane {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        VOLTAGE_PLANES
+            .iter()
+            .find(|plane| plane.to_string() == s.to_lowercase())
+            .copied()
+            .ok_or_else(|| {
+                format!(
+                    "Unknown voltage plane \"{}\", can be core, gpu, cache, uncore, or analogio",
+                    s
+                )
+            })
+    }
+}
+
+/// The offset bits of a request, rounded to the nearest 1/1.024 mV step
+pub fn encode_offset(millivolts: i32) -> u32 {
+    let steps = (millivolts as f64 * 1.024).round() as i32;
+    0xffe0_0000 & (((steps & 0xfff) as u32) << 21)
+}
+
+/// The offset in mV from a value read back from the mailbox
+pub fn decode_offset(value: u64) -> i32 {
+    let steps = ((value >> 21) & 0x7ff) as i32;
+    let steps = if steps <= 1024 { steps } else { steps - 2048 };
+    (steps as f64 / 1.024).round() as i32
+}
+
+/// The value written to the mailbox, a read request when there is no offset
+pub fn offset_request(plane: VoltagePlane, millivolts: Option<i32>) -> u64 {
+    let request = 0x8000_0010_0000_0000 | ((plane as u64) << 40);
+    match millivolts {
+        Some(millivolts) => request | (1 << 32) | encode_offset(millivolts) as u64,
+        None => request,
+    }
+}
+
+/// The vendor, family, and model of the first cpu in /proc/cpuinfo
+pub fn parse_cpu_model(cpu_info: &str) -> Option<(String, u32, u32)> {
+    let field = |name: &str| {
+        cpu_info
+            .lines()
+            .find(|line| line.split(':').next().map(str::trim) == Some(name))
+            .and_then(|line| line.split(':').nth(1))
+            .map(|value| value.trim().to_string())
+    };
+    Some((
+        field("vendor_id")?,
+        field("cpu family")?.parse().ok()?,
+        field("model")?.parse().ok()?,
+    ))
+}
+
+pub fn undervolt_supported(vendor: &str, family: u32, model: u32) -> bool {
+    vendor == "GenuineIntel" && family == 6 && SUPPORTED_MODELS.contains(&model)
+}
+
+/// Refuse cpus that are not known to take voltage offsets
+fn check_undervolt_supported() -> Result<(), Error> {
+    let cpu_info = fs::read_to_string("/proc/cpuinfo")?;
+    match parse_cpu_model(&cpu_info) {
+        Some((vendor, family, model)) if undervolt_supported(&vendor, family, model) => Ok(()),
+        _ => Err(Error::Unsupported("Undervolting".to_string())),
+    }
+}
+
+/// Read the offset currently programmed for a plane in mV
+pub fn get_voltage_offset(plane: VoltagePlane) -> Result<i32, Error> {
+    check_undervolt_supported()?;
+    write_msr(0, MSR_VOLTAGE_OFFSET, offset_request(plane, None))?;
+    Ok(decode_offset(read_msr(0, MSR_VOLTAGE_OFFSET)?))
+}
+
+/// Program an offset in mV for a plane, from -MAX_UNDERVOLT_MV up to 0
+///
+/// The planes are shared by the whole package so the request goes through cpu0. Returns the
+/// offset read back afterwards, which is not the one set when the cpu ignored the write.
+pub fn set_voltage_offset(plane: VoltagePlane, millivolts: i32) -> Result<i32, Error> {
+    if !(-MAX_UNDERVOLT_MV..=0).contains(&millivolts) {
+        return Err(Error::OutOfRange(
+            millivolts as i64,
+            -MAX_UNDERVOLT_MV as i64,
+            0,
+        ));
+    }
+    check_undervolt_supported()?;
+
+    // Reading an offset also writes the MSR, so a dry run stops before the request
+    if sysfs::dry_run() {
+        println!(
+            "{}: would set the {} voltage offset -> {}mV",
+            paint(Color::YELLOW, "DRY RUN"),
+            plane,
+            millivolts
+        );
+        return Ok(millivolts);
+    }
+
+    write_msr(
+        0,
+        MSR_VOLTAGE_OFFSET,
+        offset_request(plane, Some(millivolts)),
+    )?;
+    get_voltage_offset(plane)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offset_request_unit_test() {
+        // The values undervolt.py writes
+        assert_eq!(
+            offset_request(VoltagePlane::Core, Some(-100)),
+            0x80000011f3400000
+        );
+        assert_eq!(
+            offset_request(VoltagePlane::Cache, Some(-100)),
+            0x80000211f3400000
+        );
+        assert_eq!(
+            offset_request(VoltagePlane::Gpu, Some(0)),
+            0x8000011100000000
+        );
+        assert_eq!(
+            offset_request(VoltagePlane::Uncore, None),
+            0x8000031000000000
+        );
+
+        assert_eq!(decode_offset(0xf3400000), -100);
+        assert_eq!(decode_offset(0), 0);
+        for millivolts in [-250, -125, -50, -1, 0] {
+            assert_eq!(decode_offset(encode_offset(millivolts) as u64), millivolts);
+        }
+    }
+
+    #[test]
+    fn parse_cpu_model_unit_test() {
+        let cpu_info = "processor\t: 0\nvendor_id\t: GenuineIntel\ncpu family\t: 6\nmodel\t\t: 142\nmodel name\t: Intel(R) Core(TM) i7-8550U CPU @ 1.80GHz\n";
+        let (vendor, family, model) = parse_cpu_model(cpu_info).unwrap();
+        assert_eq!((vendor.as_str(), family, model), ("GenuineIntel", 6, 142));
+        assert!(undervolt_supported(&vendor, family, model));
+
+        // Alder Lake ignores the offsets
+        assert!(!undervolt_supported("GenuineIntel", 6, 0x9a));
+        assert!(!undervolt_supported("AuthenticAMD", 23, 0x8e));
+        assert_eq!(parse_cpu_model("processor\t: 0\n"), None);
+    }
+
+    #[test]
+    fn voltage_plane_unit_test() {
+        assert_eq!("cache".parse::<VoltagePlane>(), Ok(VoltagePlane::Cache));
+        assert_eq!(
+            "AnalogIO".parse::<VoltagePlane>(),
+            Ok(VoltagePlane::AnalogIo)
+        );
+        assert!("memory".parse::<VoltagePlane>().is_err());
+    }
+}
@@ -15,10 +15,19 @@ use std::str::ParseBoolError;
 use std::sync::Arc;
 use std::sync::Mutex;
 
+#[cfg(feature = "dbus")]
+pub mod dbus;
 pub mod hook;
+pub mod influx;
 pub mod listen;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod remote;
 pub mod send;
 
+/// Where the daemon listens for clients
+pub const SOCKET_PATH: &str = "/run/acs.sock";
+
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum Packet {
     Hello(String),
@@ -29,6 +38,15 @@ pub enum Packet {
     DaemonEnableResponse(bool),
     DaemonStatusRequest(),
     DaemonStatusResponse(bool),
+    DaemonSetGovernorRequest(String),
+    DaemonSetGovernorResponse(Result<(), String>),
+    DaemonReapplyRequest(),
+    DaemonReapplyResponse(Result<(), String>),
+    DaemonApplyProfileRequest(String),
+    DaemonApplyProfileResponse(Result<(), String>),
+    DaemonEnergyRequest(),
+    /// Package energy in microjoules used since the daemon started, and the seconds it has run
+    DaemonEnergyResponse(Option<u64>, u64),
     DaemonLogRequest(),
     DaemonLogResponse(Vec<Log>),
     DaemonLogEvent(Log),
@@ -60,6 +78,28 @@ pub fn parse_packet(packet: &str) -> Result<Packet, PacketParseError> {
     serde_json::from_str(packet).map_err(|_| PacketParseError)
 }
 
+/// Parse a plain text command such as `pause` or `set-governor performance`
+///
+/// These make it possible to control the daemon by hand, e.g. with socat, without writing JSON
+pub fn parse_command(command: &str) -> Result<Packet, PacketParseError> {
+    let mut words = command.split_whitespace();
+    let packet = match (words.next(), words.next()) {
+        (Some("pause"), None) => Packet::DaemonDisableRequest(),
+        (Some("resume"), None) => Packet::DaemonEnableRequest(),
+        (Some("status"), None) => Packet::DaemonStatusRequest(),
+        (Some("reapply"), None) => Packet::DaemonReapplyRequest(),
+        (Some("energy"), None) => Packet::DaemonEnergyRequest(),
+        (Some("set-governor"), Some(gov)) => Packet::DaemonSetGovernorRequest(gov.to_string()),
+        (Some("apply-profile"), Some(name)) => Packet::DaemonApplyProfileRequest(name.to_string()),
+        _ => return Err(PacketParseError),
+    };
+
+    if words.next().is_some() {
+        return Err(PacketParseError);
+    }
+    Ok(packet)
+}
+
 impl Display for Packet {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         writeln!(
@@ -74,3 +114,45 @@ fn log_to_daemon(daemon: &Arc<Mutex<Daemon>>, message: &str, severity: logger::S
     let mut daemon = daemon.lock().unwrap();
     daemon.logger.log(message, severity);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_command_unit_test() {
+        assert_eq!(
+            parse_command("pause").unwrap(),
+            Packet::DaemonDisableRequest()
+        );
+        assert_eq!(
+            parse_command("resume").unwrap(),
+            Packet::DaemonEnableRequest()
+        );
+        assert_eq!(
+            parse_command(" status ").unwrap(),
+            Packet::DaemonStatusRequest()
+        );
+        assert_eq!(
+            parse_command("set-governor performance").unwrap(),
+            Packet::DaemonSetGovernorRequest("performance".to_string())
+        );
+        assert_eq!(
+            parse_command("reapply").unwrap(),
+            Packet::DaemonReapplyRequest()
+        );
+        assert_eq!(
+            parse_command("apply-profile quiet").unwrap(),
+            Packet::DaemonApplyProfileRequest("quiet".to_string())
+        );
+        assert!(parse_command("set-governor").is_err());
+        assert!(parse_command("pause now").is_err());
+        assert!(parse_command("reboot").is_err());
+    }
+
+    #[test]
+    fn parse_packet_unit_test() {
+        let packet = Packet::DaemonSetGovernorRequest("powersave".to_string());
+        assert_eq!(parse_packet(packet.to_string().trim()).unwrap(), packet);
+    }
+}
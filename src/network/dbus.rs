@@ -0,0 +1,182 @@
+#![forbid(unsafe_code)]
+//! Serve the daemon on the system bus as `org.autoclockspeed.Daemon` so that desktop power
+//! widgets can show and change the governor
+//!
+//! Only built with the `dbus` feature. The bus only lets the daemon own the name with the policy
+//! from `org.autoclockspeed.Daemon.conf` installed in `/etc/dbus-1/system.d/`.
+use crate::daemon::{Checker, Daemon};
+use crate::error::Error;
+use crate::logger::Interface;
+use crate::network::{log_to_daemon, logger};
+use crate::system::{check_available_governors, check_cpu_freq, get_highest_temp, shared_governor};
+use std::io::ErrorKind;
+use std::sync::{Arc, Mutex};
+use std::{thread, time};
+use zbus::object_server::SignalEmitter;
+use zbus::{blocking, interface, DBusError};
+
+pub const BUS_NAME: &str = "org.autoclockspeed.Daemon";
+pub const OBJECT_PATH: &str = "/org/autoclockspeed/Daemon";
+
+/// How often the governor is checked for GovernorChanged
+const WATCH_INTERVAL: time::Duration = time::Duration::from_secs(1);
+
+/// Errors sent to clients, named e.g. org.autoclockspeed.Daemon.Error.PermissionDenied
+#[derive(Debug, DBusError)]
+#[zbus(prefix = "org.autoclockspeed.Daemon.Error")]
+pub enum DaemonError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    PermissionDenied(String),
+    Unsupported(String),
+    InvalidValue(String),
+    Failed(String),
+}
+
+impl From<Error> for DaemonError {
+    fn from(e: Error) -> DaemonError {
+        let message = format!("{:?}", e).trim_end().to_string();
+        match e {
+            Error::PermissionDenied { .. } => DaemonError::PermissionDenied(message),
+            Error::IO(io) if io.kind() == ErrorKind::PermissionDenied => {
+                DaemonError::PermissionDenied(message)
+            }
+            Error::Unsupported(_) | Error::HdwNotFound => DaemonError::Unsupported(message),
+            Error::InvalidValue(_, _) | Error::OutOfRange(_, _, _) | Error::CoreOffline(_) => {
+                DaemonError::InvalidValue(message)
+            }
+            _ => DaemonError::Failed(message),
+        }
+    }
+}
+
+/// The governor the cpus are on, "mixed" if they differ
+fn current_governor(daemon: &Daemon) -> String {
+    shared_governor(&daemon.cpus)
+}
+
+struct DaemonInterface {
+    daemon: Arc<Mutex<Daemon>>,
+}
+
+#[interface(name = "org.autoclockspeed.Daemon")]
+impl DaemonInterface {
+    /// Switch every core to the governor, kept until the daemon's state changes again
+    fn set_governor(&self, governor: String) -> Result<(), DaemonError> {
+        let available = check_available_governors()?;
+        if !available.contains(&governor) {
+            return Err(Error::InvalidValue(governor, available).into());
+        }
+
+        let mut daemon = self.daemon.lock().unwrap();
+        daemon.set_govs(governor.clone())?;
+        daemon.set_core_govs();
+        daemon.logger.log(
+            &format!("Governor set to {} over D-Bus", governor),
+            logger::Severity::Log,
+        );
+        Ok(())
+    }
+
+    /// The governor, average frequency in MHz, highest temperature in celsius, and if the
+    /// daemon is paused
+    fn get_status(&self) -> (String, f64, f64, bool) {
+        let daemon = self.daemon.lock().unwrap();
+        (
+            current_governor(&daemon),
+            check_cpu_freq(&daemon.cpus) as f64 / 1000.0,
+            get_highest_temp(&daemon.cpus) as f64 / 1000.0,
+            daemon.paused,
+        )
+    }
+
+    /// Sent when the governor of the cpus changes, by the daemon or by a client
+    #[zbus(signal)]
+    async fn governor_changed(emitter: &SignalEmitter<'_>, governor: &str) -> zbus::Result<()>;
+}
+
+/// Send GovernorChanged whenever the governor read by the daemon changes
+fn watch_governor(connection: blocking::Connection, daemon_mutex: Arc<Mutex<Daemon>>) {
+    let mut last = current_governor(&daemon_mutex.lock().unwrap());
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+
+        let governor = current_governor(&daemon_mutex.lock().unwrap());
+        if governor == last {
+            continue;
+        }
+
+        if let Err(e) = connection.emit_signal(
+            None::<&str>,
+            OBJECT_PATH,
+            BUS_NAME,
+            "GovernorChanged",
+            &governor,
+        ) {
+            log_to_daemon(
+                &daemon_mutex,
+                &format!("Could not send GovernorChanged: {}", e),
+                logger::Severity::Warning,
+            );
+        }
+        last = governor;
+    }
+}
+
+/// Take the name on the system bus and serve the interface in its own thread
+pub fn serve(c_daemon_mutex: Arc<Mutex<Daemon>>) {
+    thread::spawn(move || {
+        let interface = DaemonInterface {
+            daemon: Arc::clone(&c_daemon_mutex),
+        };
+
+        let connection = blocking::connection::Builder::system()
+            .and_then(|b| b.name(BUS_NAME))
+            .and_then(|b| b.serve_at(OBJECT_PATH, interface))
+            .and_then(|b| b.build());
+
+        match connection {
+            Ok(connection) => {
+                log_to_daemon(
+                    &c_daemon_mutex,
+                    &format!("Serving {} on the system bus", BUS_NAME),
+                    logger::Severity::Log,
+                );
+                // The connection handles calls in its own threads as long as it is kept around
+                watch_governor(connection, c_daemon_mutex);
+            }
+            Err(e) => log_to_daemon(
+                &c_daemon_mutex,
+                &format!("Could not serve {} on the system bus: {}", BUS_NAME, e),
+                logger::Severity::Error,
+            ),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn daemon_error_from_error_unit_test() {
+        let denied = Error::IO(io::Error::new(ErrorKind::PermissionDenied, "denied"));
+        assert!(matches!(
+            DaemonError::from(denied),
+            DaemonError::PermissionDenied(_)
+        ));
+        assert!(matches!(
+            DaemonError::from(Error::Unsupported("Turbo".to_string())),
+            DaemonError::Unsupported(_)
+        ));
+        assert!(matches!(
+            DaemonError::from(Error::InvalidValue("fast".to_string(), vec![])),
+            DaemonError::InvalidValue(_)
+        ));
+        assert!(matches!(
+            DaemonError::from(Error::Parse),
+            DaemonError::Failed(_)
+        ));
+    }
+}
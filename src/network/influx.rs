@@ -0,0 +1,291 @@
+#![forbid(unsafe_code)]
+//! Push the daemon's readings to InfluxDB, or Telegraf, in line protocol over HTTP
+//!
+//! The daemon hands every reading to a thread over a channel so a slow or unreachable server
+//! never holds up the polling loop. The readings are sent together once every interval, when
+//! a push fails it gets logged and the readings are sent again with the next one.
+use crate::config::InfluxConfig;
+use crate::cpu::CPU;
+use crate::error::Error;
+use crate::network::{log_to_daemon, logger, Daemon};
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Readings kept while the server can not be reached, the oldest get dropped after this
+pub const MAX_BUFFERED_READINGS: usize = 1000;
+
+/// How long to wait on the server before a push counts as failed
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The lines for one reading of every core, the timestamp is in nanoseconds
+pub fn line_protocol(cpus: &[CPU], timestamp_ns: u128) -> String {
+    let mut out = String::new();
+    for cpu in cpus {
+        writeln!(
+            out,
+            "acs,core={} freq={}i,temp={},usage={} {}",
+            cpu.number,
+            cpu.cur_freq / 1000,
+            cpu.cur_temp as f64 / 1000.0,
+            cpu.cur_usage,
+            timestamp_ns
+        )
+        .ok();
+    }
+    out
+}
+
+/// Escape a query parameter, anything other than letters, digits and `-_.~` is percent encoded
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Where the readings get posted to
+#[derive(Debug, PartialEq, Eq)]
+pub struct Endpoint {
+    pub host: String,
+    pub port: u16,
+    /// Path and query of the write endpoint
+    pub path: String,
+    pub token: Option<String>,
+}
+
+impl Endpoint {
+    /// Work out the write endpoint, InfluxDB 2 when an org is set and InfluxDB 1 otherwise
+    ///
+    /// Only plain http is supported, for a server behind https push to a local Telegraf
+    pub fn from_config(config: &InfluxConfig) -> Result<Endpoint, Error> {
+        let invalid = || Error::InvalidConfig(format!("influx.url \"{}\"", config.url));
+
+        let rest = config.url.strip_prefix("http://").ok_or_else(invalid)?;
+        let (authority, base) = match rest.find('/') {
+            Some(i) => (&rest[..i], rest[i..].trim_end_matches('/')),
+            None => (rest, ""),
+        };
+
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse::<u16>().map_err(|_| invalid())?),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+
+        let path = match &config.org {
+            Some(org) => format!(
+                "{}/api/v2/write?org={}&bucket={}&precision=ns",
+                base,
+                encode(org),
+                encode(&config.bucket)
+            ),
+            None => format!("{}/write?db={}&precision=ns", base, encode(&config.bucket)),
+        };
+
+        Ok(Endpoint {
+            host: host.to_string(),
+            port,
+            path,
+            token: config.token.clone(),
+        })
+    }
+}
+
+/// Post the lines, anything other than a 2xx answer is an error
+pub fn post(endpoint: &Endpoint, body: &str) -> Result<(), Error> {
+    let address = (endpoint.host.as_str(), endpoint.port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address for host"))?;
+
+    let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let auth = match &endpoint.token {
+        Some(token) => format!("Authorization: Token {}\r\n", token),
+        None => String::new(),
+    };
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}:{}\r\n{}Content-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        endpoint.path,
+        endpoint.host,
+        endpoint.port,
+        auth,
+        body.len(),
+        body
+    );
+    // In one write so the server does not get the request in pieces
+    stream.write_all(request.as_bytes())?;
+    stream.flush()?;
+
+    let mut status = String::new();
+    BufReader::new(&stream).read_line(&mut status)?;
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(Error::IO(io::Error::other(format!(
+            "server answered \"{}\"",
+            status.trim_end()
+        )))),
+    }
+}
+
+/// Start the thread that pushes the readings sent to it
+pub fn start(
+    config: InfluxConfig,
+    c_daemon_mutex: Arc<Mutex<Daemon>>,
+) -> Result<Sender<String>, Error> {
+    let endpoint = Endpoint::from_config(&config)?;
+    let interval = Duration::from_secs(config.interval_secs.max(1));
+    let (sender, receiver) = channel::<String>();
+
+    thread::spawn(move || {
+        let mut batch: Vec<String> = Vec::new();
+        let mut failing = false;
+
+        loop {
+            let deadline = Instant::now() + interval;
+            loop {
+                let left = deadline.saturating_duration_since(Instant::now());
+                match receiver.recv_timeout(left) {
+                    Ok(reading) => batch.push(reading),
+                    Err(RecvTimeoutError::Timeout) => break,
+                    // The daemon is gone
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+
+            if batch.is_empty() {
+                continue;
+            }
+
+            match post(&endpoint, &batch.concat()) {
+                Ok(_) => {
+                    if failing {
+                        log_to_daemon(
+                            &c_daemon_mutex,
+                            "Pushing to InfluxDB works again",
+                            logger::Severity::Log,
+                        );
+                    }
+                    failing = false;
+                    batch.clear();
+                }
+                Err(e) => {
+                    // Only the first failure is logged so a server that is down does not fill
+                    // the log every interval
+                    if !failing {
+                        log_to_daemon(
+                            &c_daemon_mutex,
+                            &format!("Could not push to InfluxDB: {:?}", e),
+                            logger::Severity::Warning,
+                        );
+                    }
+                    failing = true;
+                    if batch.len() > MAX_BUFFERED_READINGS {
+                        batch.drain(..batch.len() - MAX_BUFFERED_READINGS);
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(sender)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(url: &str, org: Option<&str>) -> InfluxConfig {
+        InfluxConfig {
+            url: url.to_string(),
+            bucket: "acs metrics".to_string(),
+            org: org.map(|o| o.to_string()),
+            token: None,
+            interval_secs: 10,
+        }
+    }
+
+    #[test]
+    fn line_protocol_unit_test() {
+        let cpu = CPU {
+            name: "cpu1".to_string(),
+            number: 1,
+            max_freq: 4000000,
+            min_freq: 800000,
+            cur_freq: 2400000,
+            cur_temp: 45500,
+            cur_usage: 0.25,
+            gov: "powersave".to_string(),
+        };
+        assert_eq!(
+            line_protocol(&[cpu], 1700000000000000000),
+            "acs,core=1 freq=2400i,temp=45.5,usage=0.25 1700000000000000000\n"
+        );
+    }
+
+    #[test]
+    fn post_unit_test() {
+        use std::io::Read;
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let mut answers = vec![
+                "HTTP/1.1 500 Internal Server Error",
+                "HTTP/1.1 204 No Content",
+            ];
+            let mut requests = Vec::new();
+            for stream in listener.incoming().take(2) {
+                let mut stream = stream.unwrap();
+                let mut request = vec![0; 1024];
+                let n = stream.read(&mut request).unwrap();
+                requests.push(String::from_utf8_lossy(&request[..n]).to_string());
+                write!(stream, "{}\r\nContent-Length: 0\r\n\r\n", answers.remove(0)).unwrap();
+            }
+            requests
+        });
+
+        let endpoint =
+            Endpoint::from_config(&config(&format!("http://127.0.0.1:{}", port), None)).unwrap();
+        assert!(post(&endpoint, "acs,core=0 freq=800i 1\n").is_err());
+        post(&endpoint, "acs,core=0 freq=800i 1\n").unwrap();
+
+        let requests = server.join().unwrap();
+        assert!(requests[1].starts_with("POST /write?db=acs%20metrics&precision=ns HTTP/1.1\r\n"));
+        assert!(requests[1].ends_with("\r\n\r\nacs,core=0 freq=800i 1\n"));
+    }
+
+    #[test]
+    fn endpoint_from_config_unit_test() {
+        let v1 = Endpoint::from_config(&config("http://localhost:8086", None)).unwrap();
+        assert_eq!(v1.host, "localhost");
+        assert_eq!(v1.port, 8086);
+        assert_eq!(v1.path, "/write?db=acs%20metrics&precision=ns");
+
+        let v2 = Endpoint::from_config(&config("http://influx/base/", Some("home"))).unwrap();
+        assert_eq!(v2.port, 80);
+        assert_eq!(
+            v2.path,
+            "/base/api/v2/write?org=home&bucket=acs%20metrics&precision=ns"
+        );
+
+        assert!(Endpoint::from_config(&config("https://influx", None)).is_err());
+        assert!(Endpoint::from_config(&config("http://influx:port", None)).is_err());
+        assert!(Endpoint::from_config(&config("http://:8086", None)).is_err());
+    }
+}
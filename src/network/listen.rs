@@ -1,24 +1,42 @@
 #![forbid(unsafe_code)]
+use crate::daemon::Checker;
 use crate::logger;
 use crate::logger::Interface;
 use crate::network::log_to_daemon;
+use crate::network::parse_command;
 use crate::network::parse_packet;
 use crate::network::BufWriter;
 use crate::network::Daemon;
 use crate::network::Packet;
 use crate::network::UnixListener;
 use crate::write_packet;
+use nix::sys::socket::{getsockopt, sockopt::PeerCredentials};
+use nix::unistd::getuid;
 use std::io::{BufRead, BufReader, Write};
 use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::AsRawFd;
 use std::os::unix::net::UnixStream;
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::thread;
 
 pub fn listen(path: &'static str, c_daemon_mutex: Arc<Mutex<Daemon>>) {
     thread::spawn(move || {
-        // Get rid of the old sock
-        std::fs::remove_file(path).ok();
+        if Path::new(path).exists() {
+            // A sock that accepts connections belongs to a daemon that is still running
+            if UnixStream::connect(path).is_ok() {
+                log_to_daemon(
+                    &c_daemon_mutex,
+                    &format!("Another daemon is already listening on {}", path),
+                    logger::Severity::Error,
+                );
+                return;
+            }
+
+            // Get rid of the stale sock left by a daemon that did not exit cleanly
+            std::fs::remove_file(path).ok();
+        }
 
         // Try to handle sock connections then
         let listener = match UnixListener::bind(path) {
@@ -33,15 +51,23 @@ pub fn listen(path: &'static str, c_daemon_mutex: Arc<Mutex<Daemon>>) {
             }
         };
 
-        // Set the permissions on the sock
-        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o777)).ok();
+        // Only the user running the daemon (and root) can connect to the sock
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).ok();
 
         // Spawn a new thread to listen for commands
         thread::spawn(move || {
             for stream in listener.incoming() {
                 match stream {
                     Ok(stream) => {
-                        handle_stream(stream, &c_daemon_mutex);
+                        if client_allowed(&stream) {
+                            handle_stream(stream, &c_daemon_mutex);
+                        } else {
+                            log_to_daemon(
+                                &c_daemon_mutex,
+                                "Refused connection from a user that does not own the daemon",
+                                logger::Severity::Warning,
+                            );
+                        }
                     }
                     Err(err) => {
                         log_to_daemon(
@@ -57,6 +83,17 @@ pub fn listen(path: &'static str, c_daemon_mutex: Arc<Mutex<Daemon>>) {
     });
 }
 
+/// Check that the client is root or the same user as the daemon
+///
+/// The permissions on the sock already stop other users, this also covers a sock whose
+/// permissions were changed after it was made
+fn client_allowed(stream: &UnixStream) -> bool {
+    match getsockopt(stream.as_raw_fd(), PeerCredentials) {
+        Ok(cred) => cred.uid() == 0 || cred.uid() == getuid().as_raw(),
+        Err(_) => false,
+    }
+}
+
 pub fn handle_stream(stream: UnixStream, c_daemon_mutex: &Arc<Mutex<Daemon>>) {
     // We don't need to log ALL the time
     // log_to_daemon(c_daemon_mutex, "Received connection", logger::Severity::Log);
@@ -82,7 +119,7 @@ pub fn handle_stream(stream: UnixStream, c_daemon_mutex: &Arc<Mutex<Daemon>>) {
                     }
                 },
             };
-            let packet = match parse_packet(&actual_line) {
+            let packet = match parse_packet(&actual_line).or_else(|_| parse_command(&actual_line)) {
                 Ok(p) => p,
                 Err(e) => {
                     log_to_daemon(
@@ -149,6 +186,60 @@ pub fn handle_stream(stream: UnixStream, c_daemon_mutex: &Arc<Mutex<Daemon>>) {
                     write_packet!(writer, response);
                 }
                 Packet::DaemonStatusResponse(_) => {}
+                Packet::DaemonSetGovernorRequest(gov) => {
+                    let mut inner_daemon = inner_daemon_mutex.lock().unwrap();
+                    // The governor is kept until the daemon's state changes again
+                    let response = match inner_daemon.set_govs(gov.clone()) {
+                        Ok(_) => {
+                            inner_daemon.set_core_govs();
+                            inner_daemon.logger.log(
+                                &format!("Governor set to {} by a client", gov),
+                                logger::Severity::Log,
+                            );
+                            Packet::DaemonSetGovernorResponse(Ok(()))
+                        }
+                        Err(e) => Packet::DaemonSetGovernorResponse(Err(format!("{:?}", e))),
+                    };
+                    let mut writer = BufWriter::new(&stream);
+                    write_packet!(writer, response);
+                }
+                Packet::DaemonSetGovernorResponse(_) => {}
+                Packet::DaemonReapplyRequest() => {
+                    let mut inner_daemon = inner_daemon_mutex.lock().unwrap();
+                    let response = match inner_daemon.reapply("a client asked for it") {
+                        Ok(_) => Packet::DaemonReapplyResponse(Ok(())),
+                        Err(e) => Packet::DaemonReapplyResponse(Err(format!("{:?}", e))),
+                    };
+                    let mut writer = BufWriter::new(&stream);
+                    write_packet!(writer, response);
+                }
+                Packet::DaemonReapplyResponse(_) => {}
+                Packet::DaemonApplyProfileRequest(name) => {
+                    let mut inner_daemon = inner_daemon_mutex.lock().unwrap();
+                    let response = match inner_daemon.apply_named_profile(&name) {
+                        Ok(_) => {
+                            inner_daemon.logger.log(
+                                &format!("Applied profile '{}' for a client", name),
+                                logger::Severity::Log,
+                            );
+                            Packet::DaemonApplyProfileResponse(Ok(()))
+                        }
+                        Err(e) => Packet::DaemonApplyProfileResponse(Err(format!("{:?}", e))),
+                    };
+                    let mut writer = BufWriter::new(&stream);
+                    write_packet!(writer, response);
+                }
+                Packet::DaemonApplyProfileResponse(_) => {}
+                Packet::DaemonEnergyRequest() => {
+                    let inner_daemon = inner_daemon_mutex.lock().unwrap();
+                    let response = Packet::DaemonEnergyResponse(
+                        inner_daemon.session_energy_uj,
+                        inner_daemon.started.elapsed().as_secs(),
+                    );
+                    let mut writer = BufWriter::new(&stream);
+                    write_packet!(writer, response);
+                }
+                Packet::DaemonEnergyResponse(_, _) => {}
                 Packet::DaemonLogRequest() => {}
                 Packet::DaemonLogResponse(_) => {}
                 Packet::DaemonLogEvent(_) => {}
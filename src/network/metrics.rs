@@ -0,0 +1,198 @@
+#![forbid(unsafe_code)]
+//! Serve the daemon's readings as Prometheus metrics over HTTP
+//!
+//! Only built with the `metrics` feature so that a listening port is never opened unless asked
+//! for. The daemon starts it when `metrics_port` is set in the config.
+use crate::cpu::CPU;
+use crate::network::{log_to_daemon, logger, Daemon};
+use crate::system::{capabilities, check_cpu_freq, check_turbo_enabled};
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How long a client has to send its request and read the reply, clients are served in turn
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The values that get exported, copied out of the daemon so the lock is held briefly
+pub struct Metrics {
+    pub cpus: Vec<CPU>,
+    pub turbo: Option<bool>,
+    pub battery: Option<i8>,
+}
+
+impl Metrics {
+    fn from_daemon(daemon: &Daemon) -> Metrics {
+        Metrics {
+            cpus: daemon.cpus.clone(),
+            turbo: capabilities()
+                .turbo
+                .then(|| check_turbo_enabled().ok())
+                .flatten(),
+            battery: if daemon.do_update_battery {
+                Some(daemon.battery.capacity)
+            } else {
+                None
+            },
+        }
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str) {
+    writeln!(out, "# HELP {} {}", name, help).ok();
+    writeln!(out, "# TYPE {} gauge", name).ok();
+}
+
+/// Render the metrics in the Prometheus text format
+pub fn render_metrics(metrics: &Metrics) -> String {
+    let mut out = String::new();
+
+    header(
+        &mut out,
+        "acs_cpu_frequency_hertz",
+        "Current frequency of each core",
+    );
+    for cpu in &metrics.cpus {
+        writeln!(
+            out,
+            "acs_cpu_frequency_hertz{{cpu=\"{}\"}} {}",
+            cpu.name,
+            cpu.cur_freq as i64 * 1000
+        )
+        .ok();
+    }
+
+    header(
+        &mut out,
+        "acs_average_frequency_hertz",
+        "Average frequency of every core",
+    );
+    writeln!(
+        out,
+        "acs_average_frequency_hertz {}",
+        check_cpu_freq(&metrics.cpus) as f64 * 1000.0
+    )
+    .ok();
+
+    header(
+        &mut out,
+        "acs_cpu_temperature_celsius",
+        "Temperature of each core",
+    );
+    for cpu in &metrics.cpus {
+        writeln!(
+            out,
+            "acs_cpu_temperature_celsius{{cpu=\"{}\"}} {}",
+            cpu.name,
+            cpu.cur_temp as f64 / 1000.0
+        )
+        .ok();
+    }
+
+    header(
+        &mut out,
+        "acs_cpu_governor",
+        "Governor in use by each core, always 1",
+    );
+    for cpu in &metrics.cpus {
+        writeln!(
+            out,
+            "acs_cpu_governor{{cpu=\"{}\",governor=\"{}\"}} 1",
+            cpu.name, cpu.gov
+        )
+        .ok();
+    }
+
+    if let Some(turbo) = metrics.turbo {
+        header(&mut out, "acs_turbo_enabled", "1 if turbo is enabled");
+        writeln!(out, "acs_turbo_enabled {}", turbo as u8).ok();
+    }
+
+    if let Some(battery) = metrics.battery {
+        header(&mut out, "acs_battery_percent", "Battery charge in percent");
+        writeln!(out, "acs_battery_percent {}", battery).ok();
+    }
+
+    out
+}
+
+fn handle_client(mut stream: TcpStream, daemon: &Arc<Mutex<Daemon>>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut request = String::new();
+    BufReader::new(&stream).read_line(&mut request)?;
+
+    let (status, body) = if request.starts_with("GET /metrics ") {
+        let metrics = Metrics::from_daemon(&daemon.lock().unwrap());
+        ("200 OK", render_metrics(&metrics))
+    } else {
+        (
+            "404 Not Found",
+            String::from("Metrics are served at /metrics\n"),
+        )
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// Start serving metrics on the port in its own thread
+pub fn serve(port: u16, c_daemon_mutex: Arc<Mutex<Daemon>>) {
+    thread::spawn(move || {
+        let listener = match TcpListener::bind(("0.0.0.0", port)) {
+            Ok(listener) => listener,
+            Err(e) => {
+                log_to_daemon(
+                    &c_daemon_mutex,
+                    &format!("Failed to serve metrics on port {}: {}", port, e),
+                    logger::Severity::Error,
+                );
+                return;
+            }
+        };
+
+        for stream in listener.incoming().flatten() {
+            // A client that fails only affects its own request
+            handle_client(stream, &c_daemon_mutex).ok();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_metrics_unit_test() {
+        let cpu = CPU {
+            name: "cpu0".to_string(),
+            number: 0,
+            max_freq: 4000000,
+            min_freq: 800000,
+            cur_freq: 2000000,
+            cur_temp: 45000,
+            cur_usage: 0.0,
+            gov: "powersave".to_string(),
+        };
+        let metrics = Metrics {
+            cpus: vec![cpu],
+            turbo: Some(true),
+            battery: None,
+        };
+
+        let out = render_metrics(&metrics);
+        assert!(out.contains("acs_cpu_frequency_hertz{cpu=\"cpu0\"} 2000000000\n"));
+        assert!(out.contains("acs_cpu_temperature_celsius{cpu=\"cpu0\"} 45\n"));
+        assert!(out.contains("acs_cpu_governor{cpu=\"cpu0\",governor=\"powersave\"} 1\n"));
+        assert!(out.contains("acs_turbo_enabled 1\n"));
+        assert!(!out.contains("acs_battery_percent"));
+    }
+}
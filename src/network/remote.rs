@@ -0,0 +1,131 @@
+#![forbid(unsafe_code)]
+//! Read only status of the daemon over TCP, for looking at another machine with `acs remote-status`
+//!
+//! A client that connects gets the same status as `acs get status --format json` on one line and
+//! the connection is closed, nothing sent by the client is read, so it can not change anything.
+//! The server is only built with the `remote` feature so that a listening port is never opened
+//! unless asked for. The daemon starts it when `remote_status_port` is set in the config, bound
+//! to `remote_status_bind`, which is localhost unless set otherwise.
+use crate::display::Status;
+use crate::error::Error;
+use crate::gpu::read_gpu_clocks;
+use crate::network::Daemon;
+#[cfg(feature = "remote")]
+use crate::network::{log_to_daemon, logger};
+use crate::system::{check_cpu_freq, check_cpu_temperature, list_packages, shared_governor};
+#[cfg(feature = "remote")]
+use std::io::Write;
+use std::io::{BufRead, BufReader};
+#[cfg(feature = "remote")]
+use std::net::TcpListener;
+use std::net::{TcpStream, ToSocketAddrs};
+#[cfg(feature = "remote")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "remote")]
+use std::thread;
+use std::time::Duration;
+
+/// How long the client waits on the server
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The status from the daemon's last readings
+pub fn status_from_daemon(daemon: &Daemon) -> Status {
+    Status {
+        avg_freq_mhz: check_cpu_freq(&daemon.cpus) / 1000.0,
+        temp_c: check_cpu_temperature(&daemon.cpus) / 1000.0,
+        governor: shared_governor(&daemon.cpus),
+        packages: list_packages(&daemon.cpus),
+        gpus: read_gpu_clocks(&daemon.gpus),
+        battery_percent: daemon.do_update_battery.then_some(daemon.battery.capacity),
+        battery_time: daemon.battery.time_estimate,
+        cores: daemon.cpus.clone(),
+        throttling: daemon.throttling.clone(),
+    }
+}
+
+pub fn parse_status(line: &str) -> Result<Status, Error> {
+    serde_json::from_str(line).map_err(|_| Error::Parse)
+}
+
+/// Connect to a daemon serving its status, e.g. on "nas.local:7171"
+pub fn fetch_status(address: &str) -> Result<Status, Error> {
+    let address = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or(Error::InvalidValue(
+            address.to_string(),
+            vec!["host:port".to_string()],
+        ))?;
+    let stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    parse_status(&line)
+}
+
+#[cfg(feature = "remote")]
+fn handle_client(mut stream: TcpStream, daemon: &Arc<Mutex<Daemon>>) -> std::io::Result<()> {
+    let status = status_from_daemon(&daemon.lock().unwrap());
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    writeln!(stream, "{}", serde_json::json!(status))?;
+    stream.flush()
+}
+
+/// Start serving the status on the address and port in its own thread
+#[cfg(feature = "remote")]
+pub fn serve(bind: String, port: u16, c_daemon_mutex: Arc<Mutex<Daemon>>) {
+    thread::spawn(move || {
+        let listener = match TcpListener::bind((bind.as_str(), port)) {
+            Ok(listener) => listener,
+            Err(e) => {
+                log_to_daemon(
+                    &c_daemon_mutex,
+                    &format!("Failed to serve the status on {}:{}: {}", bind, port, e),
+                    logger::Severity::Error,
+                );
+                return;
+            }
+        };
+
+        for stream in listener.incoming().flatten() {
+            // A client that fails only affects its own connection
+            handle_client(stream, &c_daemon_mutex).ok();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::CPU;
+
+    #[test]
+    fn parse_status_unit_test() -> Result<(), Error> {
+        let cpu = CPU {
+            name: "cpu0".to_string(),
+            gov: "powersave".to_string(),
+            cur_freq: 2_000_000,
+            ..CPU::default()
+        };
+        let status = Status {
+            avg_freq_mhz: 2000.0,
+            governor: "powersave".to_string(),
+            temp_c: 45.0,
+            packages: Vec::new(),
+            gpus: Vec::new(),
+            battery_percent: Some(80),
+            battery_time: None,
+            cores: vec![cpu],
+            throttling: vec!["cpu0".to_string()],
+        };
+
+        let parsed = parse_status(&serde_json::json!(status).to_string())?;
+        assert_eq!(parsed.avg_freq_mhz, 2000.0);
+        assert_eq!(parsed.cores[0].cur_freq, 2_000_000);
+        assert_eq!(parsed.battery_percent, Some(80));
+        assert_eq!(parsed.throttling, vec!["cpu0".to_string()]);
+        assert!(parse_status("not json\n").is_err());
+        Ok(())
+    }
+}
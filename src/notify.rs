@@ -0,0 +1,215 @@
+#![forbid(unsafe_code)]
+//! Desktop notifications for thermal events
+//!
+//! Notifications are sent with `notify-send` when acs is built with the `notify` feature. A
+//! daemon running as root, e.g. under systemd, has no session bus of its own, so it sends them as
+//! the desktop user on that user's bus in `/run/user`. When there is no session bus for a
+//! notification daemon to listen on, like on a headless server, nothing gets sent.
+use crate::error::Error;
+#[cfg(feature = "notify")]
+use std::path::Path;
+
+/// The runtime directory of each logged in user, e.g. /run/user/1000 with the session bus in it
+#[cfg(feature = "notify")]
+const RUNTIME_DIR: &str = "/run/user";
+
+/// Degrees under the threshold the cpu has to get to before it counts as cooled down, so a
+/// temperature hovering around the threshold does not send a notification every cycle
+pub const COOL_DOWN_MARGIN: i8 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ThermalEvent {
+    /// The temperature went over the threshold
+    Hot,
+    /// The temperature went back under the threshold by the margin
+    Cooled,
+}
+
+impl ThermalEvent {
+    pub fn summary(&self) -> &'static str {
+        match self {
+            ThermalEvent::Hot => "CPU is overheating",
+            ThermalEvent::Cooled => "CPU cooled down",
+        }
+    }
+
+    pub fn body(&self, temp: i8, threshold: i8) -> String {
+        match self {
+            ThermalEvent::Hot => format!("{}C is over the threshold of {}C", temp, threshold),
+            ThermalEvent::Cooled => format!("Back down to {}C", temp),
+        }
+    }
+}
+
+/// Check if the temperature crossed the threshold, `hot` is if the last event was `Hot`
+pub fn thermal_event(hot: bool, temp: i8, threshold: i8) -> Option<ThermalEvent> {
+    if !hot && temp > threshold {
+        Some(ThermalEvent::Hot)
+    } else if hot && (temp as i16) <= threshold as i16 - COOL_DOWN_MARGIN as i16 {
+        Some(ThermalEvent::Cooled)
+    } else {
+        None
+    }
+}
+
+/// The session bus a notification goes to
+#[cfg(feature = "notify")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SessionBus {
+    /// The one in DBUS_SESSION_BUS_ADDRESS
+    Own,
+    /// The bus of a user in the runtime directory, notify-send runs as that user
+    User { uid: u32, gid: u32 },
+}
+
+/// Find the session bus in the runtime directory for acs running as own_uid
+///
+/// The bus of own_uid is used when it has one. Root, which never has one, uses the desktop user's,
+/// the lowest uid other than root with a bus. Other users can only reach their own.
+#[cfg(feature = "notify")]
+pub fn find_session_bus(runtime_dir: &Path, own_uid: u32, from_env: bool) -> Option<SessionBus> {
+    use std::os::unix::fs::MetadataExt;
+
+    if from_env {
+        return Some(SessionBus::Own);
+    }
+
+    let user_bus = |uid: u32| {
+        let dir = runtime_dir.join(uid.to_string());
+        let gid = dir.metadata().ok()?.gid();
+        dir.join("bus")
+            .exists()
+            .then_some(SessionBus::User { uid, gid })
+    };
+    if let Some(bus) = user_bus(own_uid) {
+        return Some(bus);
+    }
+    if own_uid != 0 {
+        return None;
+    }
+
+    let mut uids: Vec<u32> = std::fs::read_dir(runtime_dir)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+        .filter(|&uid| uid != 0)
+        .collect();
+    uids.sort_unstable();
+    uids.into_iter().find_map(user_bus)
+}
+
+#[cfg(feature = "notify")]
+fn session_bus() -> Option<SessionBus> {
+    find_session_bus(
+        Path::new(RUNTIME_DIR),
+        nix::unistd::getuid().as_raw(),
+        std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_some(),
+    )
+}
+
+/// Check if there is a session bus to send notifications on and `notify-send` is installed
+#[cfg(feature = "notify")]
+pub fn available() -> bool {
+    use std::env;
+
+    let has_bus = session_bus().is_some();
+
+    let has_notify_send = env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).any(|dir| dir.join("notify-send").is_file()))
+        .unwrap_or(false);
+
+    has_bus && has_notify_send
+}
+
+#[cfg(not(feature = "notify"))]
+pub fn available() -> bool {
+    false
+}
+
+/// Send a notification without waiting for it to be shown
+#[cfg(feature = "notify")]
+pub fn send(summary: &str, body: &str) -> Result<(), Error> {
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+    use std::thread;
+
+    let mut command = Command::new("notify-send");
+    if let Some(SessionBus::User { uid, gid }) = session_bus() {
+        let runtime = format!("{}/{}", RUNTIME_DIR, uid);
+        command
+            .uid(uid)
+            .gid(gid)
+            .env(
+                "DBUS_SESSION_BUS_ADDRESS",
+                format!("unix:path={}/bus", runtime),
+            )
+            .env("XDG_RUNTIME_DIR", runtime);
+    }
+    let mut child = command
+        .arg("--app-name=acs")
+        .arg(summary)
+        .arg(body)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    // Collect the exit status so the process does not stay around as a zombie
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(not(feature = "notify"))]
+pub fn send(_summary: &str, _body: &str) -> Result<(), Error> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thermal_event_unit_test() {
+        assert_eq!(thermal_event(false, 85, 80), Some(ThermalEvent::Hot));
+        assert_eq!(thermal_event(false, 80, 80), None);
+
+        // Stays hot until it is under the threshold by the margin
+        assert_eq!(thermal_event(true, 90, 80), None);
+        assert_eq!(thermal_event(true, 78, 80), None);
+        assert_eq!(thermal_event(true, 75, 80), Some(ThermalEvent::Cooled));
+    }
+
+    #[cfg(feature = "notify")]
+    #[test]
+    fn find_session_bus_unit_test() -> Result<(), Error> {
+        use crate::test_dir::TestDir;
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = TestDir::new("notify");
+        for (uid, bus) in [
+            ("1001", true),
+            ("1000", true),
+            ("999", false),
+            ("gdm", true),
+        ] {
+            std::fs::create_dir_all(dir.join(uid))?;
+            if bus {
+                std::fs::write(dir.join(uid).join("bus"), "")?;
+            }
+        }
+        let gid = dir.join("1000").metadata()?.gid();
+
+        let found = (
+            find_session_bus(&dir, 0, true),
+            find_session_bus(&dir, 0, false),
+            find_session_bus(&dir, 1001, false),
+            find_session_bus(&dir, 999, false),
+        );
+
+        assert_eq!(found.0, Some(SessionBus::Own));
+        // Root goes to the lowest user with a bus
+        assert_eq!(found.1, Some(SessionBus::User { uid: 1000, gid }));
+        assert_eq!(found.2, Some(SessionBus::User { uid: 1001, gid }));
+        assert_eq!(found.3, None);
+        Ok(())
+    }
+}
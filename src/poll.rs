@@ -0,0 +1,114 @@
+#![forbid(unsafe_code)]
+//! Adaptive polling for the daemon loop
+//!
+//! While the readings stay about the same the time between polls grows by `BACKOFF` each cycle
+//! until it reaches the maximum. A big change in frequency, temperature, or load drops it back to
+//! the minimum right away so the daemon reacts quickly to sudden load.
+use std::time::Duration;
+
+/// How much the interval grows each cycle that nothing changed
+const BACKOFF: f64 = 1.5;
+
+/// Relative change in average frequency that counts as rapid, 0.1 is 10%
+const FREQ_CHANGE: f32 = 0.1;
+/// Change in the highest temperature in degrees celsius that counts as rapid
+const TEMP_CHANGE: i8 = 3;
+/// Change in cpu usage in percentage points that counts as rapid
+const USAGE_CHANGE: f32 = 10.0;
+
+/// The readings compared between polls
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PollSample {
+    /// Average frequency in kHz
+    pub freq: f32,
+    /// Highest temperature in celsius
+    pub temp: i8,
+    /// Cpu usage in percent
+    pub usage: f32,
+}
+
+impl PollSample {
+    /// Check if the readings moved enough since the last sample to poll quickly again
+    pub fn changed_from(&self, last: &PollSample) -> bool {
+        let freq_change = if last.freq > 0.0 {
+            (self.freq - last.freq).abs() / last.freq
+        } else {
+            0.0
+        };
+
+        freq_change >= FREQ_CHANGE
+            || (self.temp as i16 - last.temp as i16).abs() >= TEMP_CHANGE as i16
+            || (self.usage - last.usage).abs() >= USAGE_CHANGE
+    }
+}
+
+/// Keeps track of the interval between polls
+#[derive(Clone, Debug)]
+pub struct AdaptivePoll {
+    pub min: Duration,
+    pub max: Duration,
+    current: Duration,
+    last: Option<PollSample>,
+}
+
+impl AdaptivePoll {
+    pub fn new(min: Duration, max: Duration) -> AdaptivePoll {
+        // A max under the min would make the interval shrink when backing off
+        let max = max.max(min);
+        AdaptivePoll {
+            min,
+            max,
+            current: min,
+            last: None,
+        }
+    }
+
+    /// Give the next interval based on how much the readings changed since the last poll
+    pub fn next(&mut self, sample: PollSample) -> Duration {
+        self.current = match self.last {
+            Some(last) if !sample.changed_from(&last) => {
+                self.current.mul_f64(BACKOFF).min(self.max)
+            }
+            _ => self.min,
+        };
+        self.last = Some(sample);
+        self.current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(freq: f32, temp: i8, usage: f32) -> PollSample {
+        PollSample { freq, temp, usage }
+    }
+
+    #[test]
+    fn changed_from_unit_test() {
+        let last = sample(2_000_000.0, 50, 20.0);
+        assert!(!sample(2_100_000.0, 51, 25.0).changed_from(&last));
+        assert!(sample(2_400_000.0, 50, 20.0).changed_from(&last));
+        assert!(sample(2_000_000.0, 47, 20.0).changed_from(&last));
+        assert!(sample(2_000_000.0, 50, 35.0).changed_from(&last));
+    }
+
+    #[test]
+    fn adaptive_poll_backoff_unit_test() {
+        let mut poll = AdaptivePoll::new(Duration::from_millis(200), Duration::from_millis(500));
+        let stable = sample(2_000_000.0, 50, 20.0);
+
+        // The first poll has nothing to compare to
+        assert_eq!(poll.next(stable), Duration::from_millis(200));
+        assert_eq!(poll.next(stable), Duration::from_millis(300));
+        assert_eq!(poll.next(stable), Duration::from_millis(450));
+        assert_eq!(poll.next(stable), Duration::from_millis(500));
+        assert_eq!(poll.next(stable), Duration::from_millis(500));
+
+        // Sudden load goes straight back to the minimum
+        assert_eq!(
+            poll.next(sample(3_500_000.0, 50, 90.0)),
+            Duration::from_millis(200)
+        );
+    }
+}
@@ -6,6 +6,7 @@ use crate::error::Error;
 
 pub mod battery;
 pub mod lid;
+pub mod rapl;
 
 /// Called once at the start of read_power_source
 /// Discover the path to the AC power_supply
@@ -19,14 +20,9 @@ pub fn set_best_path() -> Option<&'static str> {
     ];
 
     // Find if any AC power path exists
-    for path in POWER_SOURCE_PATH {
-        if Path::new(path).exists() {
-            // Mutate Power struct and leave
-            return Some(path);
-        }
-    }
-
-    None
+    POWER_SOURCE_PATH
+        .into_iter()
+        .find(|&path| Path::new(path).exists())
 }
 
 pub struct Power {
@@ -1,13 +1,17 @@
 #![forbid(unsafe_code)]
 use crate::error::Error;
 use crate::sysfs;
+use serde::{Deserialize, Serialize};
 use std::any::Any;
-use std::fs::read_dir;
+use std::fs::{self, read_dir};
 use std::path::{Path, PathBuf};
 
 // SYSFS root path
 const SYSFS_BATTERY_PATH: &str = "/sys/class/power_supply/";
 
+/// Files that cap charging, older kernels and some ThinkPads use the second name
+const CHARGE_THRESHOLD_FILES: [&str; 2] = ["charge_control_end_threshold", "charge_stop_threshold"];
+
 /// Returns if this system has a battery or not
 pub fn has_battery() -> bool {
     let power_dir = Path::new("/sys/class/power_supply/");
@@ -15,6 +19,24 @@ pub fn has_battery() -> bool {
     dir_count > 0
 }
 
+/// Check that a charge threshold is a percentage the battery can stop at
+pub fn validate_charge_threshold(percent: u8) -> Result<(), Error> {
+    if !(1..=100).contains(&percent) {
+        return Err(Error::OutOfRange(percent as i64, 1, 100));
+    }
+    Ok(())
+}
+
+/// Get the percentage the first battery stops charging at
+pub fn get_charge_threshold() -> Result<u8, Error> {
+    Battery::new()?.get_charge_threshold()
+}
+
+/// Stop charging the first battery once it reaches percent
+pub fn set_charge_threshold(percent: u8) -> Result<(), Error> {
+    Battery::new()?.set_charge_threshold(percent)
+}
+
 /// Describes how the battery condition was obtained
 #[derive(Clone, Default)]
 pub enum BatteryConditionType {
@@ -25,7 +47,7 @@ pub enum BatteryConditionType {
 }
 
 /// Describes the current status of the battery
-#[derive(PartialEq, Eq, Clone, Default)]
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub enum BatteryStatus {
     Charging,
     Discharging,
@@ -34,6 +56,63 @@ pub enum BatteryStatus {
     Unknown,
 }
 
+impl BatteryStatus {
+    /// Parse the status file, "Not charging" (e.g. held at the charge limit) is Unknown
+    pub fn parse(status: &str) -> BatteryStatus {
+        match status.trim() {
+            "Charging" => BatteryStatus::Charging,
+            "Discharging" => BatteryStatus::Discharging,
+            "Full" => BatteryStatus::Full,
+            _ => BatteryStatus::Unknown,
+        }
+    }
+}
+
+/// Seconds left until the battery is empty or full
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeEstimate {
+    ToEmpty(u64),
+    ToFull(u64),
+}
+
+/// The stored and full capacity and the rate it changes at, in µWh and µW or in µAh and µA
+///
+/// Batteries report either energy or charge. The rate is computed from current and voltage when
+/// power_now is missing or 0, or from power and voltage when current_now is
+pub fn battery_flow(read: impl Fn(&str) -> Option<f64>) -> Option<(f64, f64, f64)> {
+    let nonzero = |file: &str| read(file).map(f64::abs).filter(|v| *v > 0.0);
+
+    if let (Some(now), Some(full)) = (read("energy_now"), read("energy_full")) {
+        let rate = nonzero("power_now")
+            .or_else(|| Some(nonzero("current_now")? * nonzero("voltage_now")? / 1_000_000.0))?;
+        return Some((now, full, rate));
+    }
+
+    let (now, full) = (read("charge_now")?, read("charge_full")?);
+    let rate = nonzero("current_now")
+        .or_else(|| Some(nonzero("power_now")? / nonzero("voltage_now")? * 1_000_000.0))?;
+    Some((now, full, rate))
+}
+
+/// Estimate the time to empty when discharging or to full when charging
+pub fn estimate_time(
+    status: &BatteryStatus,
+    now: f64,
+    full: f64,
+    rate: f64,
+) -> Option<TimeEstimate> {
+    if rate <= 0.0 {
+        return None;
+    }
+
+    let hours = |amount: f64| (amount.max(0.0) / rate * 3600.0) as u64;
+    match status {
+        BatteryStatus::Discharging => Some(TimeEstimate::ToEmpty(hours(now))),
+        BatteryStatus::Charging => Some(TimeEstimate::ToFull(hours(full - now))),
+        _ => None,
+    }
+}
+
 /// A structure for holding information about a battery
 /// This structure follows an update model where information within the structure gets updated upon
 /// calling the update method
@@ -48,6 +127,8 @@ pub struct Battery {
     pub energy_full: i32,
     pub energy_full_design: i32,
     pub status: BatteryStatus,
+    /// None when the battery is full, idle, or does not report its rate
+    pub time_estimate: Option<TimeEstimate>,
 }
 
 impl Battery {
@@ -147,11 +228,135 @@ impl Battery {
         Ok(())
     }
 
+    /// Read the charging status and estimate the time left from it
+    fn read_time_estimate(&mut self) {
+        self.status = match fs::read_to_string(self.sys_parent_path.join("status")) {
+            Ok(status) => BatteryStatus::parse(&status),
+            Err(_) => BatteryStatus::Unknown,
+        };
+
+        let read = |file: &str| {
+            let mut value: i64 = 0;
+            sysfs::read(&mut value, &self.sys_parent_path.join(file)).ok()?;
+            Some(value as f64)
+        };
+        self.time_estimate = battery_flow(read)
+            .and_then(|(now, full, rate)| estimate_time(&self.status, now, full, rate));
+    }
+
+    /// Find the file that sets where charging stops, if the hardware has one
+    fn charge_threshold_path(&self) -> Result<PathBuf, Error> {
+        CHARGE_THRESHOLD_FILES
+            .iter()
+            .map(|file| self.sys_parent_path.join(file))
+            .find(|path| path.exists())
+            .ok_or(Error::HdwNotFound)
+    }
+
+    /// Get the percentage the battery stops charging at
+    pub fn get_charge_threshold(&self) -> Result<u8, Error> {
+        let mut percent: u8 = 100;
+        sysfs::read(&mut percent, &self.charge_threshold_path()?)?;
+        Ok(percent)
+    }
+
+    /// Stop charging the battery once it reaches percent
+    pub fn set_charge_threshold(&self, percent: u8) -> Result<(), Error> {
+        validate_charge_threshold(percent)?;
+        sysfs::write(&self.charge_threshold_path()?, &percent.to_string())
+    }
+
     /// Updates all values in this struct from the battery drivers
     pub fn update(&mut self) -> Result<(), Error> {
         self.get_condition()?;
         self.read_charge()?;
+        self.read_time_estimate();
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_charge_threshold_unit_test() {
+        assert!(validate_charge_threshold(80).is_ok());
+        assert!(validate_charge_threshold(1).is_ok());
+        assert!(validate_charge_threshold(100).is_ok());
+        assert!(matches!(
+            validate_charge_threshold(0),
+            Err(Error::OutOfRange(0, 1, 100))
+        ));
+        assert!(validate_charge_threshold(101).is_err());
+    }
+
+    #[test]
+    fn battery_flow_unit_test() {
+        let flow = |files: &[(&str, f64)]| {
+            let files: Vec<(String, f64)> =
+                files.iter().map(|(f, v)| (f.to_string(), *v)).collect();
+            battery_flow(|file| files.iter().find(|(f, _)| f == file).map(|(_, v)| *v))
+        };
+
+        assert_eq!(
+            flow(&[
+                ("energy_now", 30e6),
+                ("energy_full", 50e6),
+                ("power_now", 10e6)
+            ]),
+            Some((30e6, 50e6, 10e6))
+        );
+        // power_now of 0 falls back to current times voltage
+        assert_eq!(
+            flow(&[
+                ("energy_now", 30e6),
+                ("energy_full", 50e6),
+                ("power_now", 0.0),
+                ("current_now", 1e6),
+                ("voltage_now", 12e6)
+            ]),
+            Some((30e6, 50e6, 12e6))
+        );
+        // Some drivers report the current as negative while discharging
+        assert_eq!(
+            flow(&[
+                ("charge_now", 2e6),
+                ("charge_full", 4e6),
+                ("current_now", -1e6)
+            ]),
+            Some((2e6, 4e6, 1e6))
+        );
+        assert_eq!(
+            flow(&[
+                ("charge_now", 2e6),
+                ("charge_full", 4e6),
+                ("power_now", 12e6),
+                ("voltage_now", 12e6)
+            ]),
+            Some((2e6, 4e6, 1e6))
+        );
+        assert_eq!(flow(&[("energy_now", 30e6), ("energy_full", 50e6)]), None);
+    }
+
+    #[test]
+    fn estimate_time_unit_test() {
+        let discharging = BatteryStatus::Discharging;
+        let charging = BatteryStatus::Charging;
+        assert_eq!(
+            estimate_time(&discharging, 30e6, 50e6, 10e6),
+            Some(TimeEstimate::ToEmpty(3 * 3600))
+        );
+        assert_eq!(
+            estimate_time(&charging, 30e6, 50e6, 10e6),
+            Some(TimeEstimate::ToFull(2 * 3600))
+        );
+        assert_eq!(estimate_time(&BatteryStatus::Full, 50e6, 50e6, 10e6), None);
+        assert_eq!(estimate_time(&discharging, 30e6, 50e6, 0.0), None);
+        assert_eq!(
+            BatteryStatus::parse("Not charging\n"),
+            BatteryStatus::Unknown
+        );
+    }
+}
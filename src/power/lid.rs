@@ -21,15 +21,10 @@ fn set_best_path() -> Option<&'static str> {
         "/proc/acpi/button/lid/LID2/state",
     ];
 
-    // Find if any AC power path exists
-    for path in LID_STATUS_PATH.iter() {
-        if Path::new(path).exists() {
-            // Mutate Power struct and leave
-            return Some(path);
-        }
-    }
-
-    None
+    // Find if any lid path exists
+    LID_STATUS_PATH
+        .into_iter()
+        .find(|&path| Path::new(path).exists())
 }
 
 pub struct Lid {
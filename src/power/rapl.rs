@@ -0,0 +1,165 @@
+#![forbid(unsafe_code)]
+//! Package power readings from the RAPL (Running Average Power Limit) energy counters
+//!
+//! The kernel exposes a counter of the energy used by the cpu package in microjoules. Power is
+//! found by reading the counter twice and dividing the energy used by the time between reads.
+use crate::error::Error;
+use crate::sysfs;
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+use std::{thread, time};
+
+/// Powercap zones for the cpu package, AMD is listed second as newer kernels use intel-rapl for
+/// both vendors
+const POWERCAP_PATHS: [&str; 2] = [
+    "/sys/class/powercap/intel-rapl:0",
+    "/sys/class/powercap/amd-rapl:0",
+];
+
+const HWMON_PATH: &str = "/sys/class/hwmon/";
+
+/// Time between the two energy readings in read_package_power
+const SAMPLE_INTERVAL: time::Duration = time::Duration::from_millis(500);
+
+/// A source of package energy readings
+#[derive(Clone, Debug)]
+pub struct Rapl {
+    /// The file holding the energy counter in microjoules
+    pub energy_path: PathBuf,
+    /// The value the counter wraps around at
+    pub max_energy_range: u64,
+}
+
+impl Rapl {
+    /// Find the energy counter for this system
+    ///
+    /// Checks the powercap zones first and falls back to the amd_energy hwmon driver
+    pub fn new() -> Result<Rapl, Error> {
+        for zone in POWERCAP_PATHS {
+            let energy_path = Path::new(zone).join("energy_uj");
+            if energy_path.exists() {
+                let mut max_energy_range: u64 = u32::MAX as u64;
+                sysfs::read(
+                    &mut max_energy_range,
+                    &Path::new(zone).join("max_energy_range_uj"),
+                )
+                .ok();
+
+                return Ok(Rapl {
+                    energy_path,
+                    max_energy_range,
+                });
+            }
+        }
+
+        if let Some(energy_path) = find_amd_energy() {
+            // amd_energy accumulates into a 64 bit counter
+            return Ok(Rapl {
+                energy_path,
+                max_energy_range: u64::MAX,
+            });
+        }
+
+        Err(Error::HdwNotFound)
+    }
+
+    /// Read the current value of the energy counter in microjoules
+    pub fn read_energy(&self) -> Result<u64, Error> {
+        let mut energy: u64 = 0;
+        sysfs::read(&mut energy, &self.energy_path)?;
+        Ok(energy)
+    }
+
+    /// Energy used between two counter readings, accounting for the counter wrapping around
+    pub fn energy_delta(&self, last: u64, current: u64) -> u64 {
+        if current >= last {
+            current - last
+        } else {
+            (self.max_energy_range - last) + current
+        }
+    }
+}
+
+/// Look through the hwmon devices for the amd_energy driver's socket counter
+fn find_amd_energy() -> Option<PathBuf> {
+    for entry in read_dir(HWMON_PATH).ok()? {
+        let path = entry.ok()?.path();
+        let mut name = String::new();
+        if sysfs::read(&mut name, &path.join("name")).is_err() || name != "amd_energy" {
+            continue;
+        }
+
+        // The socket counter is labeled Esocket0, the rest are per core
+        for i in 1..=256 {
+            let mut label = String::new();
+            if sysfs::read(&mut label, &path.join(format!("energy{}_label", i))).is_err() {
+                break;
+            }
+            if label.starts_with("Esocket") {
+                return Some(path.join(format!("energy{}_input", i)));
+            }
+        }
+    }
+    None
+}
+
+/// Convert an amount of microjoules used over a duration into watts
+pub fn to_watts(energy_uj: u64, duration: time::Duration) -> f32 {
+    let secs = duration.as_secs_f32();
+    if secs == 0.0 {
+        return 0.0;
+    }
+    energy_uj as f32 / 1_000_000.0 / secs
+}
+
+/// Convert microjoules into watt hours
+pub fn to_watt_hours(energy_uj: u64) -> f64 {
+    energy_uj as f64 / 3_600_000_000.0
+}
+
+/// Get the average package power in watts by sampling the energy counter twice
+pub fn read_package_power() -> Result<f32, Error> {
+    let rapl = Rapl::new()?;
+
+    let first = rapl.read_energy()?;
+    let start = time::Instant::now();
+    thread::sleep(SAMPLE_INTERVAL);
+    let second = rapl.read_energy()?;
+
+    Ok(to_watts(rapl.energy_delta(first, second), start.elapsed()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn energy_delta_unit_test() {
+        let rapl = Rapl {
+            energy_path: PathBuf::new(),
+            max_energy_range: 262143328850,
+        };
+
+        assert_eq!(rapl.energy_delta(100, 250), 150);
+        // Counter wrapped around
+        assert_eq!(rapl.energy_delta(262143328800, 20), 70);
+
+        let rapl = Rapl {
+            energy_path: PathBuf::new(),
+            max_energy_range: u64::MAX,
+        };
+        assert_eq!(rapl.energy_delta(u64::MAX - 5, 5), 10);
+    }
+
+    #[test]
+    fn to_watts_unit_test() {
+        assert_eq!(to_watts(5_000_000, time::Duration::from_millis(500)), 10.0);
+        assert_eq!(to_watts(5_000_000, time::Duration::from_millis(0)), 0.0);
+    }
+
+    #[test]
+    fn to_watt_hours_unit_test() {
+        assert_eq!(to_watt_hours(3_600_000_000), 1.0);
+        assert_eq!(to_watt_hours(900_000_000), 0.25);
+    }
+}
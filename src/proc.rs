@@ -7,16 +7,18 @@ use crate::error::Error;
 #[derive(Debug)]
 pub struct ProcStat {
     pub cpu_name: String,
-    pub cpu_sum: f32,
-    pub cpu_idle: f32,
+    /// Total time in clock ticks
+    pub cpu_sum: u64,
+    /// Time spent idle in clock ticks
+    pub cpu_idle: u64,
 }
 
 impl Default for ProcStat {
     fn default() -> ProcStat {
         ProcStat {
             cpu_name: "cpu".to_string(),
-            cpu_sum: 0.0,
-            cpu_idle: 0.0,
+            cpu_sum: 0,
+            cpu_idle: 0,
         }
     }
 }
@@ -50,15 +52,15 @@ pub fn parse_proc_file(proc: String) -> Vec<ProcStat> {
             };
 
             for col in &columns {
-                let parse = col.parse::<f32>();
+                let parse = col.parse::<u64>();
                 if let Ok(num) = parse {
-                    proc_struct.cpu_sum += num;
+                    proc_struct.cpu_sum = proc_struct.cpu_sum.wrapping_add(num);
                 }
             }
 
             let num = columns[4]
-                .parse::<f32>()
-                .expect("Should have parsed float from /proc/stat file.");
+                .parse::<u64>()
+                .expect("Should have parsed int from /proc/stat file.");
 
             proc_struct.cpu_idle = num;
             procs.push(proc_struct);
@@ -0,0 +1,189 @@
+#![forbid(unsafe_code)]
+//! Profiles bundle a governor, frequency limits, turbo, and an energy performance preference
+//! under a name in the config so that they can be applied together with `acs profile <name>`
+//!
+//! ```toml
+//! [profiles.balanced]
+//! governor = "powersave"
+//! max_freq = 3200
+//! turbo = true
+//! epp = "balance_power"
+//! ```
+use crate::config::{parse_time_window, Config, Profile};
+use crate::cpu::{get_epp, is_core_online, set_epp, set_governor_for_core, set_turbo, Speed, CPU};
+use crate::daemon::{write_freq_limits, FreqTarget};
+use crate::error::Error;
+use crate::system::{capabilities, check_turbo_enabled, list_cpus};
+
+/// Find a profile by name in the config
+pub fn find_profile<'a>(config: &'a Config, name: &str) -> Result<&'a Profile, Error> {
+    config
+        .profiles
+        .get(name)
+        .ok_or_else(|| Error::UnknownProfile(name.to_string()))
+}
+
+/// Check if the minute of the day is in the window from start to end
+pub fn in_time_window((start, end): (u16, u16), minute: u16) -> bool {
+    if start <= end {
+        minute >= start && minute < end
+    } else {
+        // Wraps past midnight
+        minute >= start || minute < end
+    }
+}
+
+/// Find the window of the schedule that the minute of the day is in, with the profile it maps to
+///
+/// The schedule is sorted by window, so when windows overlap the one that sorts first is used, e.g.
+/// "08:00-17:00" before "09:00-12:00". `acs config check` reports overlapping windows.
+pub fn scheduled_profile(config: &Config, minute: u16) -> Result<Option<(&str, &str)>, Error> {
+    for (window, profile) in &config.schedule {
+        let window_minutes = parse_time_window(window)
+            .map_err(|_| Error::InvalidConfig(format!("\"{}\" is not a time window", window)))?;
+
+        if in_time_window(window_minutes, minute) {
+            return Ok(Some((window, profile)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Apply every setting of a profile to every online core
+///
+/// The current settings are saved first and written back if any part of the profile fails, so a
+/// profile is either applied completely or not at all
+pub fn apply_profile(profile: &Profile) -> Result<(), Error> {
+    let mut cpus: Vec<CPU> = list_cpus()
+        .into_iter()
+        .filter(|c| is_core_online(c.number as usize))
+        .collect();
+
+    // list_cpus is only read once, so get the values as they are right now
+    for cpu in cpus.iter_mut() {
+        cpu.get_max();
+        cpu.get_min();
+        cpu.get_gov()?;
+    }
+
+    // Cores without an epp to read have none to put back
+    let caps = capabilities();
+    let epps: Vec<Option<String>> = cpus
+        .iter()
+        .map(|cpu| {
+            caps.epp
+                .then(|| get_epp(cpu.number as usize).ok())
+                .flatten()
+        })
+        .collect();
+    let turbo = caps.turbo.then(|| check_turbo_enabled().ok()).flatten();
+
+    if let Err(e) = write_profile(profile, &cpus) {
+        restore(&cpus, &epps, turbo);
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+fn write_profile(profile: &Profile, cpus: &[CPU]) -> Result<(), Error> {
+    for cpu in cpus {
+        let core = cpu.number as usize;
+
+        if let Some(gov) = &profile.governor {
+            set_governor_for_core(core, gov)?;
+        }
+
+        write_freq_limits(
+            core,
+            &profile.min_freq.map(|f| FreqTarget::Khz(f * 1000)),
+            &profile.max_freq.map(|f| FreqTarget::Khz(f * 1000)),
+        )?;
+
+        if let Some(epp) = &profile.epp {
+            set_epp(core, epp)?;
+        }
+    }
+
+    if let Some(turbo) = profile.turbo {
+        set_turbo(turbo)?;
+    }
+
+    Ok(())
+}
+
+/// Put back the settings from before the profile was applied
+///
+/// Errors are ignored, a value that failed to be written also failed to change
+fn restore(snapshot: &[CPU], epps: &[Option<String>], turbo: Option<bool>) {
+    for (old, epp) in snapshot.iter().zip(epps) {
+        let core = old.number as usize;
+        set_governor_for_core(core, &old.gov).ok();
+        write_freq_limits(
+            core,
+            &Some(FreqTarget::Khz(old.min_freq)),
+            &Some(FreqTarget::Khz(old.max_freq)),
+        )
+        .ok();
+        if let Some(epp) = epp {
+            set_epp(core, epp).ok();
+        }
+    }
+
+    if let Some(turbo) = turbo {
+        set_turbo(turbo).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_profile_unit_test() {
+        let mut config = Config::default();
+        config.profiles.insert(
+            "balanced".to_string(),
+            Profile {
+                governor: Some("powersave".to_string()),
+                ..Default::default()
+            },
+        );
+
+        assert!(find_profile(&config, "balanced").is_ok());
+        assert!(matches!(
+            find_profile(&config, "turbo"),
+            Err(Error::UnknownProfile(_))
+        ));
+    }
+
+    #[test]
+    fn scheduled_profile_unit_test() -> Result<(), Error> {
+        let mut config = Config::default();
+        config
+            .schedule
+            .insert("22:00-07:00".to_string(), "powersave".to_string());
+        config
+            .schedule
+            .insert("07:00-22:00".to_string(), "balanced".to_string());
+
+        let profile = |minute| scheduled_profile(&config, minute).map(|w| w.map(|(_, p)| p));
+        assert_eq!(profile(23 * 60)?, Some("powersave"));
+        assert_eq!(profile(3 * 60)?, Some("powersave"));
+        assert_eq!(profile(7 * 60)?, Some("balanced"));
+        assert_eq!(profile(22 * 60 - 1)?, Some("balanced"));
+
+        config.schedule.clear();
+        config
+            .schedule
+            .insert("12:00-13:00".to_string(), "balanced".to_string());
+        assert_eq!(scheduled_profile(&config, 14 * 60)?, None);
+
+        config
+            .schedule
+            .insert("noon".to_string(), "balanced".to_string());
+        assert!(scheduled_profile(&config, 14 * 60).is_err());
+        Ok(())
+    }
+}
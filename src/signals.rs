@@ -0,0 +1,68 @@
+#![forbid(unsafe_code)]
+//! Signal handling for the daemon
+//!
+//! - SIGHUP reloads the config
+//! - SIGTERM and SIGINT restore the governors and turbo from before the daemon started, then exit
+//!
+//! Signals are blocked in every thread and waited on by a single thread, so the work done for a
+//! signal can lock the daemon like any other thread instead of running in a signal handler.
+use crate::config::try_open_config;
+use crate::daemon::{shutdown, Checker, Daemon};
+use crate::logger::{self, Interface};
+use nix::sys::signal::{SigSet, Signal};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+fn daemon_signals() -> SigSet {
+    let mut set = SigSet::empty();
+    set.add(Signal::SIGHUP);
+    set.add(Signal::SIGTERM);
+    set.add(Signal::SIGINT);
+    set
+}
+
+/// Block the signals the daemon handles in the current thread
+///
+/// Has to be called before any other thread is spawned, threads inherit the blocked signals and
+/// an unblocked thread would get the default action (exit) instead
+pub fn block_signals() {
+    daemon_signals().thread_block().ok();
+}
+
+/// Wait for signals in a new thread and handle them
+pub fn handle_signals(daemon_mutex: Arc<Mutex<Daemon>>) {
+    thread::spawn(move || {
+        let set = daemon_signals();
+        loop {
+            match set.wait() {
+                Ok(Signal::SIGHUP) => reload_config(&daemon_mutex),
+                Ok(Signal::SIGTERM) | Ok(Signal::SIGINT) => {
+                    shutdown(&mut daemon_mutex.lock().unwrap())
+                }
+                Ok(_) => {}
+                Err(_) => return,
+            }
+        }
+    });
+}
+
+/// Read the config file again and use it in the running daemon
+///
+/// A config that can not be read or parsed is logged and the current config is kept
+fn reload_config(daemon_mutex: &Arc<Mutex<Daemon>>) {
+    let config = try_open_config();
+    let mut daemon = daemon_mutex.lock().unwrap();
+
+    match config {
+        Ok(config) => {
+            daemon.apply_config(config);
+            daemon
+                .logger
+                .log("Reloaded config after SIGHUP", logger::Severity::Log);
+        }
+        Err(e) => daemon.logger.log(
+            &format!("Keeping the current config, could not reload: {:?}", e),
+            logger::Severity::Error,
+        ),
+    }
+}
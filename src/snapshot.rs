@@ -0,0 +1,217 @@
+#![forbid(unsafe_code)]
+//! The power settings of the whole system, saved with `acs snapshot` and compared with `acs diff`
+//!
+//! A snapshot is JSON with the governor, frequency limits, and EPP of every online core, along
+//! with turbo and the battery charge limit. Anything that can not be read on a system, e.g. the
+//! EPP without a driver that has it, is left out and compared like any other value.
+use crate::cpu::{get_epp, get_turbo};
+use crate::error::Error;
+use crate::power::battery::get_charge_threshold;
+use crate::system::{capabilities, list_cpus};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoreState {
+    pub number: usize,
+    pub governor: String,
+    /// The scaling limits in kHz
+    pub min_freq: i32,
+    pub max_freq: i32,
+    pub epp: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SystemState {
+    /// When the snapshot was taken, in RFC 3339
+    pub taken: String,
+    /// Only the cores that were online
+    pub cores: Vec<CoreState>,
+    pub turbo: Option<bool>,
+    pub charge_limit: Option<u8>,
+}
+
+/// A value that is not the same in two states
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Difference {
+    /// What changed, e.g. "cpu2 governor" or "turbo"
+    pub key: String,
+    pub before: String,
+    pub after: String,
+}
+
+impl SystemState {
+    /// Read the current state with the same getters as `acs get`
+    pub fn capture() -> SystemState {
+        let caps = capabilities();
+        let cores = list_cpus()
+            .into_iter()
+            .map(|cpu| CoreState {
+                number: cpu.number as usize,
+                epp: caps
+                    .epp
+                    .then(|| get_epp(cpu.number as usize).ok())
+                    .flatten(),
+                governor: cpu.gov,
+                min_freq: cpu.min_freq,
+                max_freq: cpu.max_freq,
+            })
+            .collect();
+
+        SystemState {
+            taken: Local::now().to_rfc3339(),
+            cores,
+            turbo: caps.turbo.then(|| get_turbo().ok()).flatten(),
+            charge_limit: get_charge_threshold().ok(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    pub fn parse(text: &str) -> Result<SystemState, Error> {
+        serde_json::from_str(text).map_err(|_| Error::Parse)
+    }
+
+    pub fn load(path: &Path) -> Result<SystemState, Error> {
+        SystemState::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        fs::write(path, self.to_json() + "\n")?;
+        Ok(())
+    }
+}
+
+fn describe<T: ToString>(value: &Option<T>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => "none".to_string(),
+    }
+}
+
+/// Every value that is not the same in before and after, in core order
+///
+/// A core that is only in one of them shows up as "online" on one side and "offline" on the other
+pub fn diff(before: &SystemState, after: &SystemState) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    let mut push = |key: String, before: String, after: String| {
+        if before != after {
+            differences.push(Difference { key, before, after });
+        }
+    };
+
+    let mut numbers: Vec<usize> = before
+        .cores
+        .iter()
+        .chain(&after.cores)
+        .map(|core| core.number)
+        .collect();
+    numbers.sort_unstable();
+    numbers.dedup();
+
+    for number in numbers {
+        let find = |state: &SystemState| state.cores.iter().find(|c| c.number == number).cloned();
+        let name = format!("cpu{}", number);
+        match (find(before), find(after)) {
+            (Some(b), Some(a)) => {
+                push(format!("{} governor", name), b.governor, a.governor);
+                push(
+                    format!("{} min_freq", name),
+                    b.min_freq.to_string(),
+                    a.min_freq.to_string(),
+                );
+                push(
+                    format!("{} max_freq", name),
+                    b.max_freq.to_string(),
+                    a.max_freq.to_string(),
+                );
+                push(format!("{} epp", name), describe(&b.epp), describe(&a.epp));
+            }
+            (b, a) => {
+                let online = |core: &Option<CoreState>| match core {
+                    Some(_) => "online".to_string(),
+                    None => "offline".to_string(),
+                };
+                push(name, online(&b), online(&a));
+            }
+        }
+    }
+
+    push(
+        "turbo".to_string(),
+        describe(&before.turbo),
+        describe(&after.turbo),
+    );
+    push(
+        "charge_limit".to_string(),
+        describe(&before.charge_limit),
+        describe(&after.charge_limit),
+    );
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn core(number: usize, governor: &str, max_freq: i32) -> CoreState {
+        CoreState {
+            number,
+            governor: governor.to_string(),
+            min_freq: 400_000,
+            max_freq,
+            epp: Some("balance_power".to_string()),
+        }
+    }
+
+    fn state(cores: Vec<CoreState>, turbo: Option<bool>) -> SystemState {
+        SystemState {
+            taken: "2024-01-01T00:00:00+00:00".to_string(),
+            cores,
+            turbo,
+            charge_limit: Some(80),
+        }
+    }
+
+    #[test]
+    fn snapshot_diff_unit_test() {
+        let before = state(
+            vec![
+                core(0, "powersave", 3_000_000),
+                core(1, "powersave", 3_000_000),
+            ],
+            Some(true),
+        );
+        assert!(diff(&before, &before).is_empty());
+
+        let after = state(vec![core(0, "performance", 2_000_000)], None);
+        let keys: Vec<(String, String, String)> = diff(&before, &after)
+            .into_iter()
+            .map(|d| (d.key, d.before, d.after))
+            .collect();
+        let entry = |key: &str, before: &str, after: &str| {
+            (key.to_string(), before.to_string(), after.to_string())
+        };
+        assert_eq!(
+            keys,
+            vec![
+                entry("cpu0 governor", "powersave", "performance"),
+                entry("cpu0 max_freq", "3000000", "2000000"),
+                entry("cpu1", "online", "offline"),
+                entry("turbo", "true", "none"),
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshot_parse_unit_test() -> Result<(), Error> {
+        let saved = state(vec![core(0, "schedutil", 3_000_000)], Some(false));
+        assert_eq!(SystemState::parse(&saved.to_json())?, saved);
+        assert!(SystemState::parse("{\"cores\": 3}").is_err());
+        Ok(())
+    }
+}
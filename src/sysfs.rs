@@ -1,9 +1,17 @@
 #![forbid(unsafe_code)]
 use crate::error::Error;
+use crate::theme::paint;
+use efcl::Color;
 use globset::Glob;
-use std::fs;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 pub fn read<T>(val: &mut T, path: &Path) -> Result<(), Error>
 where
@@ -20,6 +28,192 @@ where
     Ok(())
 }
 
+/// How long a value written to a file is trusted to still be there, 0 turns it off
+static DEBOUNCE_MS: AtomicU64 = AtomicU64::new(0);
+
+/// The last value written to each file and when
+static LAST_WRITES: Mutex<BTreeMap<PathBuf, (String, Instant)>> = Mutex::new(BTreeMap::new());
+
+/// If writes are only described instead of done, set with `--dry-run`
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// The last value each file would have been given during a dry run
+static DRY_RUN_WRITES: Mutex<BTreeMap<PathBuf, String>> = Mutex::new(BTreeMap::new());
+
+/// Dry run writes waiting for the daemon to log them, None prints them right away
+static DRY_RUN_LOG: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+/// Describe every write instead of doing it, the writes still return success
+pub fn set_dry_run(enabled: bool) {
+    DRY_RUN.store(enabled, Ordering::Relaxed);
+}
+
+pub fn dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Keep the dry run writes for `take_dry_run_log` instead of printing them, for the daemon
+pub fn queue_dry_run_log() {
+    DRY_RUN_LOG.lock().unwrap().get_or_insert_with(Vec::new);
+}
+
+/// The dry run writes since it was last called
+pub fn take_dry_run_log() -> Vec<String> {
+    match DRY_RUN_LOG.lock().unwrap().as_mut() {
+        Some(log) => std::mem::take(log),
+        None => Vec::new(),
+    }
+}
+
+/// What a write does, e.g. "would set cpu3 governor -> performance"
+///
+/// Files of a core are named after it, other files keep their whole path
+pub fn describe_write(path: &Path, value: &str) -> String {
+    let file = path.file_name().unwrap_or_default().to_string_lossy();
+    let name = match file.as_ref() {
+        "scaling_governor" => "governor",
+        "scaling_max_freq" => "max_freq",
+        "scaling_min_freq" => "min_freq",
+        "energy_performance_preference" => "epp",
+        other => other,
+    };
+    let core = path
+        .ancestors()
+        .filter_map(|dir| dir.file_name()?.to_str())
+        .find(|dir| {
+            dir.strip_prefix("cpu")
+                .is_some_and(|n| n.parse::<usize>().is_ok())
+        });
+
+    match core {
+        Some(core) => format!("would set {} {} -> {}", core, name, value.trim_end()),
+        None => format!("would set {} -> {}", path.display(), value.trim_end()),
+    }
+}
+
+/// Record a write that only gets described, repeating the last value to a file is left out
+fn dry_run_write(path: &Path, value: &str) {
+    let mut writes = DRY_RUN_WRITES.lock().unwrap();
+    if writes.get(path).map(|last| last.as_str()) == Some(value) {
+        return;
+    }
+    writes.insert(path.to_path_buf(), value.to_string());
+    drop(writes);
+
+    let message = describe_write(path, value);
+    match DRY_RUN_LOG.lock().unwrap().as_mut() {
+        Some(log) => log.push(message),
+        None => print_dry_run(&message),
+    }
+}
+
+fn print_dry_run(message: &str) {
+    println!("{}: {}", paint(Color::YELLOW, "DRY RUN"), message);
+}
+
+/// Print the dry run writes kept for the daemon, for a command that makes one without running it
+pub fn print_dry_run_log() {
+    for message in take_dry_run_log() {
+        print_dry_run(&message);
+    }
+}
+
+/// Set how long writing the same value to a file again gets skipped without reading it first
+pub fn set_debounce(debounce: Duration) {
+    DEBOUNCE_MS.store(debounce.as_millis() as u64, Ordering::Relaxed);
+}
+
+/// Check if the value was written to the file within the window
+fn recently_written(path: &Path, value: &str, window: Duration) -> bool {
+    match LAST_WRITES.lock().unwrap().get(path) {
+        Some((last, at)) => last == value && at.elapsed() < window,
+        None => false,
+    }
+}
+
+/// Check if the file already holds the value, files that can not be read never do
+fn unchanged(path: &Path, value: &str) -> bool {
+    match fs::read_to_string(path) {
+        Ok(current) => current.trim_end() == value.trim_end(),
+        Err(_) => false,
+    }
+}
+
+/// Write a value to a sysfs file
+///
+/// Nothing is written when the file already holds the value, rewriting some files, like the
+/// governor, makes the kernel reset state that goes with them
+pub fn write(path: &Path, value: &str) -> Result<(), Error> {
+    let debounce = Duration::from_millis(DEBOUNCE_MS.load(Ordering::Relaxed));
+    if recently_written(path, value, debounce) || unchanged(path, value) {
+        return Ok(());
+    }
+    if dry_run() {
+        dry_run_write(path, value);
+        return Ok(());
+    }
+
+    let mut buffer = File::create(path).map_err(|e| Error::from_io(e, path))?;
+    buffer
+        .write_all(value.as_bytes())
+        .map_err(|e| Error::from_io(e, path))?;
+
+    if !debounce.is_zero() {
+        LAST_WRITES
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), (value.to_string(), Instant::now()));
+    }
+    Ok(())
+}
+
+/// Time spent in and number of entries into an idle state of a core
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct CState {
+    pub name: String,
+    /// Total time spent in the state in microseconds
+    pub time_us: u64,
+    /// How many times the state was entered
+    pub usage: u64,
+}
+
+/// The idle states of one core
+#[derive(Clone, Debug, Serialize)]
+pub struct CoreCStates {
+    pub core: usize,
+    pub states: Vec<CState>,
+}
+
+/// Read every cpuidle state of a core from a directory like /sys/devices/system/cpu/cpu0
+///
+/// Cores can have a different number of states, so the state directories are listed instead of
+/// expecting a fixed count
+pub fn read_cstates(cpu_path: &Path) -> Result<Vec<CState>, Error> {
+    let mut dirs: Vec<(usize, PathBuf)> = fs::read_dir(cpu_path.join("cpuidle"))?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let number = name.strip_prefix("state")?.parse::<usize>().ok()?;
+            Some((number, entry.path()))
+        })
+        .collect();
+    dirs.sort_by_key(|(number, _)| *number);
+
+    let mut states = Vec::new();
+    for (_, dir) in dirs {
+        let mut state = CState {
+            name: String::new(),
+            time_us: 0,
+            usage: 0,
+        };
+        read(&mut state.name, &dir.join("name"))?;
+        read(&mut state.time_us, &dir.join("time"))?;
+        read(&mut state.usage, &dir.join("usage"))?;
+        states.push(state);
+    }
+    Ok(states)
+}
+
 // Lookup a Hdw based on its Parent Path & a glob
 pub fn get_path_by_glob(sysfs_parent_path: &str, hdw_glob: &str) -> Result<PathBuf, Error> {
     let mut glob_path = sysfs_parent_path.to_string();
@@ -39,3 +233,123 @@ pub fn get_path_by_glob(sysfs_parent_path: &str, hdw_glob: &str) -> Result<PathB
     }
     Err(Error::HdwNotFound)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn write_unchanged_unit_test() -> Result<(), Error> {
+        let dir = TestDir::new("write");
+        let path = dir.join("scaling_governor");
+        fs::write(&path, "powersave\n")?;
+
+        let result = (
+            unchanged(&path, "powersave"),
+            unchanged(&path, "performance"),
+            unchanged(&path.with_extension("missing"), "powersave"),
+        );
+        write(&path, "performance")?;
+        let written = fs::read_to_string(&path)?;
+
+        assert_eq!(result, (true, false, false));
+        assert_eq!(written, "performance");
+        Ok(())
+    }
+
+    #[test]
+    fn write_error_unit_test() {
+        let dir = TestDir::new("missing");
+        let path = dir.join("cpu0").join("scaling_governor");
+        assert!(matches!(
+            write(&path, "performance"),
+            Err(Error::HdwNotFound)
+        ));
+
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        match Error::from_io(
+            denied,
+            Path::new("/sys/devices/system/cpu/intel_pstate/no_turbo"),
+        ) {
+            Error::PermissionDenied { path } => {
+                assert_eq!(path, "/sys/devices/system/cpu/intel_pstate/no_turbo")
+            }
+            e => panic!("expected PermissionDenied, got {:?}", e),
+        }
+        let busy = std::io::Error::from(std::io::ErrorKind::ResourceBusy);
+        assert!(matches!(
+            Error::from_io(busy, Path::new("online")),
+            Error::IO(_)
+        ));
+    }
+
+    #[test]
+    fn describe_write_unit_test() {
+        let describe = |path: &str, value: &str| describe_write(Path::new(path), value);
+        assert_eq!(
+            describe(
+                "/sys/devices/system/cpu/cpu3/cpufreq/scaling_governor",
+                "performance"
+            ),
+            "would set cpu3 governor -> performance"
+        );
+        assert_eq!(
+            describe("/sys/devices/system/cpu/cpu12/online", "0"),
+            "would set cpu12 online -> 0"
+        );
+        assert_eq!(
+            describe("/sys/devices/system/cpu/intel_pstate/no_turbo", "1\n"),
+            "would set /sys/devices/system/cpu/intel_pstate/no_turbo -> 1"
+        );
+    }
+
+    #[test]
+    fn recently_written_unit_test() {
+        let path = Path::new("/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor");
+        LAST_WRITES.lock().unwrap().insert(
+            path.to_path_buf(),
+            ("powersave".to_string(), Instant::now()),
+        );
+
+        assert!(recently_written(path, "powersave", Duration::from_secs(60)));
+        assert!(!recently_written(
+            path,
+            "performance",
+            Duration::from_secs(60)
+        ));
+        assert!(!recently_written(path, "powersave", Duration::ZERO));
+        assert!(!recently_written(
+            Path::new("/sys/missing"),
+            "powersave",
+            Duration::from_secs(60)
+        ));
+    }
+
+    #[test]
+    fn read_cstates_unit_test() -> Result<(), Error> {
+        let cpu_path = TestDir::new("cstates");
+        // state10 is listed after state2, the states have to be sorted by number
+        for (dir, name, time, usage) in [
+            ("state0", "POLL", "10", "1"),
+            ("state10", "C10", "3000", "30"),
+            ("state2", "C2", "200", "20"),
+        ] {
+            let dir = cpu_path.join("cpuidle").join(dir);
+            fs::create_dir_all(&dir)?;
+            fs::write(dir.join("name"), format!("{}\n", name))?;
+            fs::write(dir.join("time"), format!("{}\n", time))?;
+            fs::write(dir.join("usage"), format!("{}\n", usage))?;
+        }
+        fs::write(cpu_path.join("cpuidle").join("driver"), "intel_idle\n").ok();
+
+        let states = read_cstates(&cpu_path);
+        let states = states?;
+
+        let names: Vec<&str> = states.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["POLL", "C2", "C10"]);
+        assert_eq!(states[2].time_us, 3000);
+        assert_eq!(states[1].usage, 20);
+        Ok(())
+    }
+}
@@ -1,16 +1,21 @@
 #![forbid(unsafe_code)]
 use cached::proc_macro::once;
+use std::collections::{BTreeMap, VecDeque};
 use std::fs::{self, read_dir};
 use std::path::Path;
 use std::string::String;
 use std::{thread, time};
 
-use crate::cpu::Speed;
+use crate::cpu::{get_epp, get_turbo, is_core_online, online_cores, Speed};
+use crate::power::rapl::Rapl;
 use crate::proc::{parse_proc_file, read_proc_stat_file, ProcStat};
+use crate::sysfs::{read_cstates, CoreCStates};
 use log::debug;
 
 use crate::cpu::CPU;
 use crate::error::Error;
+use crate::thermal::apply_core_temps;
+use serde::{Deserialize, Serialize};
 
 /// Find the average frequency of all cores
 ///
@@ -25,6 +30,83 @@ pub fn check_cpu_freq(cpus: &[CPU]) -> f32 {
     sum as f32 / freqs.len() as f32
 }
 
+/// The frequencies of each core over the last few readings, for a value that jumps around less
+#[derive(Clone, Debug, Default)]
+pub struct FreqWindow {
+    /// How many readings are kept per core
+    pub size: usize,
+    /// The readings by core number, so they stay with their core when another goes offline
+    cores: BTreeMap<usize, VecDeque<i32>>,
+}
+
+impl FreqWindow {
+    pub fn new(size: usize) -> FreqWindow {
+        FreqWindow {
+            size,
+            cores: BTreeMap::new(),
+        }
+    }
+
+    /// Add a reading of every core, dropping the oldest once the window is full
+    pub fn push(&mut self, cpus: &[CPU]) {
+        if self.size == 0 {
+            return;
+        }
+
+        // Cores that went away, e.g. after being taken offline, lose their readings
+        self.cores
+            .retain(|core, _| cpus.iter().any(|cpu| cpu.number as usize == *core));
+        for cpu in cpus {
+            let samples = self.cores.entry(cpu.number as usize).or_default();
+            if samples.len() == self.size {
+                samples.pop_front();
+            }
+            samples.push_back(cpu.cur_freq);
+        }
+    }
+
+    /// Average frequency of one core over the window
+    pub fn core_mean(&self, core: usize) -> Option<f32> {
+        let samples = self.cores.get(&core).filter(|s| !s.is_empty())?;
+        Some(samples.iter().map(|&f| f as f32).sum::<f32>() / samples.len() as f32)
+    }
+
+    /// Average frequency of every core over the window
+    pub fn mean(&self) -> Option<f32> {
+        let means: Vec<f32> = self
+            .cores
+            .keys()
+            .filter_map(|&core| self.core_mean(core))
+            .collect();
+        if means.is_empty() {
+            None
+        } else {
+            Some(means.iter().sum::<f32>() / means.len() as f32)
+        }
+    }
+
+    /// How many readings the averages are over, fewer than the size until the window fills
+    pub fn samples(&self) -> usize {
+        self.cores.values().next().map_or(0, |s| s.len())
+    }
+}
+
+/// Read the cpus `size` times, `interval` apart, and average their frequencies
+pub fn sample_freq_window(size: usize, interval: time::Duration) -> FreqWindow {
+    let mut window = FreqWindow::new(size);
+    let mut cpus = list_cpus();
+    for i in 0..size {
+        if i > 0 {
+            thread::sleep(interval);
+            for cpu in cpus.iter_mut() {
+                cpu.get_cur();
+            }
+        }
+        window.push(&cpus);
+    }
+    window
+}
+
 /// Find the average usage of all cores
 pub fn check_cpu_usage(cpus: &[CPU]) -> f32 {
     let usage: Vec<i32> = cpus.iter().map(|x| (x.cur_usage * 100.0) as i32).collect();
@@ -56,6 +138,65 @@ pub fn get_highest_temp(cpus: &[CPU]) -> i32 {
     temp_max
 }
 
+/// The governor every core is on, "mixed" if they differ
+pub fn shared_governor(cpus: &[CPU]) -> String {
+    match cpus.first() {
+        Some(first) if cpus.iter().all(|c| c.gov == first.gov) => first.gov.clone(),
+        Some(_) => "mixed".to_string(),
+        None => "unknown".to_string(),
+    }
+}
+
+/// The physical package, or socket, a core is on
+pub fn read_package_id(core: usize) -> Result<u32, Error> {
+    let path = format!(
+        "/sys/devices/system/cpu/cpu{}/topology/physical_package_id",
+        core
+    );
+    Ok(read_int(&path)? as u32)
+}
+
+/// The readings of the cores on one package
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PackageSummary {
+    pub package: u32,
+    pub cores: Vec<i8>,
+    pub avg_freq_mhz: f32,
+    pub temp_c: f32,
+    /// The governor shared by the cores of the package, or "mixed" if they differ
+    pub governor: String,
+}
+
+/// Group the cores by package, cores without a package id count as package 0
+pub fn group_by_package(
+    cpus: &[CPU],
+    package_of: impl Fn(&CPU) -> Option<u32>,
+) -> Vec<PackageSummary> {
+    let mut packages: BTreeMap<u32, Vec<CPU>> = BTreeMap::new();
+    for cpu in cpus {
+        packages
+            .entry(package_of(cpu).unwrap_or(0))
+            .or_default()
+            .push(cpu.clone());
+    }
+
+    packages
+        .into_iter()
+        .map(|(package, cpus)| PackageSummary {
+            package,
+            cores: cpus.iter().map(|c| c.number).collect(),
+            avg_freq_mhz: check_cpu_freq(&cpus) / 1000.0,
+            temp_c: check_cpu_temperature(&cpus) / 1000.0,
+            governor: shared_governor(&cpus),
+        })
+        .collect()
+}
+
+/// Group the cores by the package they are on in the topology
+pub fn list_packages(cpus: &[CPU]) -> Vec<PackageSummary> {
+    group_by_package(cpus, |cpu| read_package_id(cpu.number as usize).ok())
+}
+
 /// Warn the user that speeds may be wrong if inside docker
 pub fn inside_docker() -> bool {
     Path::new("/proc/self/root/.dockerenv").exists()
@@ -129,19 +270,58 @@ pub fn calculate_cpu_percent(timing_1: &ProcStat, timing_2: &ProcStat) -> f32 {
         "ProcStat object {:?} and {:?} do not belong to the same cpu",
         timing_1, timing_2
     );
-    let cpu_delta: f32 = timing_2.cpu_sum - timing_1.cpu_sum;
-    let cpu_delta_idle: f32 = timing_2.cpu_idle - timing_1.cpu_idle;
-    let cpu_used: f32 = cpu_delta - cpu_delta_idle;
-    cpu_used / cpu_delta
+    usage_between(timing_1, timing_2).unwrap_or(0.0)
 }
 
-fn read_turbo_file() -> Result<String, Error> {
-    let turbo_path: &str = "/sys/devices/system/cpu/intel_pstate/no_turbo";
-    let is_turbo = fs::read_to_string(turbo_path)?;
-    Ok(is_turbo)
+/// The fraction of time a cpu was busy between two samples of /proc/stat, 1.0 is fully busy
+///
+/// Gives None when there is no delta to use: no time passed, or a counter went backwards, which
+/// happens when it wraps around or a core is brought back online and its counters start over
+pub fn usage_between(last: &ProcStat, cur: &ProcStat) -> Option<f32> {
+    let total = cur.cpu_sum.checked_sub(last.cpu_sum)?;
+    let idle = cur.cpu_idle.checked_sub(last.cpu_idle)?;
+
+    if total == 0 || idle > total {
+        return None;
+    }
+
+    Some((total - idle) as f32 / total as f32)
+}
+
+/// Utilization of the whole cpu and each core between two samples of /proc/stat
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Utilization {
+    /// From the aggregate "cpu" line, None if it had no delta
+    pub overall: Option<f32>,
+    /// Each core that had a delta, by name such as "cpu3"
+    pub cores: Vec<(String, f32)>,
 }
 
-fn interpret_turbo(is_turbo: &mut String) -> Result<bool, Error> {
+/// Find the utilization between two samples, matching the cores by name
+///
+/// A core missing from the last sample, like on the first sample or after a core came online,
+/// is left out rather than compared against nothing
+pub fn calculate_utilization(last: &[ProcStat], cur: &[ProcStat]) -> Utilization {
+    let mut utilization = Utilization::default();
+
+    for stat in cur {
+        let usage = last
+            .iter()
+            .find(|l| l.cpu_name == stat.cpu_name)
+            .and_then(|l| usage_between(l, stat));
+
+        match usage {
+            Some(usage) if stat.cpu_name == "cpu" => utilization.overall = Some(usage),
+            Some(usage) => utilization.cores.push((stat.cpu_name.clone(), usage)),
+            None => {}
+        }
+    }
+
+    utilization
+}
+
+/// Interpret the value of intel_pstate/no_turbo, meaning 0 is enabled
+pub fn interpret_turbo(is_turbo: &mut String) -> Result<bool, Error> {
     // Remove the last character (the newline)
     is_turbo.pop();
     // The file will be something like 0 or 1, parse this into an int
@@ -154,9 +334,7 @@ fn interpret_turbo(is_turbo: &mut String) -> Result<bool, Error> {
 
 /// Check if turbo is enabled for the machine, (enabled in bios)
 pub fn check_turbo_enabled() -> Result<bool, Error> {
-    let mut turbo_string = read_turbo_file()?;
-    let is_turbo = interpret_turbo(&mut turbo_string)?;
-    Ok(is_turbo)
+    get_turbo()
 }
 
 fn read_govs_file() -> Result<String, Error> {
@@ -185,15 +363,25 @@ pub fn check_available_governors() -> Result<Vec<String>, Error> {
 }
 
 /// Get all the cpus (cores), returns cpus from 0 to the (amount of cores -1) the machine has
+///
+/// Only read the first time, use `read_cpus` to see cores that went offline or came back since
 #[once]
 pub fn list_cpus() -> Vec<CPU> {
+    read_cpus()
+}
+
+/// Read every online cpu (core) again, not cached like `list_cpus`
+pub fn read_cpus() -> Vec<CPU> {
     let mut cpus: Vec<String> = Vec::<String>::new();
 
     // Get each item in the cpu directory
-    for a in read_dir("/sys/devices/system/cpu").unwrap_or_else(|_| {
-        panic!("Could not read directory");
-    }) {
-        let path_string: String = format!("{:?}", a.unwrap().path());
+    for a in read_dir("/sys/devices/system/cpu")
+        .unwrap_or_else(|_| {
+            panic!("Could not read directory");
+        })
+        .flatten()
+    {
+        let path_string: String = format!("{:?}", a.path());
         let path: String = path_string
             .chars()
             // Skip the characters that are before the cpu name
@@ -218,6 +406,11 @@ pub fn list_cpus() -> Vec<CPU> {
     for cpu in cpus {
         let num: i8 = cpu[3..].parse::<i8>().unwrap_or(0);
 
+        // Offline cores have no cpufreq files, reading them would only give zeros
+        if !is_core_online(num as usize) {
+            continue;
+        }
+
         // Make a new cpu
         let mut new = CPU {
             name: cpu,
@@ -231,17 +424,115 @@ pub fn list_cpus() -> Vec<CPU> {
             gov: "Unknown".to_string(),
         };
 
-        new.init_cpu().unwrap();
-
-        new.update().unwrap();
+        // A core that went away or has no cpufreq files yet, e.g. right after coming online, is
+        // left out until it can be read
+        if let Err(e) = new.init_cpu().and_then(|_| new.update()) {
+            debug!("Skipping {} that could not be read: {:?}", new.name, e);
+            continue;
+        }
 
         to_return.push(new)
     }
 
-    to_return.sort_by(|a, b| a.number.cmp(&b.number));
+    to_return.sort_by_key(|a| a.number);
+    apply_core_temps(&mut to_return);
     to_return
 }
 
+/// What the cpu and its drivers support, detected the first time `capabilities` is called
+///
+/// The daemon and the other long running parts check these instead of probing sysfs each time
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    pub model: Option<String>,
+    /// The cpufreq driver, e.g. intel_pstate, amd-pstate-epp, or acpi-cpufreq
+    pub driver: Option<String>,
+    /// Hardware P-states, the cpu picks its own frequency inside the limits it is given
+    pub hwp: bool,
+    pub turbo: bool,
+    /// If the energy performance preference can be set
+    pub epp: bool,
+    /// If the package energy counter can be read
+    pub rapl: bool,
+    /// Physical cores that are online
+    pub cores: usize,
+    /// Online threads, more than the cores with hyperthreading or SMT
+    pub threads: usize,
+}
+
+impl Capabilities {
+    pub fn detect() -> Capabilities {
+        let cpu_info = open_cpu_info().unwrap_or_default();
+        let threads = online_cores().unwrap_or_default();
+        let topology: Vec<(u32, u32)> = threads
+            .iter()
+            .filter_map(|&core| Some((read_package_id(core).ok()?, read_core_id(core).ok()?)))
+            .collect();
+
+        Capabilities {
+            model: get_name_from_cpu_info(cpu_info.clone()).ok(),
+            driver: read_str("/sys/devices/system/cpu/cpu0/cpufreq/scaling_driver").ok(),
+            hwp: parse_cpu_flags(&cpu_info).iter().any(|flag| flag == "hwp"),
+            turbo: get_turbo().is_ok(),
+            epp: get_epp(0).is_ok(),
+            rapl: Rapl::new().is_ok(),
+            // Without a topology to read each thread counts as a core
+            cores: if topology.is_empty() {
+                threads.len()
+            } else {
+                count_cores(&topology)
+            },
+            threads: threads.len(),
+        }
+    }
+}
+
+/// The capabilities of this system, only detected the first time
+#[once]
+pub fn capabilities() -> Capabilities {
+    Capabilities::detect()
+}
+
+/// The core a thread is on inside its package
+pub fn read_core_id(core: usize) -> Result<u32, Error> {
+    Ok(read_int(&format!(
+        "/sys/devices/system/cpu/cpu{}/topology/core_id",
+        core
+    ))? as u32)
+}
+
+/// The flags of the first cpu in /proc/cpuinfo, e.g. "hwp" or "constant_tsc"
+fn parse_cpu_flags(cpu_info: &str) -> Vec<String> {
+    cpu_info
+        .lines()
+        .find(|line| line.starts_with("flags"))
+        .and_then(|line| line.split(':').nth(1))
+        .map(|flags| flags.split_whitespace().map(|x| x.to_owned()).collect())
+        .unwrap_or_default()
+}
+
+/// The physical cores from the (package, core) of each thread, threads on one core share both
+fn count_cores(topology: &[(u32, u32)]) -> usize {
+    let mut cores = topology.to_vec();
+    cores.sort_unstable();
+    cores.dedup();
+    cores.len()
+}
+
+/// Get the idle state residency of every online core, offline cores are skipped
+pub fn list_cpu_cstates() -> Vec<CoreCStates> {
+    list_cpus()
+        .into_iter()
+        .map(|cpu| cpu.number as usize)
+        .filter(|core| is_core_online(*core))
+        .filter_map(|core| {
+            let path = format!("/sys/devices/system/cpu/cpu{}", core);
+            let states = read_cstates(Path::new(&path)).ok()?;
+            Some(CoreCStates { core, states })
+        })
+        .collect()
+}
+
 /// Get a vector of speeds reported from each cpu from list_cpus
 pub fn list_cpu_speeds() -> Vec<i32> {
     list_cpus().into_iter().map(|x| x.cur_freq).collect()
@@ -257,6 +548,84 @@ pub fn list_cpu_governors() -> Vec<String> {
     list_cpus().into_iter().map(|x| x.gov).collect()
 }
 
+/// Get a vector of the energy performance preferences of the cpus from list_cpus
+pub fn list_cpu_epps() -> Vec<String> {
+    list_cpus()
+        .into_iter()
+        .map(|x| get_epp(x.number as usize).unwrap_or_else(|_| "unknown".to_string()))
+        .collect()
+}
+
+/// A running process, as found in /proc
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Process {
+    pub comm: String,
+    /// The arguments joined by spaces, empty for kernel threads
+    pub cmdline: String,
+}
+
+/// Read the name and command line of every running process
+///
+/// Processes that exit while being read are skipped
+pub fn list_processes() -> Vec<Process> {
+    let entries = match read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    entries
+        .flatten()
+        .filter(|e| {
+            e.file_name()
+                .to_string_lossy()
+                .bytes()
+                .all(|b| b.is_ascii_digit())
+        })
+        .filter_map(|e| {
+            let comm = fs::read_to_string(e.path().join("comm")).ok()?;
+            let cmdline = fs::read(e.path().join("cmdline")).unwrap_or_default();
+            Some(Process {
+                comm: comm.trim_end().to_string(),
+                cmdline: String::from_utf8_lossy(&cmdline)
+                    .split('\0')
+                    .filter(|arg| !arg.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            })
+        })
+        .collect()
+}
+
+/// How performant a governor is, used to pick between rules that want different governors
+pub fn governor_rank(gov: &str) -> u8 {
+    match gov {
+        "performance" => 5,
+        "schedutil" => 4,
+        "ondemand" => 3,
+        "conservative" => 2,
+        "userspace" => 1,
+        _ => 0,
+    }
+}
+
+/// The governor wanted by the rules that match a running process, the most performant one wins
+///
+/// A rule matches a process with the same name, or a command line that contains it
+pub fn app_governor<'a>(
+    rules: &'a BTreeMap<String, String>,
+    processes: &[Process],
+) -> Option<(&'a str, &'a str)> {
+    rules
+        .iter()
+        .filter(|(pattern, _)| {
+            processes
+                .iter()
+                .any(|p| p.comm == **pattern || p.cmdline.contains(pattern.as_str()))
+        })
+        .max_by_key(|(_, gov)| governor_rank(gov))
+        .map(|(pattern, gov)| (pattern.as_str(), gov.as_str()))
+}
+
 pub fn read_int(path: &str) -> Result<i32, Error> {
     let mut value = fs::read_to_string(path)?;
 
@@ -415,6 +784,155 @@ mod tests {
         assert_eq!(high, 645);
     }
 
+    fn stat(cpu_name: &str, cpu_sum: u64, cpu_idle: u64) -> ProcStat {
+        ProcStat {
+            cpu_name: cpu_name.to_string(),
+            cpu_sum,
+            cpu_idle,
+        }
+    }
+
+    #[test]
+    fn calculate_utilization_unit_test() {
+        let first = vec![stat("cpu", 1000, 800), stat("cpu0", 500, 450)];
+        let second = vec![
+            stat("cpu", 1200, 850),
+            stat("cpu0", 600, 500),
+            // Came online since the first sample
+            stat("cpu1", 300, 100),
+        ];
+
+        let utilization = calculate_utilization(&first, &second);
+        assert_eq!(utilization.overall, Some(0.75));
+        assert_eq!(utilization.cores, vec![("cpu0".to_string(), 0.5)]);
+
+        // Nothing to compare to on the first sample
+        assert_eq!(calculate_utilization(&[], &second), Utilization::default());
+    }
+
+    #[test]
+    fn usage_between_unit_test() {
+        assert_eq!(
+            usage_between(&stat("cpu0", 100, 50), &stat("cpu0", 200, 50)),
+            Some(1.0)
+        );
+        // No time passed
+        assert_eq!(
+            usage_between(&stat("cpu0", 100, 50), &stat("cpu0", 100, 50)),
+            None
+        );
+        // Counters started over
+        assert_eq!(
+            usage_between(&stat("cpu0", 100, 50), &stat("cpu0", 20, 10)),
+            None
+        );
+        assert_eq!(
+            usage_between(&stat("cpu0", 100, 50), &stat("cpu0", 110, 40)),
+            None
+        );
+    }
+
+    #[test]
+    fn group_by_package_unit_test() {
+        let cpu = |number, cur_freq, gov: &str| CPU {
+            name: format!("cpu{}", number),
+            number,
+            max_freq: 4000000,
+            min_freq: 800000,
+            cur_freq,
+            cur_temp: 50000,
+            cur_usage: 0.0,
+            gov: gov.to_string(),
+        };
+        let cpus = vec![
+            cpu(0, 1000000, "performance"),
+            cpu(1, 3000000, "performance"),
+            cpu(2, 2000000, "performance"),
+            cpu(3, 2000000, "powersave"),
+        ];
+
+        // Cores 0 and 1 are on the first package, and 3 has no package id
+        let packages = group_by_package(&cpus, |c| match c.number {
+            0 | 1 => Some(0),
+            2 => Some(1),
+            _ => None,
+        });
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].cores, vec![0, 1, 3]);
+        assert_eq!(packages[0].governor, "mixed");
+        assert_eq!(packages[1].cores, vec![2]);
+        assert_eq!(packages[1].avg_freq_mhz, 2000.0);
+        assert_eq!(packages[1].governor, "performance");
+    }
+
+    #[test]
+    fn freq_window_unit_test() {
+        let cpu = |number: i8, cur_freq| CPU {
+            name: format!("cpu{}", number),
+            number,
+            max_freq: 4000000,
+            min_freq: 800000,
+            cur_freq,
+            cur_temp: 0,
+            cur_usage: 0.0,
+            gov: "powersave".to_string(),
+        };
+
+        let mut window = FreqWindow::new(3);
+        assert_eq!(window.mean(), None);
+
+        window.push(&[cpu(0, 1000), cpu(1, 3000)]);
+        assert_eq!(window.mean(), Some(2000.0));
+
+        window.push(&[cpu(0, 2000), cpu(1, 3000)]);
+        window.push(&[cpu(0, 3000), cpu(1, 3000)]);
+        // The first reading falls out of the window
+        window.push(&[cpu(0, 4000), cpu(1, 3000)]);
+        assert_eq!(window.samples(), 3);
+        assert_eq!(window.core_mean(0), Some(3000.0));
+        assert_eq!(window.core_mean(2), None);
+
+        // Core 0 going offline keeps the readings of core 1 on core 1
+        window.push(&[cpu(1, 1000)]);
+        assert_eq!(window.core_mean(0), None);
+        assert_eq!(window.core_mean(1), Some(7000.0 / 3.0));
+
+        let mut off = FreqWindow::new(0);
+        off.push(&[cpu(0, 1000)]);
+        assert_eq!(off.mean(), None);
+    }
+
+    #[test]
+    fn app_governor_unit_test() {
+        let process = |comm: &str, cmdline: &str| Process {
+            comm: comm.to_string(),
+            cmdline: cmdline.to_string(),
+        };
+        let processes = vec![
+            process("bash", "/bin/bash"),
+            process("rustc", "rustc --crate-name acs"),
+            process("wine64", "/usr/bin/wine64 C:\\Games\\game.exe"),
+        ];
+
+        let mut rules = BTreeMap::new();
+        rules.insert("firefox".to_string(), "powersave".to_string());
+        assert_eq!(app_governor(&rules, &processes), None);
+
+        rules.insert("bash".to_string(), "powersave".to_string());
+        assert_eq!(
+            app_governor(&rules, &processes),
+            Some(("bash", "powersave"))
+        );
+
+        // Matched by part of the command line, and more performant than powersave
+        rules.insert("game.exe".to_string(), "performance".to_string());
+        rules.insert("rustc".to_string(), "schedutil".to_string());
+        assert_eq!(
+            app_governor(&rules, &processes),
+            Some(("game.exe", "performance"))
+        );
+    }
+
     #[test]
     fn test_parse_proc_stat_file() {
         let cpu_percent = get_cpu_percent(None).parse::<f32>().unwrap();
@@ -461,6 +979,21 @@ microcode	: 0xea
         Ok(())
     }
 
+    #[test]
+    fn capabilities_unit_test() {
+        let cpu_info = "processor\t: 0\nflags\t\t: fpu vme est tm2 hwp hwp_notify\n\
+                        processor\t: 1\nflags\t\t: fpu\n";
+        let flags = parse_cpu_flags(cpu_info);
+        assert!(flags.contains(&"hwp".to_string()));
+        assert_eq!(flags.len(), 6);
+        assert!(parse_cpu_flags("processor\t: 0\n").is_empty());
+
+        // Two packages with two threads on each core
+        let topology = [(0, 0), (0, 0), (0, 1), (0, 1), (1, 0), (1, 0)];
+        assert_eq!(count_cores(&topology), 3);
+        assert_eq!(count_cores(&[]), 0);
+    }
+
     #[test]
     fn check_cpu_name_unit_test() -> Result<(), Error> {
         assert_eq!(type_of(check_cpu_name()?), type_of(String::new()));
@@ -0,0 +1,39 @@
+//! A temporary directory for the tests that need files to read, removed again even when the test
+//! fails
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+pub struct TestDir {
+    path: PathBuf,
+}
+
+impl TestDir {
+    /// Create an empty directory named after the test, acs-<name>-<pid> in the temp dir
+    pub fn new(name: &str) -> TestDir {
+        let path = std::env::temp_dir().join(format!("acs-{}-{}", name, std::process::id()));
+        fs::remove_dir_all(&path).ok();
+        fs::create_dir_all(&path).expect("Could not create the test directory");
+        TestDir { path }
+    }
+}
+
+impl Deref for TestDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for TestDir {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.path).ok();
+    }
+}
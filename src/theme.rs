@@ -0,0 +1,130 @@
+#![forbid(unsafe_code)]
+//! Colors of the terminal output
+//!
+//! Everything colored goes through `paint` and `bold` so a theme can swap colors that are hard
+//! to read on a light background, and color can be turned off altogether. Color is off when
+//! stdout is not a terminal, when `NO_COLOR` is set (https://no-color.org), or with `--no-color`.
+use crate::error::Error;
+use efcl::Color;
+use nix::unistd::isatty;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// Kept on for the library so output only changes once `detect` is called
+static COLOR_ENABLED: AtomicBool = AtomicBool::new(true);
+static THEME: AtomicU8 = AtomicU8::new(Theme::Dark as u8);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Theme {
+    /// The colors as they are, for dark backgrounds
+    #[default]
+    Dark = 0,
+    /// Dark colors in place of yellow, light gray, and cyan
+    Light = 1,
+    /// Bold text in place of every color
+    Mono = 2,
+}
+
+impl Theme {
+    /// Pick the theme from the `theme` config value, dark when there is none
+    pub fn from_config(value: Option<&str>) -> Result<Theme, Error> {
+        match value {
+            None | Some("dark") => Ok(Theme::Dark),
+            Some("light") => Ok(Theme::Light),
+            Some("mono") => Ok(Theme::Mono),
+            Some(other) => Err(Error::InvalidValue(
+                other.to_string(),
+                vec!["dark".to_string(), "light".to_string(), "mono".to_string()],
+            )),
+        }
+    }
+
+    /// The color used in place of the one asked for, None for bold text without color
+    pub fn map(self, color: Color) -> Option<Color> {
+        match (self, color) {
+            (Theme::Dark, color) => Some(color),
+            (Theme::Light, Color::YELLOW) => Some(Color::PURPLE),
+            (Theme::Light, Color::LIGHTGRAY) => Some(Color::BLACK),
+            (Theme::Light, Color::CYAN) => Some(Color::BLUE),
+            (Theme::Light, color) => Some(color),
+            (Theme::Mono, _) => None,
+        }
+    }
+}
+
+/// If color should be used, false when NO_COLOR is set to anything other than an empty string
+pub fn color_wanted(no_color_flag: bool, no_color_env: Option<&str>, is_tty: bool) -> bool {
+    !no_color_flag && no_color_env.is_none_or(str::is_empty) && is_tty
+}
+
+/// Turn color off for NO_COLOR, `--no-color`, or stdout not being a terminal
+///
+/// Called before the arguments get parsed so warnings printed while starting up follow it too
+pub fn detect() {
+    let flag = std::env::args().any(|arg| arg == "--no-color");
+    let env = std::env::var("NO_COLOR").ok();
+    set_color(color_wanted(
+        flag,
+        env.as_deref(),
+        isatty(1).unwrap_or(false),
+    ));
+}
+
+pub fn set_color(enabled: bool) {
+    COLOR_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn set_theme(theme: Theme) {
+    THEME.store(theme as u8, Ordering::Relaxed);
+}
+
+pub fn theme() -> Theme {
+    match THEME.load(Ordering::Relaxed) {
+        1 => Theme::Light,
+        2 => Theme::Mono,
+        _ => Theme::Dark,
+    }
+}
+
+/// Color text with the theme's version of a color, or make it bold when the theme has no colors
+pub fn paint(color: Color, text: &str) -> String {
+    if !COLOR_ENABLED.load(Ordering::Relaxed) {
+        return text.to_string();
+    }
+    match theme().map(color) {
+        Some(color) => efcl::color!(color, text),
+        None => efcl::bold!(text),
+    }
+}
+
+pub fn bold(text: &str) -> String {
+    if !COLOR_ENABLED.load(Ordering::Relaxed) {
+        return text.to_string();
+    }
+    efcl::bold!(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_wanted_unit_test() {
+        assert!(color_wanted(false, None, true));
+        assert!(color_wanted(false, Some(""), true));
+        assert!(!color_wanted(false, Some("1"), true));
+        assert!(!color_wanted(true, None, true));
+        assert!(!color_wanted(false, None, false));
+    }
+
+    #[test]
+    fn theme_unit_test() {
+        assert_eq!(Theme::from_config(None).ok(), Some(Theme::Dark));
+        assert_eq!(Theme::from_config(Some("light")).ok(), Some(Theme::Light));
+        assert!(Theme::from_config(Some("solarized")).is_err());
+
+        assert_eq!(Theme::Dark.map(Color::YELLOW).unwrap().as_str(), "33");
+        assert_eq!(Theme::Light.map(Color::YELLOW).unwrap().as_str(), "35");
+        assert_eq!(Theme::Light.map(Color::RED).unwrap().as_str(), "31");
+        assert!(Theme::Mono.map(Color::RED).is_none());
+    }
+}
@@ -1,14 +1,23 @@
 #![forbid(unsafe_code)]
-use super::system::{read_int, read_str};
+use super::system::{read_core_id, read_int, read_package_id, read_str};
+use crate::cpu::CPU;
 use crate::error::Error;
-use efcl::{color, Color};
+use crate::theme::paint;
+use efcl::Color;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fs::read_dir;
+use std::path::{Path, PathBuf};
 
 const THERMAL_ZONE_DIR: &str = "/sys/class/thermal/";
+const HWMON_DIR: &str = "/sys/class/hwmon/";
 
-#[derive(Debug)]
+/// The throttle counters of a core, only some cpus, e.g. Intel, have them
+pub const THROTTLE_COUNTERS: [&str; 2] = ["core_throttle_count", "package_throttle_count"];
+
+#[derive(Debug, Serialize)]
 pub struct ThermalZone {
     pub name: String,
     pub path: String,
@@ -36,7 +45,7 @@ impl Display for ThermalZone {
         write!(
             f,
             "{} {}{} {}",
-            color!(
+            paint(
                 if self.enabled {
                     Color::GREEN
                 } else {
@@ -44,30 +53,350 @@ impl Display for ThermalZone {
                 },
                 &self.name
             ),
-            color!(Color::YELLOW, (self.temp / 1000).to_string().as_str()),
-            color!(Color::YELLOW, "C°"),
+            paint(Color::YELLOW, (self.temp / 1000).to_string().as_str()),
+            paint(Color::YELLOW, "C°"),
             self.path
         )
     }
 }
 
+/// Read every thermal zone with its type name and temperature
+///
+/// Zones whose temperature can not be read right now (e.g. a sleeping NVMe drive) are skipped
 pub fn read_thermal_zones() -> Result<Vec<ThermalZone>, Error> {
     let mut zones = Vec::<ThermalZone>::new();
 
-    for a in read_dir(THERMAL_ZONE_DIR).expect("Could not read thermal directory") {
+    for a in read_dir(THERMAL_ZONE_DIR)? {
         let path_string: String = format!("{}", a?.path().to_string_lossy());
         if !path_string.starts_with(&[THERMAL_ZONE_DIR, "thermal_zone"].concat()) {
             continue;
         }
 
+        let temp = match read_int(&[&path_string, "/temp"].concat()) {
+            Ok(temp) => temp,
+            Err(_) => continue,
+        };
+
         let zone = ThermalZone {
-            temp: read_int(&[&path_string, "/temp"].concat())?,
+            temp,
             name: read_str(&[&path_string, "/type"].concat())?,
-            enabled: read_str(&[&path_string, "/mode"].concat())? == "enabled",
+            // Zones without a mode file can not be turned off
+            enabled: read_str(&[&path_string, "/mode"].concat())
+                .map(|mode| mode == "enabled")
+                .unwrap_or(true),
             path: path_string,
         };
 
         zones.push(zone);
     }
+
+    // read_dir has no order, sort so thermal_zone2 comes before thermal_zone10
+    zones.sort_by_key(|zone| zone_number(&zone.path));
     Ok(zones)
 }
+
+fn zone_number(path: &str) -> usize {
+    path.trim_start_matches(&[THERMAL_ZONE_DIR, "thermal_zone"].concat())
+        .parse()
+        .unwrap_or(usize::MAX)
+}
+
+/// Find the highest temperature of the zones with one of the names
+///
+/// The name "all" matches every zone
+pub fn zone_temp(zones: &[ThermalZone], names: &[String]) -> Option<i32> {
+    zones
+        .iter()
+        .filter(|zone| names.iter().any(|name| name == "all" || *name == zone.name))
+        .map(|zone| zone.temp)
+        .max()
+}
+
+/// Read a thermal throttle counter of a core, e.g. "core_throttle_count" of "cpu0"
+pub fn read_throttle_counter(cpu: &str, counter: &str) -> Option<u64> {
+    read_int(&format!(
+        "/sys/devices/system/cpu/{}/thermal_throttle/{}",
+        cpu, counter
+    ))
+    .ok()
+    .map(|count| count as u64)
+}
+
+/// Follows the core throttle counter of each core between polls, a counter that went up since
+/// the last poll means the core is throttling right now
+#[derive(Debug, Default)]
+pub struct ThrottleTracker {
+    counts: HashMap<String, u64>,
+}
+
+impl ThrottleTracker {
+    /// Keep the new count of a core, true if it went up since the last one
+    ///
+    /// The first count of a core is only kept, the counter has been going since boot
+    pub fn observe(&mut self, cpu: &str, count: u64) -> bool {
+        match self.counts.insert(cpu.to_string(), count) {
+            Some(last) => count > last,
+            None => false,
+        }
+    }
+
+    /// Read the counter of every core, the names of the ones that throttled since the last poll
+    pub fn update(&mut self, cpus: &[CPU]) -> Vec<String> {
+        cpus.iter()
+            .filter(
+                |cpu| match read_throttle_counter(&cpu.name, THROTTLE_COUNTERS[0]) {
+                    Some(count) => self.observe(&cpu.name, count),
+                    None => false,
+                },
+            )
+            .map(|cpu| cpu.name.clone())
+            .collect()
+    }
+
+    /// If any core had a counter to read
+    pub fn supported(&self) -> bool {
+        !self.counts.is_empty()
+    }
+}
+
+/// How the daemon steps the max frequency with active_throttle on, temperatures in C
+#[derive(Debug, Clone, Copy)]
+pub struct ThrottleSteps {
+    pub throttle_temp: i8,
+    pub recover_temp: i8,
+    pub step_khz: i32,
+}
+
+/// The max frequency cap in kHz after a poll at the temperature, None once it is lifted
+///
+/// Over throttle_temp the cap goes down a step, to no lower than floor. Under recover_temp it goes
+/// back up a step and is lifted once it reaches ceiling. In between the two it is kept, so the
+/// cap does not go up and down on every small change.
+pub fn next_freq_cap(
+    cap: Option<i32>,
+    temp: i8,
+    steps: &ThrottleSteps,
+    floor: i32,
+    ceiling: i32,
+) -> Option<i32> {
+    if temp > steps.throttle_temp {
+        let current = cap.unwrap_or(ceiling);
+        return Some((current - steps.step_khz).max(floor).min(current));
+    }
+
+    match cap {
+        Some(cap) if temp < steps.recover_temp => {
+            let raised = cap + steps.step_khz;
+            (raised < ceiling).then_some(raised)
+        }
+        cap => cap,
+    }
+}
+
+/// The temperatures of a coretemp hwmon device, there is one for each package
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CoreTemps {
+    /// From the "Package id 0" label
+    pub package: Option<u32>,
+    pub package_temp: Option<i32>,
+    /// The temperature of each physical core by its core id, from labels like "Core 0"
+    pub cores: HashMap<u32, i32>,
+}
+
+/// Read the labeled temperatures, the tempN_label and tempN_input files, of a coretemp device
+pub fn parse_coretemp(dir: &Path) -> CoreTemps {
+    let mut temps = CoreTemps::default();
+    let entries = match read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return temps,
+    };
+
+    for entry in entries.flatten() {
+        let file = entry.file_name().to_string_lossy().to_string();
+        let sensor = match file.strip_suffix("_label") {
+            Some(sensor) if sensor.starts_with("temp") => sensor.to_string(),
+            _ => continue,
+        };
+        let (label, temp) = match (
+            read_str(&entry.path().to_string_lossy()),
+            read_int(&dir.join(format!("{}_input", sensor)).to_string_lossy()),
+        ) {
+            (Ok(label), Ok(temp)) => (label, temp),
+            _ => continue,
+        };
+
+        if let Some(core) = label.strip_prefix("Core ") {
+            if let Ok(core) = core.trim().parse() {
+                temps.cores.insert(core, temp);
+            }
+        } else if let Some(package) = label.strip_prefix("Package id ") {
+            temps.package = package.trim().parse().ok();
+            temps.package_temp = Some(temp);
+        }
+    }
+    temps
+}
+
+/// The hwmon devices of the coretemp driver, none when it is not loaded, e.g. on AMD
+fn coretemp_dirs() -> Vec<PathBuf> {
+    let entries = match read_dir(HWMON_DIR) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            read_str(&path.join("name").to_string_lossy()).is_ok_and(|name| name == "coretemp")
+        })
+        .collect()
+}
+
+/// The temperature of a physical core from the coretemp devices
+///
+/// Only one device is used without knowing the package, a core without a temperature of its own
+/// gets the one of its package
+pub fn core_temp(devices: &[CoreTemps], package: Option<u32>, core: Option<u32>) -> Option<i32> {
+    let device = match package {
+        Some(package) => devices.iter().find(|d| d.package == Some(package)),
+        None if devices.len() == 1 => devices.first(),
+        None => None,
+    }?;
+    core.and_then(|core| device.cores.get(&core).copied())
+        .or(device.package_temp)
+}
+
+/// The temperature of each of the cores from coretemp, in the same order
+///
+/// None when coretemp is not loaded, a core coretemp has nothing for keeps the temperature of its
+/// thermal zone
+pub fn read_core_temps(cpus: &[CPU]) -> Option<Vec<i32>> {
+    let devices: Vec<CoreTemps> = coretemp_dirs().iter().map(|d| parse_coretemp(d)).collect();
+    if devices.is_empty() {
+        return None;
+    }
+
+    Some(
+        cpus.iter()
+            .map(|cpu| {
+                let number = cpu.number as usize;
+                core_temp(
+                    &devices,
+                    read_package_id(number).ok(),
+                    read_core_id(number).ok(),
+                )
+                .unwrap_or(cpu.cur_temp)
+            })
+            .collect(),
+    )
+}
+
+/// Use the per core temperatures from coretemp where it is loaded
+pub fn apply_core_temps(cpus: &mut [CPU]) {
+    if let Some(temps) = read_core_temps(cpus) {
+        for (cpu, temp) in cpus.iter_mut().zip(temps) {
+            cpu.cur_temp = temp;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    fn zone(name: &str, temp: i32) -> ThermalZone {
+        ThermalZone {
+            name: name.to_string(),
+            temp,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn zone_temp_unit_test() {
+        let zones = vec![
+            zone("acpitz", 40000),
+            zone("x86_pkg_temp", 65000),
+            zone("nvme", 45000),
+        ];
+
+        let names = |n: &[&str]| n.iter().map(|x| x.to_string()).collect::<Vec<String>>();
+        assert_eq!(zone_temp(&zones, &names(&["x86_pkg_temp"])), Some(65000));
+        assert_eq!(zone_temp(&zones, &names(&["acpitz", "nvme"])), Some(45000));
+        assert_eq!(zone_temp(&zones, &names(&["all"])), Some(65000));
+        assert_eq!(zone_temp(&zones, &names(&["iwlwifi"])), None);
+    }
+
+    #[test]
+    fn throttle_tracker_unit_test() {
+        let mut tracker = ThrottleTracker::default();
+        assert!(!tracker.supported());
+
+        // The count since boot is not throttling by itself
+        assert!(!tracker.observe("cpu0", 120));
+        assert!(!tracker.observe("cpu1", 0));
+        assert!(tracker.supported());
+
+        assert!(tracker.observe("cpu0", 125));
+        assert!(!tracker.observe("cpu1", 0));
+        assert!(!tracker.observe("cpu0", 125));
+    }
+
+    #[test]
+    fn next_freq_cap_unit_test() {
+        let steps = ThrottleSteps {
+            throttle_temp: 90,
+            recover_temp: 80,
+            step_khz: 200_000,
+        };
+        let next = |cap, temp| next_freq_cap(cap, temp, &steps, 800_000, 3_000_000);
+
+        assert_eq!(next(None, 85), None);
+        assert_eq!(next(None, 95), Some(2_800_000));
+        assert_eq!(next(Some(2_800_000), 95), Some(2_600_000));
+        assert_eq!(next(Some(900_000), 95), Some(800_000));
+
+        // Kept between the thresholds, raised under the recovery one and lifted at the ceiling
+        assert_eq!(next(Some(2_600_000), 85), Some(2_600_000));
+        assert_eq!(next(Some(2_600_000), 75), Some(2_800_000));
+        assert_eq!(next(Some(2_800_000), 75), None);
+    }
+
+    #[test]
+    fn coretemp_unit_test() -> Result<(), Error> {
+        let dir = TestDir::new("coretemp");
+        for (sensor, label, temp) in [
+            ("temp1", "Package id 1", "61000"),
+            ("temp2", "Core 0", "58000"),
+            ("temp6", "Core 4", "63000"),
+        ] {
+            std::fs::write(
+                dir.join(format!("{}_label", sensor)),
+                format!("{}\n", label),
+            )?;
+            std::fs::write(dir.join(format!("{}_input", sensor)), format!("{}\n", temp))?;
+        }
+        // A label without an input is skipped
+        std::fs::write(dir.join("temp7_label"), "Core 5\n")?;
+
+        let device = parse_coretemp(&dir);
+        assert_eq!(device.package, Some(1));
+        assert_eq!(device.package_temp, Some(61000));
+        assert_eq!(device.cores, HashMap::from([(0, 58000), (4, 63000)]));
+
+        let devices = [device];
+        assert_eq!(core_temp(&devices, Some(1), Some(4)), Some(63000));
+        // A core without a sensor of its own gets the package temperature
+        assert_eq!(core_temp(&devices, Some(1), Some(2)), Some(61000));
+        assert_eq!(core_temp(&devices, None, Some(0)), Some(58000));
+        assert_eq!(core_temp(&devices, Some(0), Some(0)), None);
+        Ok(())
+    }
+
+    #[test]
+    fn zone_number_unit_test() {
+        assert_eq!(zone_number("/sys/class/thermal/thermal_zone10"), 10);
+        assert_eq!(zone_number("/sys/class/thermal/thermal_zone2"), 2);
+    }
+}
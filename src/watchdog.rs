@@ -0,0 +1,179 @@
+#![forbid(unsafe_code)]
+//! Catch a daemon loop that stopped going around, e.g. on a sysfs read that never returns
+//!
+//! The loop beats a heartbeat every iteration and a separate thread checks how old it is. The
+//! thread does not take the daemon's lock since a stuck loop would be holding it, so a stall is
+//! written to stderr, which ends up in the journal under systemd. With `WatchdogSec` set in the
+//! unit, the thread also pings systemd while the heartbeat is fresh so systemd restarts acs once
+//! the loop stops.
+use crate::error::Error;
+use crate::{print_error, warn_user};
+use std::env;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::process::exit;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The shortest time between two checks of the heartbeat
+const MIN_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// When the daemon loop last finished an iteration
+pub struct Heartbeat {
+    start: Instant,
+    last_ms: AtomicU64,
+}
+
+impl Heartbeat {
+    pub fn new() -> Arc<Heartbeat> {
+        Arc::new(Heartbeat {
+            start: Instant::now(),
+            last_ms: AtomicU64::new(0),
+        })
+    }
+
+    pub fn beat(&self) {
+        self.last_ms
+            .store(self.start.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Time since the last beat
+    pub fn age(&self) -> Duration {
+        self.start
+            .elapsed()
+            .saturating_sub(Duration::from_millis(self.last_ms.load(Ordering::Relaxed)))
+    }
+}
+
+/// Send a state like `READY=1` to systemd, false when acs was not started by systemd
+pub fn sd_notify(state: &str) -> Result<bool, Error> {
+    let path = match env::var("NOTIFY_SOCKET") {
+        Ok(path) => path,
+        Err(_) => return Ok(false),
+    };
+
+    // A leading @ is a socket in the abstract namespace
+    let address = match path.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name.as_bytes())?,
+        None => SocketAddr::from_pathname(&path)?,
+    };
+
+    let socket = UnixDatagram::unbound()?;
+    socket.send_to_addr(state.as_bytes(), &address)?;
+    Ok(true)
+}
+
+/// How often systemd wants to be pinged, half of `WATCHDOG_USEC` like sd_watchdog_enabled
+/// suggests, None when the watchdog is off or meant for another process
+pub fn parse_watchdog_usec(
+    usec: Option<&str>,
+    pid: Option<&str>,
+    own_pid: u32,
+) -> Option<Duration> {
+    if let Some(pid) = pid {
+        if pid.parse::<u32>().ok()? != own_pid {
+            return None;
+        }
+    }
+
+    match usec?.parse::<u64>().ok()? {
+        0 => None,
+        usec => Some(Duration::from_micros(usec) / 2),
+    }
+}
+
+/// The ping interval of systemd's watchdog for this process
+pub fn systemd_watchdog_interval() -> Option<Duration> {
+    parse_watchdog_usec(
+        env::var("WATCHDOG_USEC").ok().as_deref(),
+        env::var("WATCHDOG_PID").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+/// Start the thread that watches the heartbeat
+///
+/// A heartbeat older than `timeout` gets reported once, and with `exit_on_stall` acs exits with
+/// 1 so a service manager can start it again. A timeout of 0 only pings systemd.
+pub fn start(heartbeat: Arc<Heartbeat>, timeout: Duration, exit_on_stall: bool) {
+    let systemd = systemd_watchdog_interval();
+    if timeout.is_zero() && systemd.is_none() {
+        return;
+    }
+
+    // Without a timeout of its own the heartbeat only has to be fresh enough for systemd
+    let stale_after = match (timeout.is_zero(), systemd) {
+        (true, Some(interval)) => interval * 2,
+        _ => timeout,
+    };
+    let check_interval = [Some(stale_after / 4), systemd]
+        .into_iter()
+        .flatten()
+        .min()
+        .unwrap_or(stale_after)
+        .max(MIN_CHECK_INTERVAL);
+
+    thread::spawn(move || {
+        let mut stalled = false;
+        loop {
+            thread::sleep(check_interval);
+            let age = heartbeat.age();
+
+            if age > stale_after {
+                if !stalled && !timeout.is_zero() {
+                    print_error!(format!(
+                        "The daemon loop has not finished an iteration in {}s",
+                        age.as_secs()
+                    ));
+                    if exit_on_stall {
+                        exit(1);
+                    }
+                }
+                stalled = true;
+                continue;
+            }
+
+            if stalled && !timeout.is_zero() {
+                warn_user!("The daemon loop is running again");
+            }
+            stalled = false;
+
+            if systemd.is_some() {
+                sd_notify("WATCHDOG=1").ok();
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heartbeat_unit_test() {
+        let heartbeat = Heartbeat::new();
+        thread::sleep(Duration::from_millis(20));
+        assert!(heartbeat.age() >= Duration::from_millis(20));
+
+        heartbeat.beat();
+        assert!(heartbeat.age() < Duration::from_millis(20));
+    }
+
+    #[test]
+    fn parse_watchdog_usec_unit_test() {
+        assert_eq!(
+            parse_watchdog_usec(Some("30000000"), None, 10),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            parse_watchdog_usec(Some("30000000"), Some("10"), 10),
+            Some(Duration::from_secs(15))
+        );
+        // Meant for another process
+        assert_eq!(parse_watchdog_usec(Some("30000000"), Some("11"), 10), None);
+        assert_eq!(parse_watchdog_usec(Some("0"), None, 10), None);
+        assert_eq!(parse_watchdog_usec(None, None, 10), None);
+    }
+}